@@ -2,8 +2,14 @@ use crate::{
     constants::{COLUMN_STAGGER, MAX_ROW, MIN_ROW, ORTHO, ROW_STAGGER},
     error::{KbOptError, Result},
 };
+use chrono::NaiveDate;
+use key_id::PhysicalLayout;
+use keylog_crypt::Secret;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 /// メイン設定構造体
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -39,6 +45,36 @@ pub struct SolverConfig {
     pub align_right_edge: bool, // 右端揃え
     #[serde(default)]
     pub solution_threshold: f64, // 解の閾値（デフォルト0.5）
+    #[serde(default)]
+    pub layout: Option<String>, // "US" | "ISO" | "JIS"（デフォルトUS）。ISO・JIS固有のキーも配置する
+
+    // データの期間 (ファイル名の日付で絞り込み、両端を含む)
+    #[serde(default)]
+    pub date_from: Option<NaiveDate>,
+    #[serde(default)]
+    pub date_to: Option<NaiveDate>,
+
+    // キーリピート (押しっぱなしによる自動入力) をKeyFreqに含めるか
+    #[serde(default)]
+    pub include_repeats: bool,
+
+    // 最多キーがキー入力のこの割合を超えるセッションを除外 (WASDを連打するゲームなど)
+    #[serde(default)]
+    pub max_session_key_share: Option<f64>,
+
+    // 指定した入力デバイスのキー入力のみ使用 (keylog_devices_*.csv、key_loggerの--per-deviceで記録)
+    #[serde(default)]
+    pub devices: Option<Vec<String>>,
+
+    // csv_dirの代わりにkey_loggerの--format sqliteで記録したkeylog.sqliteから読み込む
+    #[serde(default)]
+    pub database: Option<String>,
+
+    // 暗号化されたCSV (*.csv.enc) の鍵ファイルまたはパスフレーズファイル (どちらか一方)
+    #[serde(default)]
+    pub encryption_key_file: Option<String>,
+    #[serde(default)]
+    pub encryption_passphrase_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -92,6 +128,15 @@ impl Default for Config {
                 align_left_edge: false,
                 align_right_edge: false,
                 solution_threshold: 0.5,
+                layout: None,
+                date_from: None,
+                date_to: None,
+                include_repeats: false,
+                max_session_key_share: None,
+                devices: None,
+                database: None,
+                encryption_key_file: None,
+                encryption_passphrase_file: None,
             },
             v1: None,
             v2: None,
@@ -134,6 +179,73 @@ impl Config {
             )));
         }
 
+        // 物理配列の検証
+        if let Some(ref layout) = self.solver.layout
+            && PhysicalLayout::from_label(layout).is_none()
+        {
+            return Err(KbOptError::Config(format!(
+                "Invalid layout: {}. Must be 'US', 'ISO', or 'JIS'",
+                layout
+            )));
+        }
+
+        // 期間の検証
+        if let (Some(from), Some(to)) = (self.solver.date_from, self.solver.date_to)
+            && from > to
+        {
+            return Err(KbOptError::Config(format!(
+                "date_from ({}) must not be after date_to ({})",
+                from, to
+            )));
+        }
+
+        // セッション除外の閾値の検証
+        if let Some(share) = self.solver.max_session_key_share
+            && !(share > 0.0 && share <= 1.0)
+        {
+            return Err(KbOptError::Config(format!(
+                "max_session_key_share must be in (0, 1], got {}",
+                share
+            )));
+        }
+
+        // デバイス指定の検証
+        if let Some(ref devices) = self.solver.devices {
+            if devices.iter().all(|name| name.trim().is_empty()) {
+                return Err(KbOptError::Config(
+                    "devices must name at least one input device".to_string(),
+                ));
+            }
+            // セッションはデバイスを区別しないため、セッション除外とは併用できない
+            if self.solver.max_session_key_share.is_some() {
+                return Err(KbOptError::Config(
+                    "devices cannot be combined with max_session_key_share; sessions are not recorded per device"
+                        .to_string(),
+                ));
+            }
+        }
+
+        // 暗号化の鍵の検証
+        if self.solver.encryption_key_file.is_some()
+            && self.solver.encryption_passphrase_file.is_some()
+        {
+            return Err(KbOptError::Config(
+                "Set either encryption_key_file or encryption_passphrase_file, not both"
+                    .to_string(),
+            ));
+        }
+
+        // SQLiteストアは暗号化されない
+        if self.solver.database.is_some()
+            && (self.solver.encryption_key_file.is_some()
+                || self.solver.encryption_passphrase_file.is_some())
+        {
+            return Err(KbOptError::Config(
+                "database cannot be combined with an encryption key; the SQLite store is never encrypted"
+                    .to_string(),
+            ));
+        }
+
         // バージョンの検証
         match self.solver.version.as_str() {
             "v1" => self.validate_v1_config()?,
@@ -161,6 +273,29 @@ impl Config {
         Ok(())
     }
 
+    /// キーボードの物理配列 (未設定ならUS)
+    pub fn physical_layout(&self) -> PhysicalLayout {
+        self.solver
+            .layout
+            .as_deref()
+            .and_then(PhysicalLayout::from_label)
+            .unwrap_or_default()
+    }
+
+    /// 暗号化されたCSVを読むための鍵 (未設定ならNone)
+    pub fn encryption_secret(&self) -> Result<Option<Secret>> {
+        let solver = &self.solver;
+        let secret = match (
+            &solver.encryption_key_file,
+            &solver.encryption_passphrase_file,
+        ) {
+            (Some(path), _) => Secret::from_key_file(Path::new(path))?,
+            (None, Some(path)) => Secret::from_passphrase_file(Path::new(path))?,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(secret))
+    }
+
     fn validate_v1_config(&self) -> Result<()> {
         let solver_config = &self.solver;
         // 単一レイヤーなので、Fキーを入れる場合は、十分な行数が必要 (Fキー行 + 数字行)
@@ -30,6 +30,41 @@ pub const U2PX: f64 = 60.0; // u -> px (for visualization)
 /// Expected headers in CSV files
 pub const EXPECTED_KEY_HEADER: &str = "Key"; // Key column header
 pub const EXPECTED_COUNT_HEADER: &str = "Count"; // Count column header
+pub const REPEAT_HEADER: &str = "Repeat"; // Optional auto-repeat count column header
+pub const EXPECTED_FROM_HEADER: &str = "From"; // Bigram source key column header
+pub const EXPECTED_TO_HEADER: &str = "To"; // Bigram target key column header
+pub const EXPECTED_MODIFIERS_HEADER: &str = "Modifiers"; // Chord modifier set column header
+pub const EXPECTED_DEVICE_HEADER: &str = "Device"; // Per-device input device column header
+
+/// File name prefixes written by key_logger
+pub const BIGRAM_FILE_PREFIX: &str = "keylog_bigrams_"; // From,To,Count
+pub const TIMING_FILE_PREFIX: &str = "keylog_timing_"; // Metric,Key,LowerMs,UpperMs,Count
+pub const CHORD_FILE_PREFIX: &str = "keylog_chords_"; // Modifiers,Key,Count
+pub const SESSION_FILE_PREFIX: &str = "keylog_sessions_"; // Session,Start,End,...,Key,Count
+pub const DEVICE_FILE_PREFIX: &str = "keylog_devices_"; // Device,Key,Count,Repeat
+pub const COUNT_FILE_PREFIX: &str = "keylog_"; // Key,Count (default and rotated names)
+/// Auxiliary files that share the csv directory but are not `Key,Count` data
+pub const AUXILIARY_FILE_PREFIXES: &[&str] = &[
+    BIGRAM_FILE_PREFIX,
+    TIMING_FILE_PREFIX,
+    CHORD_FILE_PREFIX,
+    SESSION_FILE_PREFIX,
+    DEVICE_FILE_PREFIX,
+];
+/// Date at the start of a file name after its prefix, e.g. `keylog_2025-07-27.csv`
+pub const FILE_DATE_FORMAT: &str = "%Y-%m-%d";
+pub const FILE_DATE_LEN: usize = 10;
+
+/// Metadata sidecar written by key_logger next to each count CSV
+pub const METADATA_FILE_SUFFIX: &str = ".meta.json";
+pub const SUPPORTED_METADATA_SCHEMA: u32 = 1; // newest schema this analyzer understands
+
+/// Store written by key_logger `--format sqlite` (`PRAGMA user_version`)
+pub const SUPPORTED_DATABASE_SCHEMA: i64 = 1; // newest schema this analyzer understands
+
+/// Sessions shorter than this are never dropped as outliers; a handful of presses is
+/// dominated by one key by chance
+pub const MIN_OUTLIER_SESSION_KEYSTROKES: u64 = 50;
 
 /// Visualization
 pub const MARGIN: f64 = 24.0; // margin [px]
@@ -1,12 +1,30 @@
 use crate::{
     config::Config,
-    constants::{EXPECTED_COUNT_HEADER, EXPECTED_KEY_HEADER},
+    constants::{
+        AUXILIARY_FILE_PREFIXES, BIGRAM_FILE_PREFIX, CHORD_FILE_PREFIX, COUNT_FILE_PREFIX,
+        DEVICE_FILE_PREFIX, EXPECTED_COUNT_HEADER, EXPECTED_DEVICE_HEADER, EXPECTED_FROM_HEADER,
+        EXPECTED_KEY_HEADER, EXPECTED_MODIFIERS_HEADER, EXPECTED_TO_HEADER, FILE_DATE_FORMAT,
+        FILE_DATE_LEN, METADATA_FILE_SUFFIX, MIN_OUTLIER_SESSION_KEYSTROKES, REPEAT_HEADER,
+        SESSION_FILE_PREFIX, SUPPORTED_METADATA_SCHEMA,
+    },
     error::{KbOptError, Result},
     keys::{KeyId, parse_key_label},
+    sqlite_reader::{
+        read_bigram_freq_db, read_chord_freq_db, read_datasets_db, read_key_freq_db,
+        read_sessions_db,
+    },
 };
 
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use csv::{ReaderBuilder, StringRecord, Trim};
-use std::{collections::HashMap, io::Read, path::Path};
+use key_id::PhysicalLayout;
+use keylog_crypt::{ENCRYPTED_EXTENSION, Secret};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct KeyFreq {
@@ -58,6 +76,19 @@ impl KeyFreq {
         self.total = self.raw_counts.values().copied().sum();
     }
 
+    /// Removes the counts of `other`, e.g. a dropped session, never going below zero
+    pub fn subtract(&mut self, other: &KeyFreq) {
+        for (k, &v) in &other.raw_counts {
+            if let Some(count) = self.raw_counts.get_mut(k) {
+                *count = count.saturating_sub(v);
+                if *count == 0 {
+                    self.raw_counts.remove(k);
+                }
+            }
+        }
+        self.total = self.raw_counts.values().copied().sum();
+    }
+
     /// Returns the count for a specific key
     pub fn get_count(&self, key: KeyId) -> u64 {
         self.raw_counts.get(&key).copied().unwrap_or(0)
@@ -82,51 +113,146 @@ impl KeyFreq {
     }
 }
 
-pub fn read_key_freq(config: &Config) -> Result<KeyFreq> {
-    let csv_path = &config.solver.csv_dir;
-    let dir_path = Path::new(csv_path);
+/// Key-to-key transition counts (`keylog_bigrams_*.csv`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigramFreq {
+    /// raw count of each (from, to) pair (only optimized keys) from data file
+    raw_counts: HashMap<(KeyId, KeyId), u64>,
+    /// total count
+    total: u64,
+}
 
-    if !dir_path.exists() {
-        return Err(KbOptError::Io(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Directory does not exist: {}", dir_path.display()),
-        )));
+impl BigramFreq {
+    pub fn counts(&self) -> &HashMap<(KeyId, KeyId), u64> {
+        &self.raw_counts
     }
 
-    if !dir_path.is_dir() {
-        return Err(KbOptError::Io(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            format!("Path is not a directory: {}", dir_path.display()),
-        )));
+    pub fn total(&self) -> u64 {
+        self.total
     }
 
-    let mut merged_freq = KeyFreq::new();
-    let mut csv_files_processed = 0;
+    /// Creates a new empty BigramFreq
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    // Read directory entries
-    for entry in std::fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
+    /// Creates BigramFreq from raw counts, automatically calculating total
+    pub fn from_counts(raw_counts: HashMap<(KeyId, KeyId), u64>) -> Self {
+        let total = raw_counts.values().copied().sum();
+        Self { raw_counts, total }
+    }
 
-        // Skip directories and non-CSV files
-        if !path.is_file() {
-            continue;
+    /// Merges another BigramFreq into this one, combining counts
+    pub fn merge(&mut self, other: BigramFreq) {
+        for (k, v) in other.raw_counts {
+            *self.raw_counts.entry(k).or_insert(0) += v;
+        }
+        self.total = self.raw_counts.values().copied().sum();
+    }
+
+    /// Returns the count for a specific transition
+    pub fn get_count(&self, from: KeyId, to: KeyId) -> u64 {
+        self.raw_counts.get(&(from, to)).copied().unwrap_or(0)
+    }
+
+    /// Returns true if no transitions have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.raw_counts.is_empty()
+    }
+}
+
+/// Modifier chord counts (`keylog_chords_*.csv`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChordFreq {
+    /// raw count of each (held modifiers, key) pair; modifiers are sorted and deduplicated
+    raw_counts: HashMap<(Vec<KeyId>, KeyId), u64>,
+    /// total count
+    total: u64,
+}
+
+impl ChordFreq {
+    pub fn counts(&self) -> &HashMap<(Vec<KeyId>, KeyId), u64> {
+        &self.raw_counts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Creates a new empty ChordFreq
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates ChordFreq from raw counts, automatically calculating total
+    pub fn from_counts(raw_counts: HashMap<(Vec<KeyId>, KeyId), u64>) -> Self {
+        let total = raw_counts.values().copied().sum();
+        Self { raw_counts, total }
+    }
+
+    /// Merges another ChordFreq into this one, combining counts
+    pub fn merge(&mut self, other: ChordFreq) {
+        for (k, v) in other.raw_counts {
+            *self.raw_counts.entry(k).or_insert(0) += v;
         }
+        self.total = self.raw_counts.values().copied().sum();
+    }
 
-        if let Some(extension) = path.extension() {
-            if extension.to_str() != Some("csv") {
-                continue;
+    /// Returns how often each modifier took part in a chord
+    pub fn modifier_usage(&self) -> HashMap<KeyId, u64> {
+        let mut usage = HashMap::new();
+        for ((modifiers, _), &count) in &self.raw_counts {
+            for &modifier in modifiers {
+                *usage.entry(modifier).or_insert(0) += count;
             }
-        } else {
+        }
+        usage
+    }
+
+    /// Returns true if no chords have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.raw_counts.is_empty()
+    }
+}
+
+pub fn read_key_freq(config: &Config) -> Result<KeyFreq> {
+    if let Some(ref database) = config.solver.database {
+        let mut freq = read_key_freq_db(config, Path::new(database))?;
+        warn_about_datasets(config, &read_datasets_db(config, Path::new(database))?);
+        if let Some(max_share) = config.solver.max_session_key_share {
+            drop_outlier_sessions(config, &mut freq, max_share)?;
+        }
+        return Ok(freq);
+    }
+    if let Some(ref devices) = config.solver.devices {
+        return read_device_key_freq(config, devices);
+    }
+    let dir_path = Path::new(&config.solver.csv_dir);
+
+    let secret = config.encryption_secret()?;
+    let mut merged_freq = KeyFreq::new();
+    let mut csv_files_processed = 0;
+    let mut datasets = Vec::new();
+
+    for path in list_csv_files(dir_path)? {
+        // Bigram and other auxiliary outputs live next to the count files
+        if is_auxiliary_file(&path) || !in_date_range(config, &path, secret.as_ref()) {
             continue;
         }
 
         // Try to read the CSV file
-        match read_key_freq_csv(&path) {
+        let freq = read_data(&path, secret.as_ref())
+            .and_then(|data| read_key_freq_from_reader(&data[..], config.solver.include_repeats));
+        match freq {
             Ok(freq) => {
                 if !freq.is_empty() {
                     merged_freq.merge(freq);
                     csv_files_processed += 1;
+                    let metadata = read_metadata(&path, secret.as_ref()).unwrap_or_else(|e| {
+                        log::warn!("Ignoring unreadable metadata for {}: {}", path.display(), e);
+                        None
+                    });
+                    datasets.push((path, metadata));
                 }
             }
             Err(e) => {
@@ -148,18 +274,519 @@ pub fn read_key_freq(config: &Config) -> Result<KeyFreq> {
         csv_files_processed,
         dir_path.display()
     );
+    warn_about_datasets(config, &datasets);
+
+    if let Some(max_share) = config.solver.max_session_key_share {
+        drop_outlier_sessions(config, &mut merged_freq, max_share)?;
+    }
+
+    Ok(merged_freq)
+}
+
+/// Logs [`dataset_warnings`], and warns when the data was recorded on another layout
+fn warn_about_datasets(config: &Config, datasets: &[(PathBuf, Option<DatasetMetadata>)]) {
+    for warning in dataset_warnings(datasets) {
+        log::warn!("{}", warning);
+    }
+    let layout = config.physical_layout();
+    let recorded: BTreeSet<&str> = datasets
+        .iter()
+        .filter_map(|(_, metadata)| metadata.as_ref()?.layout.as_deref())
+        .filter(|recorded| PhysicalLayout::from_label(recorded).is_some_and(|l| l != layout))
+        .collect();
+    if !recorded.is_empty() {
+        log::warn!(
+            "Data was recorded on {} boards, but layout is {}; keys only those boards have are not placed",
+            recorded.into_iter().collect::<Vec<_>>().join("/"),
+            layout
+        );
+    }
+}
+
+/// Reads key counts of the given input devices from the `keylog_devices_*.csv` files.
+///
+/// Device names must match exactly; the names found are logged when none of them matches.
+fn read_device_key_freq(config: &Config, devices: &[String]) -> Result<KeyFreq> {
+    let dir_path = Path::new(&config.solver.csv_dir);
+    let wanted: HashSet<&str> = devices.iter().map(|name| name.trim()).collect();
+
+    let secret = config.encryption_secret()?;
+    let mut merged_freq = KeyFreq::new();
+    let mut found = BTreeSet::new();
+    let mut csv_files_processed = 0;
+
+    for path in list_csv_files(dir_path)? {
+        if !has_file_prefix(&path, DEVICE_FILE_PREFIX)
+            || !in_date_range(config, &path, secret.as_ref())
+        {
+            continue;
+        }
+        let data = read_data(&path, secret.as_ref()).and_then(|data| {
+            read_device_freq_from_reader(&data[..], &wanted, config.solver.include_repeats)
+        });
+        match data {
+            Ok((freq, names)) => {
+                found.extend(names);
+                if !freq.is_empty() {
+                    merged_freq.merge(freq);
+                    csv_files_processed += 1;
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to read CSV file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    if csv_files_processed == 0 {
+        let found = if found.is_empty() {
+            "none; record with --per-device".to_string()
+        } else {
+            found.into_iter().collect::<Vec<_>>().join(", ")
+        };
+        return Err(KbOptError::Other(format!(
+            "No key presses from devices {:?} in {} (devices found: {})",
+            devices,
+            dir_path.display(),
+            found
+        )));
+    }
+
+    log::info!(
+        "Processed {} per-device CSV files from {}",
+        csv_files_processed,
+        dir_path.display()
+    );
+    Ok(merged_freq)
+}
+
+/// Removes sessions dominated by a single key (e.g. a game flooding WASD) from `freq`.
+///
+/// Only sessions of at least [`MIN_OUTLIER_SESSION_KEYSTROKES`] presses are judged.
+/// The session files cover the same presses as the count files, so a dropped session's
+/// counts are simply subtracted.
+fn drop_outlier_sessions(config: &Config, freq: &mut KeyFreq, max_share: f64) -> Result<()> {
+    let sessions = read_sessions(config)?;
+    if sessions.is_empty() {
+        log::warn!(
+            "max_session_key_share is set, but no session files were found; record with --session-gap-secs"
+        );
+        return Ok(());
+    }
+
+    let mut dropped = 0;
+    let mut dropped_keystrokes = 0;
+    let outliers = sessions.iter().filter(|s| {
+        s.keystrokes >= MIN_OUTLIER_SESSION_KEYSTROKES && s.top_key_share() > max_share
+    });
+    for session in outliers {
+        freq.subtract(&session.freq);
+        dropped += 1;
+        dropped_keystrokes += session.keystrokes;
+    }
+    log::info!(
+        "Dropped {} of {} sessions ({} key presses) whose most pressed key exceeds {:.0}% of keystrokes",
+        dropped,
+        sessions.len(),
+        dropped_keystrokes,
+        max_share * 100.0
+    );
+    Ok(())
+}
+
+/// One typing session from a `keylog_sessions_*.csv` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    /// Every press in the session, including keys the analyzer does not know
+    pub keystrokes: u64,
+    /// Empty for a single-instant session
+    pub keys_per_minute: Option<f64>,
+    /// Per-key counts of the known keys
+    pub freq: KeyFreq,
+}
+
+impl SessionRecord {
+    /// Fraction of the session's keystrokes that went to its most pressed key
+    pub fn top_key_share(&self) -> f64 {
+        let top = self.freq.counts().values().copied().max().unwrap_or(0);
+        if self.keystrokes == 0 {
+            0.0
+        } else {
+            top as f64 / self.keystrokes as f64
+        }
+    }
+}
+
+/// Reads every `keylog_sessions_*.csv` file in the csv directory, oldest file first,
+/// or the sessions in `database` when it is set.
+///
+/// Session statistics are opt-in on the key_logger side, so having no files yields an
+/// empty list.
+pub fn read_sessions(config: &Config) -> Result<Vec<SessionRecord>> {
+    if let Some(ref database) = config.solver.database {
+        return read_sessions_db(config, Path::new(database));
+    }
+    let dir_path = Path::new(&config.solver.csv_dir);
+
+    let secret = config.encryption_secret()?;
+    let mut sessions = Vec::new();
+    for path in list_csv_files(dir_path)? {
+        if !has_file_prefix(&path, SESSION_FILE_PREFIX)
+            || !in_date_range(config, &path, secret.as_ref())
+        {
+            continue;
+        }
+
+        match read_data(&path, secret.as_ref())
+            .and_then(|data| read_sessions_from_reader(&data[..]))
+        {
+            Ok(file_sessions) => sessions.extend(file_sessions),
+            Err(e) => {
+                log::warn!("Failed to read session CSV file {}: {}", path.display(), e);
+            }
+        }
+    }
+    Ok(sessions)
+}
+
+/// Session description from the `.meta.json` sidecar key_logger writes next to a count CSV.
+///
+/// Every field except the schema version is optional so older or hand-written sidecars
+/// still load; unknown fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DatasetMetadata {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub key_logger_version: Option<String>,
+    #[serde(default)]
+    pub session_start: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub session_end: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub backend: Option<String>,
+    #[serde(default)]
+    pub polling_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+/// Sidecar path for a count CSV: same stem with the `.meta.json` suffix
+/// (`keylog_x.csv.enc` shares the sidecar of `keylog_x.csv`)
+pub fn metadata_path(csv_path: &Path) -> PathBuf {
+    let name = csv_path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name
+        .strip_suffix(&format!(".{}", ENCRYPTED_EXTENSION))
+        .unwrap_or(&name);
+    let stem = Path::new(name)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    csv_path.with_file_name(format!("{stem}{METADATA_FILE_SUFFIX}"))
+}
+
+/// Reads the sidecar of `csv_path`, or `None` if there is none. An encrypted sidecar
+/// (`.meta.json.enc`, written next to an encrypted CSV) is decrypted with `secret`.
+pub fn read_metadata(csv_path: &Path, secret: Option<&Secret>) -> Result<Option<DatasetMetadata>> {
+    let path = metadata_path(csv_path);
+    let mut encrypted = path.clone().into_os_string();
+    encrypted.push(format!(".{}", ENCRYPTED_EXTENSION));
+    let Some(path) = [path, PathBuf::from(encrypted)]
+        .into_iter()
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+    let data = read_data(&path, secret)?;
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+/// Describes problems with combining the given datasets into one frequency table:
+/// unsupported schemas, mixed layouts or capture backends, missing metadata, and
+/// overlapping sessions (usually the same recording copied twice).
+pub fn dataset_warnings(datasets: &[(PathBuf, Option<DatasetMetadata>)]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    for (path, metadata) in datasets {
+        if let Some(meta) = metadata
+            && meta.schema_version > SUPPORTED_METADATA_SCHEMA
+        {
+            warnings.push(format!(
+                "{} uses metadata schema {} (supported: {}); update analyzer",
+                path.display(),
+                meta.schema_version,
+                SUPPORTED_METADATA_SCHEMA
+            ));
+        }
+    }
+
+    let described: Vec<(&PathBuf, &DatasetMetadata)> = datasets
+        .iter()
+        .filter_map(|(path, metadata)| metadata.as_ref().map(|m| (path, m)))
+        .collect();
+
+    let missing = datasets.len() - described.len();
+    if missing > 0 && !described.is_empty() {
+        warnings.push(format!(
+            "{} of {} CSV files have no metadata; their layout and capture backend are unknown",
+            missing,
+            datasets.len()
+        ));
+    }
+
+    let describe_mix = |field: &str, values: BTreeMap<&str, usize>| {
+        let parts: Vec<String> = values
+            .iter()
+            .map(|(value, n)| format!("{} ({} files)", value, n))
+            .collect();
+        format!("Mixed {}: {}", field, parts.join(", "))
+    };
+
+    let mut layouts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut backends: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, meta) in &described {
+        *layouts
+            .entry(meta.layout.as_deref().unwrap_or("unknown"))
+            .or_default() += 1;
+        *backends
+            .entry(meta.backend.as_deref().unwrap_or("unknown"))
+            .or_default() += 1;
+    }
+    if layouts.len() > 1 {
+        warnings.push(describe_mix("physical layouts", layouts));
+    }
+    if backends.len() > 1 {
+        // Polling misses taps shorter than its interval, so its counts are systematically lower
+        warnings.push(describe_mix("capture backends", backends));
+    }
+
+    let mut sessions: Vec<(&PathBuf, DateTime<FixedOffset>, DateTime<FixedOffset>)> = described
+        .iter()
+        .filter_map(|(path, meta)| Some((*path, meta.session_start?, meta.session_end?)))
+        .collect();
+    sessions.sort_by_key(|&(_, start, _)| start);
+    for pair in sessions.windows(2) {
+        let (prev_path, _, prev_end) = pair[0];
+        let (path, start, _) = pair[1];
+        if start < prev_end {
+            warnings.push(format!(
+                "Sessions in {} and {} overlap; the data may be counted twice",
+                prev_path.display(),
+                path.display()
+            ));
+        }
+    }
+
+    warnings
+}
+
+/// Reads and merges every `keylog_bigrams_*.csv` file in the csv directory, or the
+/// bigrams of the SQLite store when `database` is set.
+///
+/// Unlike [`read_key_freq`], having no bigram files is not an error: bigram counting
+/// is opt-in on the key_logger side, so an empty result is returned instead.
+pub fn read_bigram_freq(config: &Config) -> Result<BigramFreq> {
+    if let Some(ref database) = config.solver.database {
+        return read_bigram_freq_db(config, Path::new(database));
+    }
+    let dir_path = Path::new(&config.solver.csv_dir);
+
+    let secret = config.encryption_secret()?;
+    let mut merged_freq = BigramFreq::new();
+    let mut csv_files_processed = 0;
+
+    for path in list_csv_files(dir_path)? {
+        if !has_file_prefix(&path, BIGRAM_FILE_PREFIX)
+            || !in_date_range(config, &path, secret.as_ref())
+        {
+            continue;
+        }
+
+        match read_data(&path, secret.as_ref())
+            .and_then(|data| read_bigram_freq_from_reader(&data[..]))
+        {
+            Ok(freq) => {
+                merged_freq.merge(freq);
+                csv_files_processed += 1;
+            }
+            Err(e) => {
+                log::warn!("Failed to read bigram CSV file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    log::info!(
+        "Processed {} bigram CSV files from {}",
+        csv_files_processed,
+        dir_path.display()
+    );
 
     Ok(merged_freq)
 }
 
-/// Reads key frequency data from a CSV file
-fn read_key_freq_csv<P: AsRef<Path>>(path: P) -> Result<KeyFreq> {
-    let file = std::fs::File::open(path)?;
-    read_key_freq_from_reader(file)
+/// Reads and merges every `keylog_chords_*.csv` file in the csv directory, or the chords
+/// of the SQLite store when `database` is set.
+///
+/// Chord counting is opt-in on the key_logger side, so having no files yields an empty result.
+pub fn read_chord_freq(config: &Config) -> Result<ChordFreq> {
+    if let Some(ref database) = config.solver.database {
+        return read_chord_freq_db(config, Path::new(database));
+    }
+    let dir_path = Path::new(&config.solver.csv_dir);
+
+    let secret = config.encryption_secret()?;
+    let mut merged_freq = ChordFreq::new();
+    let mut csv_files_processed = 0;
+
+    for path in list_csv_files(dir_path)? {
+        if !has_file_prefix(&path, CHORD_FILE_PREFIX)
+            || !in_date_range(config, &path, secret.as_ref())
+        {
+            continue;
+        }
+
+        match read_data(&path, secret.as_ref())
+            .and_then(|data| read_chord_freq_from_reader(&data[..]))
+        {
+            Ok(freq) => {
+                merged_freq.merge(freq);
+                csv_files_processed += 1;
+            }
+            Err(e) => {
+                log::warn!("Failed to read chord CSV file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    log::info!(
+        "Processed {} chord CSV files from {}",
+        csv_files_processed,
+        dir_path.display()
+    );
+
+    Ok(merged_freq)
+}
+
+/// Lists the `.csv` and encrypted `.csv.enc` files directly inside `dir_path`
+pub(crate) fn list_csv_files(dir_path: &Path) -> Result<Vec<PathBuf>> {
+    if !dir_path.exists() {
+        return Err(KbOptError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Directory does not exist: {}", dir_path.display()),
+        )));
+    }
+
+    if !dir_path.is_dir() {
+        return Err(KbOptError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Path is not a directory: {}", dir_path.display()),
+        )));
+    }
+
+    let mut files = Vec::new();
+
+    // Read directory entries
+    for entry in std::fs::read_dir(dir_path)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // Skip directories and non-CSV files
+        if !path.is_file() {
+            continue;
+        }
+
+        if is_csv_file(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn is_csv_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let name = name
+        .strip_suffix(&format!(".{}", ENCRYPTED_EXTENSION))
+        .unwrap_or(name);
+    name.ends_with(".csv")
+}
+
+/// Contents of `path`, decrypted if key_logger encrypted it
+fn read_data(path: &Path, secret: Option<&Secret>) -> Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    if !keylog_crypt::is_encrypted(&data) {
+        return Ok(data);
+    }
+    let secret = secret.ok_or_else(|| {
+        KbOptError::Config(format!(
+            "{} is encrypted; set encryption_key_file or encryption_passphrase_file",
+            path.display()
+        ))
+    })?;
+    Ok(keylog_crypt::decrypt(secret, &data)?)
 }
 
-/// Read CSV with `Key,Count` format.
-fn read_key_freq_from_reader<R: Read>(reader: R) -> Result<KeyFreq> {
+fn has_file_prefix(path: &Path, prefix: &str) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(prefix))
+}
+
+pub(crate) fn is_auxiliary_file(path: &Path) -> bool {
+    AUXILIARY_FILE_PREFIXES
+        .iter()
+        .any(|prefix| has_file_prefix(path, prefix))
+}
+
+/// Whether `path` lies within the configured `date_from..=date_to` range.
+///
+/// The date is taken from the file name (`keylog_2025-07-27.csv` as written by daily
+/// rotation, or the default `keylog_2025-07-27_14-30-00.csv`), falling back to the session
+/// start in the metadata sidecar. Undated files are skipped once a range is set.
+fn in_date_range(config: &Config, path: &Path, secret: Option<&Secret>) -> bool {
+    let (from, to) = (config.solver.date_from, config.solver.date_to);
+    if from.is_none() && to.is_none() {
+        return true;
+    }
+
+    let date = file_date(path).or_else(|| {
+        read_metadata(path, secret)
+            .ok()
+            .flatten()
+            .and_then(|meta| meta.session_start)
+            .map(|start| start.date_naive())
+    });
+    let Some(date) = date else {
+        log::warn!(
+            "Skipping {}: no date in its name or metadata",
+            path.display()
+        );
+        return false;
+    };
+
+    from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+}
+
+/// Date following the key_logger prefix in the file name, if any
+fn file_date(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_stem()?.to_str()?;
+    // Auxiliary prefixes first, since they all start with the count file prefix
+    let rest = AUXILIARY_FILE_PREFIXES
+        .iter()
+        .chain([&COUNT_FILE_PREFIX])
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    NaiveDate::parse_from_str(rest.get(..FILE_DATE_LEN)?, FILE_DATE_FORMAT).ok()
+}
+
+/// Read CSV with `Key,Count[,Repeat]` format.
+///
+/// `Repeat` holds auto-repeats of held keys, which are only added to the counts when
+/// `include_repeats` is set; files without the column count presses only.
+fn read_key_freq_from_reader<R: Read>(reader: R, include_repeats: bool) -> Result<KeyFreq> {
     let mut rdr = ReaderBuilder::new()
         .has_headers(true)
         .trim(Trim::All)
@@ -167,6 +794,13 @@ fn read_key_freq_from_reader<R: Read>(reader: R) -> Result<KeyFreq> {
         .from_reader(reader);
 
     validate_csv_headers(&mut rdr)?;
+    let repeat_column = if include_repeats {
+        rdr.headers()?
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(REPEAT_HEADER))
+    } else {
+        None
+    };
 
     let mut counts: HashMap<KeyId, u64> = HashMap::new();
 
@@ -174,7 +808,15 @@ fn read_key_freq_from_reader<R: Read>(reader: R) -> Result<KeyFreq> {
         let rec = result?;
         let row = i + 2; // CSV rows are 1-indexed, +1 for header
 
-        if let Some((kid, n)) = parse_record(&rec, row)? {
+        if let Some((kid, mut n)) = parse_record(&rec, row)? {
+            // A blank or missing cell means no repeats were recorded for the key
+            if let Some(repeats) = repeat_column
+                .and_then(|column| rec.get(column))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+            {
+                n += parse_count_value(repeats, row)?;
+            }
             *counts.entry(kid).or_insert(0) += n;
         }
     }
@@ -182,32 +824,263 @@ fn read_key_freq_from_reader<R: Read>(reader: R) -> Result<KeyFreq> {
     Ok(KeyFreq::from_counts(counts))
 }
 
+/// Read CSV with `Device,Key,Count,Repeat` format, keeping the rows of `devices` only.
+///
+/// Also returns every device name in the file, so a misspelled name can be reported.
+fn read_device_freq_from_reader<R: Read>(
+    reader: R,
+    devices: &HashSet<&str>,
+    include_repeats: bool,
+) -> Result<(KeyFreq, HashSet<String>)> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    validate_headers(
+        &mut rdr,
+        &[
+            EXPECTED_DEVICE_HEADER,
+            EXPECTED_KEY_HEADER,
+            EXPECTED_COUNT_HEADER,
+            REPEAT_HEADER,
+        ],
+    )?;
+
+    let mut counts: HashMap<KeyId, u64> = HashMap::new();
+    let mut names = HashSet::new();
+
+    for (i, result) in rdr.records().enumerate() {
+        let rec = result?;
+        let row = i + 2; // CSV rows are 1-indexed, +1 for header
+
+        if rec.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let device = get_column_value(&rec, 0, row)?;
+        if !names.contains(device) {
+            names.insert(device.to_string());
+        }
+        if !devices.contains(device) {
+            continue;
+        }
+        let key_label = get_column_value(&rec, 1, row)?;
+        if let Some(key) = parse_key_label(key_label) {
+            let mut count = parse_count_value(get_column_value(&rec, 2, row)?, row)?;
+            if include_repeats {
+                count += parse_count_value(get_column_value(&rec, 3, row)?, row)?;
+            }
+            *counts.entry(key).or_insert(0) += count;
+        }
+    }
+
+    Ok((KeyFreq::from_counts(counts), names))
+}
+
+/// Reads sessions from a
+/// `Session,Start,End,DurationSecs,Keystrokes,KeysPerMinute,Key,Count` CSV file
+pub fn read_sessions_csv<P: AsRef<Path>>(path: P) -> Result<Vec<SessionRecord>> {
+    let data = read_data(path.as_ref(), None)?;
+    read_sessions_from_reader(&data[..])
+}
+
+/// Read CSV with one row per (session, key); consecutive rows of a session share its number.
+fn read_sessions_from_reader<R: Read>(reader: R) -> Result<Vec<SessionRecord>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    validate_headers(
+        &mut rdr,
+        &[
+            "Session",
+            "Start",
+            "End",
+            "DurationSecs",
+            "Keystrokes",
+            "KeysPerMinute",
+            EXPECTED_KEY_HEADER,
+            EXPECTED_COUNT_HEADER,
+        ],
+    )?;
+
+    let mut sessions: Vec<(String, SessionRecord, HashMap<KeyId, u64>)> = Vec::new();
+    for (i, result) in rdr.records().enumerate() {
+        let rec = result?;
+        let row = i + 2; // CSV rows are 1-indexed, +1 for header
+        if rec.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+
+        let id = get_column_value(&rec, 0, row)?;
+        if sessions.last().is_none_or(|(last, _, _)| last != id) {
+            let time = |column| {
+                let value = get_column_value(&rec, column, row)?;
+                DateTime::parse_from_rfc3339(value).map_err(|e| {
+                    KbOptError::Other(format!("Invalid time at row {}: {} ({})", row, value, e))
+                })
+            };
+            let rate = get_column_value(&rec, 5, row)?;
+            let session = SessionRecord {
+                start: time(1)?,
+                end: time(2)?,
+                keystrokes: parse_count_value(get_column_value(&rec, 4, row)?, row)?,
+                keys_per_minute: rate.parse().ok(),
+                freq: KeyFreq::new(),
+            };
+            sessions.push((id.to_string(), session, HashMap::new()));
+        }
+
+        let key_label = get_column_value(&rec, 6, row)?;
+        let count = parse_count_value(get_column_value(&rec, 7, row)?, row)?;
+        if let (Some(kid), Some((_, _, counts))) = (parse_key_label(key_label), sessions.last_mut())
+        {
+            *counts.entry(kid).or_insert(0) += count;
+        }
+    }
+
+    Ok(sessions
+        .into_iter()
+        .map(|(_, session, counts)| SessionRecord {
+            freq: KeyFreq::from_counts(counts),
+            ..session
+        })
+        .collect())
+}
+
+/// Reads transition counts from a `From,To,Count` CSV file
+pub fn read_bigram_freq_csv<P: AsRef<Path>>(path: P) -> Result<BigramFreq> {
+    let data = read_data(path.as_ref(), None)?;
+    read_bigram_freq_from_reader(&data[..])
+}
+
+/// Read CSV with `From,To,Count` format.
+fn read_bigram_freq_from_reader<R: Read>(reader: R) -> Result<BigramFreq> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    validate_headers(
+        &mut rdr,
+        &[
+            EXPECTED_FROM_HEADER,
+            EXPECTED_TO_HEADER,
+            EXPECTED_COUNT_HEADER,
+        ],
+    )?;
+
+    let mut counts: HashMap<(KeyId, KeyId), u64> = HashMap::new();
+
+    for (i, result) in rdr.records().enumerate() {
+        let rec = result?;
+        let row = i + 2; // CSV rows are 1-indexed, +1 for header
+
+        if rec.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let from_label = get_column_value(&rec, 0, row)?;
+        let to_label = get_column_value(&rec, 1, row)?;
+        let count_str = get_column_value(&rec, 2, row)?;
+
+        // Transitions involving keys outside the optimized set are dropped
+        if let (Some(from), Some(to)) = (parse_key_label(from_label), parse_key_label(to_label)) {
+            let count = parse_count_value(count_str, row)?;
+            *counts.entry((from, to)).or_insert(0) += count;
+        }
+    }
+
+    Ok(BigramFreq::from_counts(counts))
+}
+
+/// Reads chord counts from a `Modifiers,Key,Count` CSV file
+pub fn read_chord_freq_csv<P: AsRef<Path>>(path: P) -> Result<ChordFreq> {
+    let data = read_data(path.as_ref(), None)?;
+    read_chord_freq_from_reader(&data[..])
+}
+
+/// Read CSV with `Modifiers,Key,Count` format.
+fn read_chord_freq_from_reader<R: Read>(reader: R) -> Result<ChordFreq> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    validate_headers(
+        &mut rdr,
+        &[
+            EXPECTED_MODIFIERS_HEADER,
+            EXPECTED_KEY_HEADER,
+            EXPECTED_COUNT_HEADER,
+        ],
+    )?;
+
+    let mut counts: HashMap<(Vec<KeyId>, KeyId), u64> = HashMap::new();
+
+    for (i, result) in rdr.records().enumerate() {
+        let rec = result?;
+        let row = i + 2; // CSV rows are 1-indexed, +1 for header
+
+        if rec.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let modifiers_label = get_column_value(&rec, 0, row)?;
+        let key_label = get_column_value(&rec, 1, row)?;
+        let count_str = get_column_value(&rec, 2, row)?;
+
+        let Some(modifiers) = parse_modifier_set(modifiers_label) else {
+            continue;
+        };
+        if let Some(key) = parse_key_label(key_label) {
+            let count = parse_count_value(count_str, row)?;
+            *counts.entry((modifiers, key)).or_insert(0) += count;
+        }
+    }
+
+    Ok(ChordFreq::from_counts(counts))
+}
+
+/// Parses a `+`-joined modifier set such as `LeftControl+LeftShift`
+pub(crate) fn parse_modifier_set(label: &str) -> Option<Vec<KeyId>> {
+    let mut modifiers = label
+        .split('+')
+        .map(|part| parse_key_label(part).filter(|k| k.is_modifier()))
+        .collect::<Option<Vec<KeyId>>>()?;
+    modifiers.sort();
+    modifiers.dedup();
+    Some(modifiers)
+}
+
 /// Validates CSV headers match expected format
-fn validate_csv_headers<R: Read>(csv_reader: &mut csv::Reader<R>) -> Result<()> {
+pub(crate) fn validate_csv_headers<R: Read>(csv_reader: &mut csv::Reader<R>) -> Result<()> {
+    validate_headers(csv_reader, &[EXPECTED_KEY_HEADER, EXPECTED_COUNT_HEADER])
+}
+
+/// Validates that the leading header columns match `expected` (case-insensitive)
+fn validate_headers<R: Read>(csv_reader: &mut csv::Reader<R>, expected: &[&str]) -> Result<()> {
     let headers = csv_reader
         .headers()
         .map_err(|e| KbOptError::CsvHeader(format!("Failed to read headers: {}", e)))?;
 
-    let key_header = headers
-        .get(0)
-        .ok_or_else(|| KbOptError::CsvHeader("Missing key column at index 0".to_string()))?;
+    for (index, &expected_header) in expected.iter().enumerate() {
+        let header = headers.get(index).ok_or_else(|| {
+            KbOptError::CsvHeader(format!(
+                "Missing '{}' column at index {}",
+                expected_header, index
+            ))
+        })?;
 
-    let count_header = headers
-        .get(1)
-        .ok_or_else(|| KbOptError::CsvHeader("Missing count column at index 1".to_string()))?;
-
-    if !key_header.eq_ignore_ascii_case(EXPECTED_KEY_HEADER) {
-        return Err(KbOptError::CsvHeader(format!(
-            "Expected '{}' in column 0, found '{}'",
-            EXPECTED_KEY_HEADER, key_header
-        )));
-    }
-
-    if !count_header.eq_ignore_ascii_case(EXPECTED_COUNT_HEADER) {
-        return Err(KbOptError::CsvHeader(format!(
-            "Expected '{}' in column 1, found '{}'",
-            EXPECTED_COUNT_HEADER, count_header
-        )));
+        if !header.eq_ignore_ascii_case(expected_header) {
+            return Err(KbOptError::CsvHeader(format!(
+                "Expected '{}' in column {}, found '{}'",
+                expected_header, index, header
+            )));
+        }
     }
 
     Ok(())
@@ -234,7 +1107,11 @@ fn parse_record(rec: &StringRecord, row: usize) -> Result<Option<(KeyId, u64)>>
 }
 
 /// Safely extracts a column value from a CSV record
-fn get_column_value(record: &StringRecord, column_index: usize, row_number: usize) -> Result<&str> {
+pub(crate) fn get_column_value(
+    record: &StringRecord,
+    column_index: usize,
+    row_number: usize,
+) -> Result<&str> {
     record
         .get(column_index)
         .map(str::trim)
@@ -245,7 +1122,7 @@ fn get_column_value(record: &StringRecord, column_index: usize, row_number: usiz
 }
 
 /// Parses a count string into u64
-fn parse_count_value(count_str: &str, row_number: usize) -> Result<u64> {
+pub(crate) fn parse_count_value(count_str: &str, row_number: usize) -> Result<u64> {
     count_str
         .parse()
         .map_err(|parse_error| KbOptError::CountParse {
@@ -254,3 +1131,32 @@ fn parse_count_value(count_str: &str, row_number: usize) -> Result<u64> {
             source: parse_error,
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_encrypted_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let csv_path = temp_dir.path().join("keylog_2025-07-01.csv.enc");
+        let secret = Secret::Key([5; 32]);
+        let sidecar = br#"{"schema_version": 1, "backend": "evdev", "layout": "JIS"}"#;
+        std::fs::write(
+            temp_dir.path().join("keylog_2025-07-01.meta.json.enc"),
+            keylog_crypt::encrypt(&secret, sidecar).unwrap(),
+        )
+        .unwrap();
+
+        let metadata = read_metadata(&csv_path, Some(&secret)).unwrap().unwrap();
+        assert_eq!(metadata.layout.as_deref(), Some("JIS"));
+        assert_eq!(metadata.backend.as_deref(), Some("evdev"));
+        assert!(read_metadata(&csv_path, None).is_err());
+        assert!(
+            read_metadata(&temp_dir.path().join("keylog_2025-07-02.csv"), None)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
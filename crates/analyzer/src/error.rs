@@ -48,6 +48,12 @@ pub enum KbOptError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("Encryption error: {0}")]
+    Crypt(#[from] keylog_crypt::Error),
+
     #[error("Configuration error: {0}")]
     Config(String),
 
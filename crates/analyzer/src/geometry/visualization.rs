@@ -4,6 +4,7 @@ use crate::{
     csv_reader::KeyFreq,
     error::{KbOptError, Result},
     geometry::types::*,
+    keys::{ArrowKey, KeyId, SymbolKey},
 };
 
 use ab_glyph::{FontVec, PxScale};
@@ -15,6 +16,63 @@ use imageproc::{
 };
 use std::{fs, path::Path};
 
+/// Short text drawn on a key: a glyph for symbols and modifiers, the label otherwise.
+///
+/// Matched on [`KeyId`] rather than on label strings, so renaming a label cannot
+/// silently drop a glyph.
+fn key_glyph(key: KeyId) -> &'static str {
+    use KeyId::*;
+    match key {
+        Symbol(symbol) => match symbol {
+            SymbolKey::Backtick => "`",
+            SymbolKey::Minus => "-",
+            SymbolKey::Equal => "=",
+            SymbolKey::LBracket => "[",
+            SymbolKey::RBracket => "]",
+            SymbolKey::Backslash => r"\",
+            SymbolKey::Semicolon => ";",
+            SymbolKey::Quote => "'",
+            SymbolKey::Comma => ",",
+            SymbolKey::Period => ".",
+            SymbolKey::Slash => "/",
+            SymbolKey::IntlBackslash => "ISO",
+            SymbolKey::Yen => "¥",
+            SymbolKey::Ro => "Ro",
+        },
+        Arrow(arrow) => match arrow {
+            ArrowKey::Up => "↑",
+            ArrowKey::Down => "↓",
+            ArrowKey::Left => "←",
+            ArrowKey::Right => "→",
+        },
+        ShiftL => "L⇧",
+        ShiftR => "R⇧",
+        Space => "△",
+        CtrlL => "LCtrl",
+        CtrlR => "RCtrl",
+        AltL => "LAlt",
+        AltR => "RAlt",
+        MetaL => "LMeta",
+        MetaR => "RMeta",
+        Backspace => "BS",
+        Delete => "Del",
+        CapsLock => "Caps",
+        Escape => "Esc",
+        Muhenkan => "Muhen",
+        Henkan => "Henk",
+        KatakanaHiragana => "Kana",
+        _ => key.label(),
+    }
+}
+
+/// Text drawn on the key placed as `key_name`; names that are not keys are drawn as is
+fn display_text(key_name: &str, key_id: Option<KeyId>) -> &str {
+    match key_id.or_else(|| KeyId::from_label(key_name)) {
+        Some(key) => key_glyph(key),
+        None => key_name,
+    }
+}
+
 /// キー中心座標をピクセル座標に変換（Y軸反転、center-to-center）
 #[inline]
 fn key_center_to_px(u_x: f64, u_y: f64) -> (f64, f64) {
@@ -299,75 +357,7 @@ fn render_all_keys(renderer: &mut Renderer, geom: &Geometry, freqs: &KeyFreq) ->
         }
 
         // 記号を表示
-        let display_text = match key_name.as_str() {
-            "ArrowUp" => "↑",
-            "ArrowDown" => "↓",
-            "ArrowLeft" => "←",
-            "ArrowRight" => "→",
-            "Backslash" => r"\",
-            "Slash" => "/",
-            "RBracket" => "]",
-            "LBracket" => "[",
-            "Semicolon" => ";",
-            "Equal" => "=",
-            "Minus" => "-",
-            "Backtick" => "`",
-            "Quote" => "'",
-            "RightShift" => "R⇧",
-            "Period" => ".",
-            "Comma" => ",",
-            "LeftShift" => "L⇧",
-            "Space" => "△",
-            "LeftControl" => "LCtrl",
-            "RightControl" => "RCtrl",
-            "LeftAlt" => "LAlt",
-            "RightAlt" => "RAlt",
-            "LeftMeta" => "LMeta",
-            "RightMeta" => "RMeta",
-            "Backspace" => "BS",
-            "Delete" => "Del",
-            "CapsLock" => "Caps",
-            "Escape" => "Esc",
-            "Tab" => "Tab",
-            "Enter" => "Enter",
-            // KeyIdのDebug形式に対応
-            s if s.starts_with("Digit(") => {
-                // "Digit(3)" -> "3"
-                s.trim_start_matches("Digit(").trim_end_matches(")")
-            }
-            s if s.starts_with("Letter(") => {
-                // "Letter(A)" -> "A"
-                s.trim_start_matches("Letter(").trim_end_matches(")")
-            }
-            s if s.starts_with("Symbol(") => {
-                // "Symbol(Comma)" -> "," など、個別マッピングが必要
-                match s {
-                    "Symbol(Comma)" => ",",
-                    "Symbol(Period)" => ".",
-                    "Symbol(Slash)" => "/",
-                    "Symbol(Semicolon)" => ";",
-                    "Symbol(Quote)" => "'",
-                    "Symbol(LBracket)" => "[",
-                    "Symbol(RBracket)" => "]",
-                    "Symbol(Backslash)" => r"\",
-                    "Symbol(Backtick)" => "`",
-                    "Symbol(Minus)" => "-",
-                    "Symbol(Equal)" => "=",
-                    _ => s,
-                }
-            }
-            s if s.starts_with("Arrow(") => {
-                // "Arrow(Up)" -> "↑"
-                match s {
-                    "Arrow(Up)" => "↑",
-                    "Arrow(Down)" => "↓",
-                    "Arrow(Left)" => "←",
-                    "Arrow(Right)" => "→",
-                    _ => s,
-                }
-            }
-            _ => key_name.as_str(),
-        };
+        let display_text = display_text(key_name, key_placement.key_id);
 
         // キー名を描画（キー中心）
         let text_x = px_x - U2PX / 10.0 - U2PX / 15.0 * (display_text.chars().count() - 1) as f64;
@@ -613,82 +603,7 @@ fn render_all_keys_with_offset(
         }
 
         // 記号を表示
-        let display_text = match key_name.as_str() {
-            "ArrowUp" => "↑",
-            "ArrowDown" => "↓",
-            "ArrowLeft" => "←",
-            "ArrowRight" => "→",
-            "Backslash" => r"\",
-            "Slash" => "/",
-            "RBracket" => "]",
-            "LBracket" => "[",
-            "Semicolon" => ";",
-            "Equal" => "=",
-            "Minus" => "-",
-            "Backtick" => "`",
-            "Quote" => "'",
-            "RightShift" => "R⇧",
-            "Period" => ".",
-            "Comma" => ",",
-            "LeftShift" => "L⇧",
-            "Space" => "△",
-            "LeftControl" => "LCtrl",
-            "RightControl" => "RCtrl",
-            "LeftAlt" => "LAlt",
-            "RightAlt" => "RAlt",
-            "LeftMeta" => "LMeta",
-            "RightMeta" => "RMeta",
-            "Backspace" => "BS",
-            "Delete" => "Del",
-            "CapsLock" => "Caps",
-            "Escape" => "Esc",
-            "Tab" => "Tab",
-            "Enter" => "Enter",
-            // 数字キーの対応を追加
-            "Digit(0)" => "0",
-            "Digit(1)" => "1",
-            "Digit(2)" => "2",
-            "Digit(3)" => "3",
-            "Digit(4)" => "4",
-            "Digit(5)" => "5",
-            "Digit(6)" => "6",
-            "Digit(7)" => "7",
-            "Digit(8)" => "8",
-            "Digit(9)" => "9",
-            // KeyIdのDebug形式に対応
-            s if s.starts_with("Digit(") => {
-                // "Digit(3)" -> "3"
-                s.trim_start_matches("Digit(").trim_end_matches(")")
-            }
-            s if s.starts_with("Symbol(") => {
-                // "Symbol(Comma)" -> "," など、個別マッピングが必要
-                match s {
-                    "Symbol(Comma)" => ",",
-                    "Symbol(Period)" => ".",
-                    "Symbol(Slash)" => "/",
-                    "Symbol(Semicolon)" => ";",
-                    "Symbol(Quote)" => "'",
-                    "Symbol(LBracket)" => "[",
-                    "Symbol(RBracket)" => "]",
-                    "Symbol(Backslash)" => r"\",
-                    "Symbol(Backtick)" => "`",
-                    "Symbol(Minus)" => "-",
-                    "Symbol(Equal)" => "=",
-                    _ => s,
-                }
-            }
-            s if s.starts_with("Arrow(") => {
-                // "Arrow(Up)" -> "↑"
-                match s {
-                    "Arrow(Up)" => "↑",
-                    "Arrow(Down)" => "↓",
-                    "Arrow(Left)" => "←",
-                    "Arrow(Right)" => "→",
-                    _ => s,
-                }
-            }
-            _ => key_name.as_str(),
-        };
+        let display_text = display_text(key_name, key_placement.key_id);
 
         // キー名を描画（キー中心）
         let text_x = px_x - U2PX / 10.0 - U2PX / 15.0 * (display_text.chars().count() - 1) as f64;
@@ -1,358 +1,38 @@
-use crate::constants::MAX_DIGIT;
+//! Key identities live in the shared `key_id` crate so key_logger and analyzer agree on
+//! labels; this module decides which of them the optimizer handles.
 
-use std::fmt;
-
-/// letter keys
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum LetterKey {
-    A,
-    B,
-    C,
-    D,
-    E,
-    F,
-    G,
-    H,
-    I,
-    J,
-    K,
-    L,
-    M,
-    N,
-    O,
-    P,
-    Q,
-    R,
-    S,
-    T,
-    U,
-    V,
-    W,
-    X,
-    Y,
-    Z,
-}
-
-/// Symbol keys
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum SymbolKey {
-    Backtick,  // `
-    Minus,     // -
-    Equal,     // =
-    LBracket,  // [
-    RBracket,  // ]
-    Backslash, // \
-    Semicolon, // ;
-    Quote,     // '
-    Comma,     // ,
-    Period,    // .
-    Slash,     // /
-}
-
-/// Arrow keys
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum ArrowKey {
-    Left,
-    Down,
-    Up,
-    Right,
-}
-
-/// Modifier keys for layer switching
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum ModifierKey {
-    Layer1,
-    Layer2,
-    Layer3,
-}
-
-/// Optimized key identifier.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum KeyId {
-    // letter
-    Letter(LetterKey),
-    // digit
-    Digit(u8), // 0..9
-    // symbols (US)
-    Symbol(SymbolKey),
-    Tab,
-    Escape,
-    CapsLock,
-    Delete,
-    Backspace,
-    Space,
-    Enter,
-    ShiftL,
-    ShiftR,
-    CtrlL,
-    CtrlR,
-    AltL,
-    AltR,
-    MetaL,
-    MetaR,
-    Function(u8),
-    Arrow(ArrowKey),
-    Modifier(ModifierKey),
-    // navigation keys
-    Home,
-    End,
-    PageUp,
-    PageDown,
-    Insert,
-    // numeric keypad keys
-    NumpadDigit(u8), // 0..9
-    NumpadAdd,
-    NumpadSubtract,
-    NumpadMultiply,
-    NumpadDivide,
-    NumpadEnter,
-    NumpadEquals,
-    NumpadDecimal,
-}
-
-impl fmt::Display for KeyId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use KeyId::*;
-        match self {
-            Letter(l) => write!(f, "{:?}", l),
-            Digit(d) => write!(f, "{}", d),
-            Symbol(s) => write!(f, "{:?}", s),
-            Tab => write!(f, "Tab"),
-            Escape => write!(f, "Escape"),
-            CapsLock => write!(f, "CapsLock"),
-            Delete => write!(f, "Delete"),
-            Backspace => write!(f, "Backspace"),
-            Space => write!(f, "Space"),
-            Enter => write!(f, "Enter"),
-            ShiftL => write!(f, "LeftShift"),
-            ShiftR => write!(f, "RightShift"),
-            CtrlL => write!(f, "LeftControl"),
-            CtrlR => write!(f, "RightControl"),
-            AltL => write!(f, "LeftAlt"),
-            AltR => write!(f, "RightAlt"),
-            MetaL => write!(f, "LeftMeta"),
-            MetaR => write!(f, "RightMeta"),
-            Function(n) => write!(f, "F{}", n),
-            Arrow(a) => write!(f, "Arrow{:?}", a),
-            Modifier(m) => write!(f, "Modifier{:?}", m),
-            Home => write!(f, "Home"),
-            End => write!(f, "End"),
-            PageUp => write!(f, "PageUp"),
-            PageDown => write!(f, "PageDown"),
-            Insert => write!(f, "Insert"),
-            NumpadDigit(d) => write!(f, "Numpad{}", d),
-            NumpadAdd => write!(f, "NumpadAdd"),
-            NumpadSubtract => write!(f, "NumpadSubtract"),
-            NumpadMultiply => write!(f, "NumpadMultiply"),
-            NumpadDivide => write!(f, "NumpadDivide"),
-            NumpadEnter => write!(f, "NumpadEnter"),
-            NumpadEquals => write!(f, "NumpadEquals"),
-            NumpadDecimal => write!(f, "NumpadDecimal"),
-        }
-    }
-}
+pub use key_id::{ArrowKey, KeyId, LetterKey, ModifierKey, SymbolKey};
 
 pub fn str_to_keyid(str: &str) -> Option<KeyId> {
-    use LetterKey::*;
-    match str {
-        // letters
-        "A" => Some(KeyId::Letter(A)),
-        "B" => Some(KeyId::Letter(B)),
-        "C" => Some(KeyId::Letter(C)),
-        "D" => Some(KeyId::Letter(D)),
-        "E" => Some(KeyId::Letter(E)),
-        "F" => Some(KeyId::Letter(F)),
-        "G" => Some(KeyId::Letter(G)),
-        "H" => Some(KeyId::Letter(H)),
-        "I" => Some(KeyId::Letter(I)),
-        "J" => Some(KeyId::Letter(J)),
-        "K" => Some(KeyId::Letter(K)),
-        "L" => Some(KeyId::Letter(L)),
-        "M" => Some(KeyId::Letter(M)),
-        "N" => Some(KeyId::Letter(N)),
-        "O" => Some(KeyId::Letter(O)),
-        "P" => Some(KeyId::Letter(P)),
-        "Q" => Some(KeyId::Letter(Q)),
-        "R" => Some(KeyId::Letter(R)),
-        "S" => Some(KeyId::Letter(S)),
-        "T" => Some(KeyId::Letter(T)),
-        "U" => Some(KeyId::Letter(U)),
-        "V" => Some(KeyId::Letter(V)),
-        "W" => Some(KeyId::Letter(W)),
-        "X" => Some(KeyId::Letter(X)),
-        "Y" => Some(KeyId::Letter(Y)),
-        "Z" => Some(KeyId::Letter(Z)),
-        // digits
-        "0" => Some(KeyId::Digit(0)),
-        "1" => Some(KeyId::Digit(1)),
-        "2" => Some(KeyId::Digit(2)),
-        "3" => Some(KeyId::Digit(3)),
-        "4" => Some(KeyId::Digit(4)),
-        "5" => Some(KeyId::Digit(5)),
-        "6" => Some(KeyId::Digit(6)),
-        "7" => Some(KeyId::Digit(7)),
-        "8" => Some(KeyId::Digit(8)),
-        "9" => Some(KeyId::Digit(9)),
-        _ => None, // まだ必要性がないのでここまで
-    }
+    // letters and digits only; まだ必要性がないのでここまで
+    KeyId::from_label(str)
+        .filter(|k| matches!(k, KeyId::Letter(_) | KeyId::Digit(_)) && k.label() == str)
 }
 
+/// Parses a key_logger label (or an alias) into a key the optimizer handles.
+///
+/// Function, navigation, numpad and layer keys are parsed by `key_id` but not optimized yet.
 pub fn parse_key_label(label: &str) -> Option<KeyId> {
-    use ArrowKey::*;
     use KeyId::*;
-    use LetterKey::*;
-    use SymbolKey::*;
-    //use ModifierKey::*;
-
-    let s = label.trim();
-    if s.is_empty() {
-        return None;
-    }
-
-    // One character keys
-    // - ASCII alphabetic characters
-    // - ASCII digits are considered keys (0-9)
-    if s.len() == 1 {
-        let ch = s.as_bytes()[0];
-        if ch.is_ascii_alphabetic() {
-            match ch.to_ascii_uppercase() {
-                b'A' => return Some(Letter(A)),
-                b'B' => return Some(Letter(B)),
-                b'C' => return Some(Letter(C)),
-                b'D' => return Some(Letter(D)),
-                b'E' => return Some(Letter(E)),
-                b'F' => return Some(Letter(F)),
-                b'G' => return Some(Letter(G)),
-                b'H' => return Some(Letter(H)),
-                b'I' => return Some(Letter(I)),
-                b'J' => return Some(Letter(J)),
-                b'K' => return Some(Letter(K)),
-                b'L' => return Some(Letter(L)),
-                b'M' => return Some(Letter(M)),
-                b'N' => return Some(Letter(N)),
-                b'O' => return Some(Letter(O)),
-                b'P' => return Some(Letter(P)),
-                b'Q' => return Some(Letter(Q)),
-                b'R' => return Some(Letter(R)),
-                b'S' => return Some(Letter(S)),
-                b'T' => return Some(Letter(T)),
-                b'U' => return Some(Letter(U)),
-                b'V' => return Some(Letter(V)),
-                b'W' => return Some(Letter(W)),
-                b'X' => return Some(Letter(X)),
-                b'Y' => return Some(Letter(Y)),
-                b'Z' => return Some(Letter(Z)),
-                _ => return None,
-            };
-        }
-        if ch.is_ascii_digit() {
-            return Some(Digit(ch - b'0'));
-        }
-    }
-
-    let t = s.to_ascii_lowercase();
-    let t = t.as_str();
-
-    if let Some(rest) = t.strip_prefix("key")
-        && let Ok(n) = rest.parse::<u8>()
-        && n <= MAX_DIGIT
-    {
-        return Some(Digit(n));
-    }
-
-    // Symbol keys (US layout)
-    match t {
-        "grave" | "`" => return Some(Symbol(Backtick)),
-        "minus" | "-" => return Some(Symbol(Minus)),
-        "equal" | "=" => return Some(Symbol(Equal)),
-        "leftbracket" | "[" => return Some(Symbol(LBracket)),
-        "rightbracket" | "]" => return Some(Symbol(RBracket)),
-        "backslash" | "\\" => return Some(Symbol(Backslash)),
-        "semicolon" | ";" => return Some(Symbol(Semicolon)),
-        "apostrophe" | "'" | "unknown" => return Some(Symbol(Quote)), // キーロガーの初期不具合のため
-        "comma" | "," => return Some(Symbol(Comma)),
-        "period" | "dot" | "." => return Some(Symbol(Period)),
-        "slash" | "/" => return Some(Symbol(Slash)),
-        _ => {}
-    }
-
-    match t {
-        "tab" => return Some(Tab),
-        "escape" => return Some(Escape),
-        "capslock" => return Some(CapsLock),
-        "delete" => return Some(Delete),
-        "backspace" => return Some(Backspace),
-        "space" | "spacebar" => return Some(Space),
-        "enter" | "return" => return Some(Enter),
-        "leftshift" => return Some(ShiftL),
-        "rightshift" => return Some(ShiftR),
-        "leftcontrol" => return Some(CtrlL),
-        "rightcontrol" => return Some(CtrlR),
-        "leftalt" | "loption" => return Some(AltL),
-        "rightalt" | "roption" => return Some(AltR),
-        "leftmeta" | "command" => return Some(MetaL),
-        "rightmeta" | "rcommand" => return Some(MetaR),
-        _ => {}
-    }
-
-    match t {
-        "arrowleft" | "left" => return Some(Arrow(Left)),
-        "arrowright" | "right" => return Some(Arrow(Right)),
-        "arrowup" | "up" => return Some(Arrow(Up)),
-        "arrowdown" | "down" => return Some(Arrow(Down)),
-        _ => {}
-    }
-
-    // Modifier keys for layer switching
-    //if opt.include_modifiers {
-    //    match t {
-    //        "layer1" | "modifier1" => return Some(Modifier(Layer1)),
-    //        "layer2" | "modifier2" => return Some(Modifier(Layer2)),
-    //        "layer3" | "modifier3" => return Some(Modifier(Layer3)),
-    //        _ => {}
-    //    }
-    //}
-
-    //if opt.include_navigation {
-    //    match t {
-    //        "home" => return Some(Home),
-    //        "end" => return Some(End),
-    //        "pageup" => return Some(PageUp),
-    //        "pagedown" => return Some(PageDown),
-    //        "insert" => return Some(Insert),
-    //        _ => {}
-    //    }
-    //}
-
-    //if opt.include_fkeys
-    //    && let Some(rest) = t.strip_prefix('f')
-    //    && let Ok(n) = rest.parse::<u8>()
-    //    && 1 <= n
-    //    && n <= opt.fkeys_max
-    //{
-    //    return Some(Function(n));
-    //}
-
-    //if opt.include_numpad
-    //    && let Some(rest) = t.strip_prefix("numpad")
-    //{
-    //    match rest {
-    //        "add" => return Some(NumpadAdd),
-    //        "subtract" => return Some(NumpadSubtract),
-    //        "multiply" => return Some(NumpadMultiply),
-    //        "divide" => return Some(NumpadDivide),
-    //        "enter" => return Some(NumpadEnter),
-    //        "equals" => return Some(NumpadEquals),
-    //        "decimal" => return Some(NumpadDecimal),
-    //        _ => {
-    //            if let Ok(n) = rest.parse::<u8>()
-    //                && n <= MAX_NUMPAD_DIGIT
-    //            {
-    //                return Some(NumpadDigit(n));
-    //            }
-    //        }
-    //    }
-    //}
 
-    None
+    KeyId::from_label(label).filter(|k| {
+        !matches!(
+            k,
+            Function(_)
+                | Modifier(_)
+                | Home
+                | End
+                | PageUp
+                | PageDown
+                | Insert
+                | NumpadDigit(_)
+                | NumpadAdd
+                | NumpadSubtract
+                | NumpadMultiply
+                | NumpadDivide
+                | NumpadEnter
+                | NumpadEquals
+                | NumpadDecimal
+        )
+    })
 }
@@ -4,14 +4,19 @@ pub mod csv_reader;
 pub mod error;
 pub mod geometry;
 pub mod keys;
+pub mod merge;
 pub mod optimize;
+pub mod sqlite_reader;
 
 pub use config::Config;
 pub use constants::{
     DEFAULT_FKEYS_MAX, EXPECTED_COUNT_HEADER, EXPECTED_KEY_HEADER, MAX_DIGIT, MAX_NUMPAD_DIGIT,
     MAX_ROW, MIN_ROW,
 };
-pub use csv_reader::{KeyFreq, read_key_freq};
+pub use csv_reader::{
+    BigramFreq, ChordFreq, DatasetMetadata, KeyFreq, SessionRecord, dataset_warnings,
+    read_bigram_freq, read_chord_freq, read_key_freq, read_metadata, read_sessions,
+};
 pub use error::KbOptError;
 pub use geometry::{Geometry, GeometryName, save_layout};
 pub use keys::{ArrowKey, KeyId, SymbolKey};
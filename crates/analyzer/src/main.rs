@@ -2,11 +2,12 @@ use analyzer::{
     config::Config,
     csv_reader::read_key_freq,
     geometry::{Geometry, save_layout},
+    merge::{InputDisposal, collect_inputs, merge_csv_files},
     optimize::solve_layout,
 };
 use anyhow::Result;
-use clap::Parser;
-use log::{debug, error, info};
+use clap::{Parser, Subcommand};
+use log::{debug, error, info, warn};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -15,11 +16,87 @@ struct Args {
     /// Configuration file path
     #[arg(short, long, default_value = "config/default.toml")]
     config: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Combine key count CSVs into one canonical CSV
+    Merge(MergeArgs),
+}
+
+#[derive(clap::Args)]
+struct MergeArgs {
+    /// Directories, files or glob patterns (e.g. 'csv/keylog_2025-07-*.csv')
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Merged CSV to create
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Delete the inputs once the merged totals are verified
+    #[arg(long, conflicts_with = "archive")]
+    delete: bool,
+
+    /// Move the inputs into this directory once the merged totals are verified
+    #[arg(long, value_name = "DIR")]
+    archive: Option<PathBuf>,
+}
+
+fn merge(args: MergeArgs) -> Result<()> {
+    let inputs = collect_inputs(&args.inputs)?;
+    let disposal = match (args.delete, args.archive) {
+        (true, _) => InputDisposal::Delete,
+        (false, Some(dir)) => InputDisposal::Archive(dir),
+        (false, None) => InputDisposal::Keep,
+    };
+    info!(
+        "Merging {} CSV files into {}",
+        inputs.len(),
+        args.output.display()
+    );
+
+    let merged = merge_csv_files(&inputs, &args.output, &disposal)?;
+    for (label, count) in &merged.unrecognized {
+        warn!(
+            "Unrecognized key label '{}' (count {}) was not merged",
+            label, count
+        );
+    }
+    info!(
+        "Merged {} presses and {} repeats of {} keys",
+        merged.presses.total(),
+        merged.repeats.total(),
+        merged.presses.unique_keys()
+    );
+    match disposal {
+        InputDisposal::Keep => {
+            if inputs
+                .iter()
+                .any(|path| path.parent() == args.output.parent())
+            {
+                warn!(
+                    "The inputs were kept next to the merged file; the analyzer will count them twice"
+                );
+            }
+        }
+        InputDisposal::Delete => info!("Deleted {} input files", inputs.len()),
+        InputDisposal::Archive(dir) => {
+            info!("Moved {} input files to {}", inputs.len(), dir.display())
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let args = Args::parse();
+    if let Some(Command::Merge(merge_args)) = args.command {
+        return merge(merge_args);
+    }
 
     // Load configuration file
     let config = Config::load_from_file(&args.config)?;
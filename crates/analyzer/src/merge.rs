@@ -0,0 +1,573 @@
+//! Combines key_logger count CSVs (`Key,Count[,Repeat]`) into one, so a `csv/` folder
+//! with hundreds of exports can be compacted into a single file.
+//!
+//! Labels are normalized through [`KeyId::from_label`], so aliases written by older
+//! versions end up under their canonical label; every key key_logger records is kept,
+//! including the ones the optimizer ignores. The `.meta.json` sidecars are combined into
+//! one for the merged file. The merged file is read back and its totals compared with the
+//! inputs before any input is deleted or archived.
+
+use crate::{
+    constants::{EXPECTED_COUNT_HEADER, EXPECTED_KEY_HEADER, REPEAT_HEADER},
+    csv_reader::{
+        DatasetMetadata, KeyFreq, get_column_value, is_auxiliary_file, is_csv_file, list_csv_files,
+        metadata_path, parse_count_value, validate_csv_headers,
+    },
+    error::{KbOptError, Result},
+    keys::KeyId,
+};
+
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Sidecar fields that change what the counts mean; inputs must agree on all of them
+const RECORDING_FIELDS: [&str; 5] = [
+    "schema_version",
+    "layout",
+    "backend",
+    "polling_interval_ms",
+    "privacy",
+];
+
+/// What happens to the inputs once the merged file is verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputDisposal {
+    Keep,
+    Delete,
+    /// Moved (with their metadata sidecars) into this directory
+    Archive(PathBuf),
+}
+
+/// Counts of one or more count CSVs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergedCounts {
+    pub presses: KeyFreq,
+    pub repeats: KeyFreq,
+    /// Labels `KeyId::from_label` rejects, with their presses and repeats; not merged
+    pub unrecognized: BTreeMap<String, u64>,
+}
+
+impl MergedCounts {
+    fn merge(&mut self, other: MergedCounts) {
+        self.presses.merge(other.presses);
+        self.repeats.merge(other.repeats);
+        for (label, count) in other.unrecognized {
+            *self.unrecognized.entry(label).or_insert(0) += count;
+        }
+    }
+}
+
+/// Resolves directories, files and glob patterns into the count CSVs they contain.
+///
+/// Auxiliary outputs (bigrams, timing, ...) are skipped, since `keylog_*.csv` also
+/// matches them. The result is sorted and free of duplicates.
+pub fn collect_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+    for input in inputs {
+        let path = Path::new(input);
+        let matched = if path.is_dir() {
+            list_csv_files(path)?
+        } else if path.is_file() {
+            vec![path.to_path_buf()]
+        } else {
+            let paths = glob::glob(input).map_err(|e| {
+                KbOptError::Config(format!("Invalid input pattern '{}': {}", input, e))
+            })?;
+            let mut matched = Vec::new();
+            for path in paths {
+                let path = path.map_err(glob::GlobError::into_error)?;
+                if path.is_file() && is_csv_file(&path) {
+                    matched.push(path);
+                }
+            }
+            if matched.is_empty() {
+                log::warn!("No CSV files match {}", input);
+            }
+            matched
+        };
+        files.extend(matched.into_iter().filter(|path| !is_auxiliary_file(path)));
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Reads one count CSV, keeping presses, repeats and unrecognized labels apart
+pub fn read_counts(path: &Path) -> Result<MergedCounts> {
+    let data = fs::read(path)?;
+    if keylog_crypt::is_encrypted(&data) {
+        return Err(KbOptError::Other(format!(
+            "{} is encrypted; merging encrypted files is not supported",
+            path.display()
+        )));
+    }
+    read_counts_from_reader(&data[..])
+}
+
+fn read_counts_from_reader<R: Read>(reader: R) -> Result<MergedCounts> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    validate_csv_headers(&mut rdr)?;
+    let repeat_column = rdr
+        .headers()?
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(REPEAT_HEADER));
+
+    let mut presses = BTreeMap::new();
+    let mut repeats = BTreeMap::new();
+    let mut unrecognized = BTreeMap::new();
+    for (i, result) in rdr.records().enumerate() {
+        let rec = result?;
+        let row = i + 2; // CSV rows are 1-indexed, +1 for header
+        if rec.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let label = get_column_value(&rec, 0, row)?;
+        if label.is_empty() {
+            continue;
+        }
+        let count = parse_count_value(get_column_value(&rec, 1, row)?, row)?;
+        // A blank or missing cell means no repeats were recorded for the key
+        let repeat = match repeat_column
+            .and_then(|column| rec.get(column))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => parse_count_value(value, row)?,
+            None => 0,
+        };
+
+        match KeyId::from_label(label) {
+            Some(kid) => {
+                *presses.entry(kid).or_insert(0) += count;
+                *repeats.entry(kid).or_insert(0) += repeat;
+            }
+            None => *unrecognized.entry(label.to_string()).or_insert(0) += count + repeat,
+        }
+    }
+
+    Ok(MergedCounts {
+        presses: KeyFreq::from_counts(presses.into_iter().collect()),
+        repeats: KeyFreq::from_counts(repeats.into_iter().filter(|&(_, n)| n > 0).collect()),
+        unrecognized,
+    })
+}
+
+/// Formats `Key,Count,Repeat` rows sorted by descending count, like key_logger does
+fn format_counts(counts: &MergedCounts) -> Result<Vec<u8>> {
+    let keys: BTreeSet<KeyId> = counts
+        .presses
+        .counts()
+        .keys()
+        .chain(counts.repeats.counts().keys())
+        .copied()
+        .collect();
+    let mut rows: Vec<(&str, u64, u64)> = keys
+        .into_iter()
+        .map(|key| {
+            (
+                key.label(),
+                counts.presses.get_count(key),
+                counts.repeats.get_count(key),
+            )
+        })
+        .collect();
+    rows.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut wtr = WriterBuilder::new().from_writer(Vec::new());
+    wtr.write_record([EXPECTED_KEY_HEADER, EXPECTED_COUNT_HEADER, REPEAT_HEADER])?;
+    for (key, count, repeat) in rows {
+        wtr.write_record([key, count.to_string().as_str(), repeat.to_string().as_str()])?;
+    }
+    wtr.into_inner().map_err(|e| KbOptError::Io(e.into_error()))
+}
+
+/// Reads the sidecar of every input and combines them into one for the merged file.
+///
+/// Returns `None` when no input has a sidecar. Merging is refused when only some inputs
+/// have one, or when they disagree on the layout, backend or privacy settings, since the
+/// merged file could not say how its counts were recorded. The merged session spans
+/// from the earliest start to the latest end.
+fn merged_metadata(inputs: &[PathBuf]) -> Result<Option<Value>> {
+    let mut sidecars = Vec::new();
+    for path in inputs {
+        let meta_path = metadata_path(path);
+        if meta_path.is_file() {
+            let value: Value = serde_json::from_str(&fs::read_to_string(&meta_path)?)?;
+            let session: DatasetMetadata = serde_json::from_value(value.clone())?;
+            sidecars.push((meta_path, value, session));
+        }
+    }
+    let Some((first_path, first, _)) = sidecars.first() else {
+        return Ok(None);
+    };
+    if sidecars.len() < inputs.len() {
+        return Err(KbOptError::Other(format!(
+            "Refusing to merge: {} of {} inputs have no .meta.json, so the merged file could not record their layout and privacy settings",
+            inputs.len() - sidecars.len(),
+            inputs.len()
+        )));
+    }
+    for (path, value, _) in &sidecars[1..] {
+        for field in RECORDING_FIELDS {
+            if value.get(field) != first.get(field) {
+                return Err(KbOptError::Other(format!(
+                    "Refusing to merge: {} has {} {} but {} has {}",
+                    first_path.display(),
+                    field,
+                    first.get(field).unwrap_or(&Value::Null),
+                    path.display(),
+                    value.get(field).unwrap_or(&Value::Null)
+                )));
+            }
+        }
+    }
+
+    let mut merged = first.clone();
+    let span = |pick: fn(&DatasetMetadata) -> Option<_>, field: &str, latest: bool| {
+        let values = sidecars
+            .iter()
+            .filter_map(|(_, value, session)| Some((pick(session)?, value.get(field)?)));
+        let value = if latest {
+            values.max_by_key(|&(time, _)| time)
+        } else {
+            values.min_by_key(|&(time, _)| time)
+        };
+        value.map(|(_, value)| value.clone())
+    };
+    let start = span(|m| m.session_start, "session_start", false);
+    let end = span(|m| m.session_end, "session_end", true);
+    if let Some(object) = merged.as_object_mut() {
+        for (field, value) in [("session_start", start), ("session_end", end)] {
+            match value {
+                Some(value) => object.insert(field.to_string(), value),
+                None => object.remove(field),
+            };
+        }
+        if sidecars
+            .iter()
+            .any(|(_, value, _)| value.get("key_logger_version") != first.get("key_logger_version"))
+        {
+            object.remove("key_logger_version");
+        }
+        object.insert("merged_files".to_string(), inputs.len().into());
+    }
+    Ok(Some(merged))
+}
+
+/// Writes `data` next to `path` and renames it into place once it is on disk
+fn write_synced(path: &Path, data: &[u8]) -> Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let mut file = File::create(&partial)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Flushes the renames in `dir` to disk; directories cannot be opened for this on Windows
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    if cfg!(unix) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Moves `from` to `to`, copying only when they are on different file systems
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            File::open(to)?.sync_all()?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Merges `inputs` into a new CSV at `output`, then disposes of the inputs.
+///
+/// The output and its sidecar must not exist yet. Both are written next to their final
+/// names, synced and renamed into place, then the CSV is read back; if its counts differ
+/// from the inputs' both are removed and every input is left alone. Deleting is refused
+/// when some labels were not recognized, since their counts would be lost; archiving
+/// keeps them.
+pub fn merge_csv_files(
+    inputs: &[PathBuf],
+    output: &Path,
+    disposal: &InputDisposal,
+) -> Result<MergedCounts> {
+    if inputs.is_empty() {
+        return Err(KbOptError::Other("No CSV files to merge".to_string()));
+    }
+    let output_metadata = metadata_path(output);
+    for path in [output, output_metadata.as_path()] {
+        if path.exists() {
+            return Err(KbOptError::Other(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+    }
+    let mut merged = MergedCounts::default();
+    for path in inputs {
+        let counts = read_counts(path)
+            .map_err(|e| KbOptError::Other(format!("Failed to read {}: {}", path.display(), e)))?;
+        merged.merge(counts);
+    }
+    let metadata = merged_metadata(inputs)?;
+    if disposal == &InputDisposal::Delete && !merged.unrecognized.is_empty() {
+        return Err(KbOptError::Other(format!(
+            "Refusing to delete the inputs: {} unrecognized keys would be lost (archive them instead)",
+            merged.unrecognized.len()
+        )));
+    }
+    if let InputDisposal::Archive(dir) = disposal {
+        let mut names = HashSet::new();
+        for file in inputs
+            .iter()
+            .flat_map(|path| [path.clone(), metadata_path(path)])
+        {
+            let name = file.file_name().unwrap_or_default().to_owned();
+            if file.is_file() && !names.insert(name.clone()) {
+                return Err(KbOptError::Other(format!(
+                    "Refusing to archive: more than one input is named {}",
+                    name.to_string_lossy()
+                )));
+            }
+        }
+        fs::create_dir_all(dir)?;
+        for name in &names {
+            let target = dir.join(name);
+            if target.exists() {
+                return Err(KbOptError::Other(format!(
+                    "{} already exists",
+                    target.display()
+                )));
+            }
+        }
+    }
+
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    write_synced(output, &format_counts(&merged)?)?;
+    if let Some(metadata) = &metadata {
+        let json = serde_json::to_string_pretty(metadata)? + "\n";
+        write_synced(&output_metadata, json.as_bytes())?;
+    }
+    sync_dir(parent)?;
+
+    let written = read_counts(output)?;
+    if written.presses != merged.presses || written.repeats != merged.repeats {
+        fs::remove_file(output)?;
+        if metadata.is_some() {
+            fs::remove_file(&output_metadata)?;
+        }
+        return Err(KbOptError::Other(format!(
+            "Merged totals do not match the inputs ({} presses and {} repeats written, {} and {} read); inputs were left untouched",
+            written.presses.total(),
+            written.repeats.total(),
+            merged.presses.total(),
+            merged.repeats.total()
+        )));
+    }
+
+    for path in inputs {
+        let metadata = Some(metadata_path(path)).filter(|meta| meta.is_file());
+        for file in std::iter::once(path).chain(metadata.as_ref()) {
+            match disposal {
+                InputDisposal::Keep => {}
+                InputDisposal::Delete => fs::remove_file(file)?,
+                InputDisposal::Archive(dir) => {
+                    move_file(file, &dir.join(file.file_name().unwrap_or_default()))?
+                }
+            }
+        }
+    }
+    if let InputDisposal::Archive(dir) = disposal {
+        sync_dir(dir)?;
+    }
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_input(dir: &Path, name: &str, csv: &str, layout: Option<&str>) -> PathBuf {
+        let path = dir.join(format!("{name}.csv"));
+        fs::write(&path, csv).unwrap();
+        if let Some(layout) = layout {
+            let meta = serde_json::json!({
+                "schema_version": 1,
+                "key_logger_version": "0.1.0",
+                "session_start": format!("2025-07-0{}T09:00:00+09:00", name.len() - 7),
+                "session_end": format!("2025-07-0{}T18:00:00+09:00", name.len() - 7),
+                "backend": "evdev",
+                "polling_interval_ms": null,
+                "layout": layout,
+            });
+            fs::write(metadata_path(&path), meta.to_string()).unwrap();
+        }
+        path
+    }
+
+    fn sample_inputs(dir: &Path, layout: Option<&str>) -> Vec<PathBuf> {
+        vec![
+            write_input(
+                dir,
+                "keylog_a",
+                "Key,Count,Repeat\nA,5,1\nF5,3,0\nNumpad1,2,0\nLeftShift,4,\n",
+                layout,
+            ),
+            write_input(
+                dir,
+                "keylog_bb",
+                "Key,Count\na,1\nkey1,2\nNumpadEnter,1\nBogus,7\n",
+                layout,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_merge_keeps_every_known_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let inputs = sample_inputs(temp_dir.path(), None);
+        let output = temp_dir.path().join("out/merged.csv");
+
+        let merged = merge_csv_files(&inputs, &output, &InputDisposal::Keep).unwrap();
+        assert_eq!(merged.presses.get_count(KeyId::from_label("A").unwrap()), 6);
+        assert_eq!(merged.presses.get_count(KeyId::Function(5)), 3);
+        assert_eq!(merged.presses.get_count(KeyId::NumpadDigit(1)), 2);
+        assert_eq!(merged.presses.get_count(KeyId::NumpadEnter), 1);
+        assert_eq!(
+            merged
+                .presses
+                .get_count(KeyId::from_label("LeftShift").unwrap()),
+            4
+        );
+        assert_eq!(merged.presses.get_count(KeyId::Digit(1)), 2);
+        assert_eq!(merged.repeats.total(), 1);
+        assert_eq!(merged.unrecognized, BTreeMap::from([("Bogus".into(), 7)]));
+
+        // The written file reads back to the same counts, and the inputs are untouched
+        assert_eq!(read_counts(&output).unwrap().presses, merged.presses);
+        assert!(fs::read_to_string(&output).unwrap().contains("F5,3,0"));
+        assert!(!metadata_path(&output).exists());
+        assert!(inputs.iter().all(|path| path.is_file()));
+        assert!(merge_csv_files(&inputs, &output, &InputDisposal::Keep).is_err());
+    }
+
+    #[test]
+    fn test_merge_delete_writes_combined_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut inputs = sample_inputs(temp_dir.path(), Some("JIS"));
+        let output = temp_dir.path().join("merged.csv");
+
+        // Bogus would be lost
+        assert!(merge_csv_files(&inputs, &output, &InputDisposal::Delete).is_err());
+        assert!(!output.exists());
+
+        fs::write(&inputs[1], "Key,Count\nF12,9\n").unwrap();
+        inputs.push(write_input(
+            temp_dir.path(),
+            "keylog_ccc",
+            "Key,Count\nB,1\n",
+            Some("JIS"),
+        ));
+        let merged = merge_csv_files(&inputs, &output, &InputDisposal::Delete).unwrap();
+        assert_eq!(merged.presses.total(), 5 + 3 + 2 + 4 + 9 + 1);
+        for path in &inputs {
+            assert!(!path.exists());
+            assert!(!metadata_path(path).exists());
+        }
+
+        let meta: Value =
+            serde_json::from_str(&fs::read_to_string(metadata_path(&output)).unwrap()).unwrap();
+        assert_eq!(meta["layout"], "JIS");
+        assert_eq!(meta["backend"], "evdev");
+        assert_eq!(meta["session_start"], "2025-07-01T09:00:00+09:00");
+        assert_eq!(meta["session_end"], "2025-07-03T18:00:00+09:00");
+        assert_eq!(meta["merged_files"], 3);
+    }
+
+    #[test]
+    fn test_merge_archive_moves_inputs_and_sidecars() {
+        let temp_dir = TempDir::new().unwrap();
+        let inputs = sample_inputs(temp_dir.path(), Some("US"));
+        let output = temp_dir.path().join("merged.csv");
+        let archive = temp_dir.path().join("archive");
+
+        merge_csv_files(&inputs, &output, &InputDisposal::Archive(archive.clone())).unwrap();
+        for path in &inputs {
+            assert!(!path.exists());
+            let archived = archive.join(path.file_name().unwrap());
+            assert!(archived.is_file());
+            assert!(metadata_path(&archived).is_file());
+        }
+        assert!(metadata_path(&output).is_file());
+    }
+
+    #[test]
+    fn test_merge_archive_refuses_duplicate_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let inputs: Vec<PathBuf> = ["a", "b"]
+            .iter()
+            .map(|sub| {
+                let dir = temp_dir.path().join(sub);
+                fs::create_dir(&dir).unwrap();
+                write_input(&dir, "keylog_2", "Key,Count\nA,1\n", Some("US"))
+            })
+            .collect();
+        let output = temp_dir.path().join("merged.csv");
+        let archive = temp_dir.path().join("archive");
+
+        let err = merge_csv_files(&inputs, &output, &InputDisposal::Archive(archive.clone()))
+            .unwrap_err();
+        assert!(err.to_string().contains("keylog_2.csv"), "{err}");
+        assert!(!output.exists());
+        assert!(!archive.exists());
+        assert!(inputs.iter().all(|path| path.is_file()));
+    }
+
+    #[test]
+    fn test_merge_refuses_mixed_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut inputs = sample_inputs(temp_dir.path(), Some("US"));
+        inputs.push(write_input(
+            temp_dir.path(),
+            "keylog_jis",
+            "Key,Count\nA,1\n",
+            Some("JIS"),
+        ));
+        inputs.push(write_input(
+            temp_dir.path(),
+            "keylog_bare",
+            "Key,Count\nA,1\n",
+            None,
+        ));
+        let output = temp_dir.path().join("merged.csv");
+
+        for inputs in [&inputs[..3], &[inputs[0].clone(), inputs[3].clone()]] {
+            let err = merge_csv_files(inputs, &output, &InputDisposal::Delete).unwrap_err();
+            assert!(err.to_string().contains("Refusing to merge"), "{err}");
+            assert!(!output.exists());
+            assert!(inputs.iter().all(|path| path.is_file()));
+        }
+    }
+}
@@ -125,6 +125,9 @@ pub fn all_movable_keys(config: &Config) -> Vec<KeyId> {
         AltR, MetaL, MetaR,
     ]);
 
+    // ISO・JIS固有のキー (¥、ろ、変換など)
+    v.extend(config.physical_layout().extra_keys());
+
     if config.solver.include_fkeys {
         for n in 1..=DEFAULT_FKEYS_MAX {
             v.push(Function(n));
@@ -1,7 +1,7 @@
 // Phase 5: ビグラム近似の実装準備
 // このファイルは Phase 5 実装時に詳細化される
 
-use crate::error::KbOptError;
+use crate::{csv_reader::read_bigram_freq_csv, error::KbOptError};
 use std::collections::HashMap;
 
 /// ビグラム近似手法
@@ -58,8 +58,13 @@ where
     todo!("Phase 5: bigram linearization constraints not yet implemented")
 }
 
-/// Phase 5で実装予定: ビグラム頻度データの読み込み
-pub fn load_bigram_data(_path: &str) -> Result<BigramData, KbOptError> {
-    // Phase 5で実装
-    todo!("Phase 5: bigram data loading not yet implemented")
+/// key_logger の `keylog_bigrams_*.csv` (From,To,Count) からビグラム頻度データを読み込む
+pub fn load_bigram_data(path: &str) -> Result<BigramData, KbOptError> {
+    let freq = read_bigram_freq_csv(path)?;
+    let frequencies = freq
+        .counts()
+        .iter()
+        .map(|(&(from, to), &count)| ((from.to_string(), to.to_string()), count as f64))
+        .collect();
+    Ok(BigramData { frequencies })
 }
@@ -0,0 +1,418 @@
+//! Reads the append-only SQLite store key_logger writes with `--format sqlite`.
+//!
+//! Every export is a row of `exports` with its counts in the statistic tables, so summing
+//! the rows of the exports in the date range gives the same data as the CSV files would.
+
+use crate::{
+    config::Config,
+    constants::{FILE_DATE_FORMAT, FILE_DATE_LEN, SUPPORTED_DATABASE_SCHEMA},
+    csv_reader::{
+        BigramFreq, ChordFreq, DatasetMetadata, KeyFreq, SessionRecord, parse_modifier_set,
+    },
+    error::{KbOptError, Result},
+    keys::{KeyId, parse_key_label},
+};
+
+use chrono::{DateTime, NaiveDate};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Opens the store read-only, so a running key_logger can keep appending
+fn open_database(path: &Path) -> Result<Connection> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SUPPORTED_DATABASE_SCHEMA {
+        return Err(KbOptError::Other(format!(
+            "{} uses database schema {}, but this analyzer supports up to {}",
+            path.display(),
+            version,
+            SUPPORTED_DATABASE_SCHEMA
+        )));
+    }
+    Ok(conn)
+}
+
+/// Ids of the exports within `date_from`..=`date_to`.
+///
+/// An export is dated by its timestamp (the date the CSV files would have been named
+/// after), or by its session start if the timestamp does not start with a date.
+fn exports_in_range(config: &Config, conn: &Connection) -> Result<HashSet<i64>> {
+    let (from, to) = (config.solver.date_from, config.solver.date_to);
+    let mut stmt = conn.prepare("SELECT id, timestamp, session_start FROM exports")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+
+    let mut ids = HashSet::new();
+    for row in rows {
+        let (id, timestamp, session_start) = row?;
+        if from.is_none() && to.is_none() {
+            ids.insert(id);
+            continue;
+        }
+        let date = timestamp
+            .get(..FILE_DATE_LEN)
+            .and_then(|date| NaiveDate::parse_from_str(date, FILE_DATE_FORMAT).ok())
+            .or_else(|| {
+                session_start
+                    .and_then(|start| DateTime::parse_from_rfc3339(&start).ok())
+                    .map(|start| start.date_naive())
+            });
+        let Some(date) = date else {
+            log::warn!("Skipping export {}: no date in its timestamp", timestamp);
+            continue;
+        };
+        if from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to) {
+            ids.insert(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Reads the key counts of every export in the date range.
+///
+/// With `devices` set, only the per-device counts of those devices are used.
+pub fn read_key_freq_db(config: &Config, path: &Path) -> Result<KeyFreq> {
+    let conn = open_database(path)?;
+    let exports = exports_in_range(config, &conn)?;
+    let include_repeats = config.solver.include_repeats;
+
+    let mut counts: HashMap<KeyId, u64> = HashMap::new();
+    let mut used = BTreeSet::new();
+    let mut found = BTreeSet::new();
+    let wanted: Option<HashSet<&str>> = config
+        .solver
+        .devices
+        .as_ref()
+        .map(|devices| devices.iter().map(|name| name.trim()).collect());
+
+    let query = if wanted.is_some() {
+        "SELECT export_id, key, count, repeat, device FROM devices"
+    } else {
+        "SELECT export_id, key, count, repeat, NULL FROM key_counts"
+    };
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (export_id, key, count, repeat, device) = row?;
+        if !exports.contains(&export_id) {
+            continue;
+        }
+        if let (Some(wanted), Some(device)) = (&wanted, device) {
+            let keep = wanted.contains(device.as_str());
+            found.insert(device);
+            if !keep {
+                continue;
+            }
+        }
+        let Some(kid) = parse_key_label(&key) else {
+            continue;
+        };
+        let repeats = if include_repeats { repeat } else { 0 };
+        *counts.entry(kid).or_insert(0) += (count + repeats).max(0) as u64;
+        used.insert(export_id);
+    }
+
+    let freq = KeyFreq::from_counts(counts);
+    if freq.is_empty() {
+        return Err(match config.solver.devices {
+            Some(ref devices) => {
+                let found = if found.is_empty() {
+                    "none; record with --per-device".to_string()
+                } else {
+                    found.into_iter().collect::<Vec<_>>().join(", ")
+                };
+                KbOptError::Other(format!(
+                    "No key presses from devices {:?} in {} (devices found: {})",
+                    devices,
+                    path.display(),
+                    found
+                ))
+            }
+            None => KbOptError::Other(format!("No key counts found in {}", path.display())),
+        });
+    }
+
+    log::info!(
+        "Processed {} of {} exports from {}",
+        used.len(),
+        exports.len(),
+        path.display()
+    );
+    Ok(freq)
+}
+
+/// Describes every export in the date range by the metadata stored with it, for
+/// [`dataset_warnings`](crate::csv_reader::dataset_warnings).
+pub fn read_datasets_db(
+    config: &Config,
+    path: &Path,
+) -> Result<Vec<(PathBuf, Option<DatasetMetadata>)>> {
+    let conn = open_database(path)?;
+    let exports = exports_in_range(config, &conn)?;
+
+    let mut stmt = conn.prepare("SELECT id, timestamp, metadata FROM exports ORDER BY id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+    let mut datasets = Vec::new();
+    for row in rows {
+        let (id, timestamp, metadata) = row?;
+        if !exports.contains(&id) {
+            continue;
+        }
+        let name = PathBuf::from(format!("{} export {}", path.display(), timestamp));
+        let metadata = metadata.and_then(|json| {
+            serde_json::from_str(&json)
+                .inspect_err(|e| {
+                    log::warn!("Ignoring unreadable metadata of {}: {}", name.display(), e)
+                })
+                .ok()
+        });
+        datasets.push((name, metadata));
+    }
+    Ok(datasets)
+}
+
+/// Reads the bigram counts of every export in the date range.
+///
+/// Like the CSV files, a store without bigrams gives an empty result.
+pub fn read_bigram_freq_db(config: &Config, path: &Path) -> Result<BigramFreq> {
+    let conn = open_database(path)?;
+    let exports = exports_in_range(config, &conn)?;
+
+    let mut counts: HashMap<(KeyId, KeyId), u64> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT export_id, from_key, to_key, count FROM bigrams")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (export_id, from, to, count) = row?;
+        if !exports.contains(&export_id) {
+            continue;
+        }
+        if let (Some(from), Some(to)) = (parse_key_label(&from), parse_key_label(&to)) {
+            *counts.entry((from, to)).or_insert(0) += count.max(0) as u64;
+        }
+    }
+
+    log::info!(
+        "Read bigrams of {} exports from {}",
+        exports.len(),
+        path.display()
+    );
+    Ok(BigramFreq::from_counts(counts))
+}
+
+/// Reads the chord counts of every export in the date range.
+///
+/// Like the CSV files, a store without chords gives an empty result.
+pub fn read_chord_freq_db(config: &Config, path: &Path) -> Result<ChordFreq> {
+    let conn = open_database(path)?;
+    let exports = exports_in_range(config, &conn)?;
+
+    let mut counts: HashMap<(Vec<KeyId>, KeyId), u64> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT export_id, modifiers, key, count FROM chords")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (export_id, modifiers, key, count) = row?;
+        if !exports.contains(&export_id) {
+            continue;
+        }
+        if let (Some(modifiers), Some(key)) =
+            (parse_modifier_set(&modifiers), parse_key_label(&key))
+        {
+            *counts.entry((modifiers, key)).or_insert(0) += count.max(0) as u64;
+        }
+    }
+
+    log::info!(
+        "Read chords of {} exports from {}",
+        exports.len(),
+        path.display()
+    );
+    Ok(ChordFreq::from_counts(counts))
+}
+
+/// Reads every recorded session of the exports in the date range, oldest first.
+pub fn read_sessions_db(config: &Config, path: &Path) -> Result<Vec<SessionRecord>> {
+    let conn = open_database(path)?;
+    let exports = exports_in_range(config, &conn)?;
+
+    let mut keys: HashMap<(i64, i64), HashMap<KeyId, u64>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT export_id, session, key, count FROM session_keys")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (export_id, session, key, count) = row?;
+        if let (true, Some(kid)) = (exports.contains(&export_id), parse_key_label(&key)) {
+            *keys
+                .entry((export_id, session))
+                .or_default()
+                .entry(kid)
+                .or_insert(0) += count.max(0) as u64;
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT export_id, session, start_time, end_time, keystrokes, keys_per_minute
+         FROM sessions ORDER BY export_id, session",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, Option<f64>>(5)?,
+        ))
+    })?;
+
+    let mut sessions = Vec::new();
+    for row in rows {
+        let (export_id, session, start, end, keystrokes, keys_per_minute) = row?;
+        if !exports.contains(&export_id) {
+            continue;
+        }
+        let time = |value: &str| {
+            DateTime::parse_from_rfc3339(value).map_err(|e| {
+                KbOptError::Other(format!(
+                    "Invalid time in session {} of export {}: {} ({})",
+                    session, export_id, value, e
+                ))
+            })
+        };
+        sessions.push(SessionRecord {
+            start: time(&start)?,
+            end: time(&end)?,
+            keystrokes: keystrokes.max(0) as u64,
+            keys_per_minute,
+            freq: KeyFreq::from_counts(keys.remove(&(export_id, session)).unwrap_or_default()),
+        });
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::{dataset_warnings, read_bigram_freq, read_chord_freq, read_key_freq};
+    use tempfile::TempDir;
+
+    /// The tables key_logger creates, so the test store matches what it writes
+    const KEY_LOGGER_SCHEMA: &str = include_str!("../../key_logger/src/export/schema.sql");
+
+    /// Writes one export per day, the first recorded on a US board and the second on JIS
+    fn write_store(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(KEY_LOGGER_SCHEMA).unwrap();
+        conn.pragma_update(None, "user_version", SUPPORTED_DATABASE_SCHEMA)
+            .unwrap();
+        for (id, day, layout) in [(1, "01", "US"), (2, "02", "JIS")] {
+            let start = format!("2025-07-{day}T09:00:00.000+09:00");
+            let end = format!("2025-07-{day}T18:00:00.000+09:00");
+            let metadata = serde_json::json!({
+                "schema_version": 1,
+                "session_start": start,
+                "session_end": end,
+                "backend": "evdev",
+                "layout": layout,
+            });
+            conn.execute(
+                "INSERT INTO exports (id, timestamp, exported_at, session_start, session_end, metadata)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![
+                    id,
+                    format!("2025-07-{day}_18-00-00"),
+                    end,
+                    start,
+                    end,
+                    metadata.to_string()
+                ],
+            )
+            .unwrap();
+            conn.execute_batch(&format!(
+                "INSERT INTO key_counts VALUES ({id}, 'A', 5, 2), ({id}, 'F5', 1, 0);
+                 INSERT INTO bigrams VALUES ({id}, 'A', 'B', 3);
+                 INSERT INTO chords VALUES ({id}, 'LeftControl+LeftShift', 'T', 1);"
+            ))
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reads_store_written_by_key_logger() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keylog.sqlite");
+        write_store(&path);
+
+        let mut config = Config::default();
+        config.solver.database = Some(path.to_string_lossy().into_owned());
+        let a = KeyId::from_label("A").unwrap();
+        let b = KeyId::from_label("B").unwrap();
+        let t = KeyId::from_label("T").unwrap();
+        let mut ctrl_shift = vec![KeyId::CtrlL, KeyId::ShiftL];
+        ctrl_shift.sort();
+
+        let keys = read_key_freq(&config).unwrap();
+        assert_eq!(keys.get_count(a), 10);
+        assert_eq!(read_bigram_freq(&config).unwrap().get_count(a, b), 6);
+        let chords = read_chord_freq(&config).unwrap();
+        assert_eq!(chords.counts().get(&(ctrl_shift.clone(), t)), Some(&2));
+
+        let datasets = read_datasets_db(&config, &path).unwrap();
+        assert_eq!(datasets.len(), 2);
+        assert!(
+            dataset_warnings(&datasets)
+                .iter()
+                .any(|warning| warning.starts_with("Mixed physical layouts"))
+        );
+
+        // The date range applies to every table
+        config.solver.date_from = NaiveDate::from_ymd_opt(2025, 7, 2);
+        config.solver.include_repeats = true;
+        assert_eq!(read_key_freq(&config).unwrap().get_count(a), 7);
+        assert_eq!(read_bigram_freq(&config).unwrap().get_count(a, b), 3);
+        let chords = read_chord_freq(&config).unwrap();
+        assert_eq!(chords.counts().get(&(ctrl_shift, t)), Some(&1));
+        assert_eq!(read_datasets_db(&config, &path).unwrap().len(), 1);
+    }
+}
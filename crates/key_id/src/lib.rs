@@ -0,0 +1,666 @@
+//! Key identities shared by `key_logger` and `analyzer`.
+//!
+//! `key_logger` writes [`KeyId::label`] into its CSV files and `analyzer` reads them back with
+//! [`KeyId::from_label`], so both sides always agree on what a label means. Older spellings
+//! are accepted through [`ALIASES`].
+
+use std::fmt;
+
+/// Highest digit on the number row and the numeric keypad
+pub const MAX_DIGIT: u8 = 9;
+/// Highest function key that has a label
+pub const MAX_FUNCTION_KEY: u8 = 20;
+
+/// letter keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LetterKey {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+}
+
+impl LetterKey {
+    pub const ALL: [LetterKey; 26] = {
+        use LetterKey::*;
+        [
+            A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        ]
+    };
+}
+
+/// Symbol keys
+///
+/// The first 11 are on every layout; the rest only on ISO or JIS boards
+/// (see [`PhysicalLayout::extra_keys`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SymbolKey {
+    Backtick,  // `
+    Minus,     // -
+    Equal,     // =
+    LBracket,  // [
+    RBracket,  // ]
+    Backslash, // \
+    Semicolon, // ;
+    Quote,     // '
+    Comma,     // ,
+    Period,    // .
+    Slash,     // /
+    // ISO
+    IntlBackslash, // \ between left Shift and Z
+    // JIS
+    Yen, // ¥ between = and Backspace
+    Ro,  // \ between / and right Shift
+}
+
+impl SymbolKey {
+    pub const ALL: [SymbolKey; 14] = {
+        use SymbolKey::*;
+        [
+            Backtick,
+            Minus,
+            Equal,
+            LBracket,
+            RBracket,
+            Backslash,
+            Semicolon,
+            Quote,
+            Comma,
+            Period,
+            Slash,
+            IntlBackslash,
+            Yen,
+            Ro,
+        ]
+    };
+}
+
+/// Arrow keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ArrowKey {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+impl ArrowKey {
+    pub const ALL: [ArrowKey; 4] = [
+        ArrowKey::Left,
+        ArrowKey::Down,
+        ArrowKey::Up,
+        ArrowKey::Right,
+    ];
+}
+
+/// Modifier keys for layer switching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ModifierKey {
+    Layer1,
+    Layer2,
+    Layer3,
+}
+
+impl ModifierKey {
+    pub const ALL: [ModifierKey; 3] = [
+        ModifierKey::Layer1,
+        ModifierKey::Layer2,
+        ModifierKey::Layer3,
+    ];
+}
+
+/// Optimized key identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyId {
+    // letter
+    Letter(LetterKey),
+    // digit
+    Digit(u8), // 0..9
+    // symbols
+    Symbol(SymbolKey),
+    Tab,
+    Escape,
+    CapsLock,
+    Delete,
+    Backspace,
+    Space,
+    Enter,
+    ShiftL,
+    ShiftR,
+    CtrlL,
+    CtrlR,
+    AltL,
+    AltR,
+    MetaL,
+    MetaR,
+    Function(u8), // 1..20
+    Arrow(ArrowKey),
+    Modifier(ModifierKey),
+    // navigation keys
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    // numeric keypad keys
+    NumpadDigit(u8), // 0..9
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadEnter,
+    NumpadEquals,
+    NumpadDecimal,
+    // JIS input method keys around the space bar
+    Muhenkan,
+    Henkan,
+    KatakanaHiragana,
+}
+
+const LETTER_LABELS: [&str; 26] = [
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
+    "T", "U", "V", "W", "X", "Y", "Z",
+];
+const DIGIT_LABELS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+const FUNCTION_LABELS: [&str; MAX_FUNCTION_KEY as usize] = [
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", "F13", "F14", "F15",
+    "F16", "F17", "F18", "F19", "F20",
+];
+const NUMPAD_DIGIT_LABELS: [&str; 10] = [
+    "Numpad0", "Numpad1", "Numpad2", "Numpad3", "Numpad4", "Numpad5", "Numpad6", "Numpad7",
+    "Numpad8", "Numpad9",
+];
+
+/// Alternative spellings accepted by [`KeyId::from_label`], compared case-insensitively.
+///
+/// Covers characters typed as-is, names used by other tools, names printed by earlier
+/// analyzer versions, and `unknown`, which early key_logger releases wrote for the
+/// apostrophe key.
+pub const ALIASES: &[(&str, KeyId)] = {
+    use KeyId::*;
+    use SymbolKey::*;
+    &[
+        ("key0", Digit(0)),
+        ("key1", Digit(1)),
+        ("key2", Digit(2)),
+        ("key3", Digit(3)),
+        ("key4", Digit(4)),
+        ("key5", Digit(5)),
+        ("key6", Digit(6)),
+        ("key7", Digit(7)),
+        ("key8", Digit(8)),
+        ("key9", Digit(9)),
+        ("`", Symbol(Backtick)),
+        ("backtick", Symbol(Backtick)),
+        ("-", Symbol(Minus)),
+        ("=", Symbol(Equal)),
+        ("[", Symbol(LBracket)),
+        ("lbracket", Symbol(LBracket)),
+        ("]", Symbol(RBracket)),
+        ("rbracket", Symbol(RBracket)),
+        ("\\", Symbol(Backslash)),
+        (";", Symbol(Semicolon)),
+        ("'", Symbol(Quote)),
+        ("quote", Symbol(Quote)),
+        ("unknown", Symbol(Quote)),
+        (",", Symbol(Comma)),
+        (".", Symbol(Period)),
+        ("dot", Symbol(Period)),
+        ("/", Symbol(Slash)),
+        ("102nd", Symbol(IntlBackslash)),
+        ("¥", Symbol(Yen)),
+        ("intlyen", Symbol(Yen)),
+        ("intlro", Symbol(Ro)),
+        ("spacebar", Space),
+        ("return", Enter),
+        ("loption", AltL),
+        ("roption", AltR),
+        ("command", MetaL),
+        ("rcommand", MetaR),
+        ("left", Arrow(ArrowKey::Left)),
+        ("right", Arrow(ArrowKey::Right)),
+        ("up", Arrow(ArrowKey::Up)),
+        ("down", Arrow(ArrowKey::Down)),
+        ("nonconvert", Muhenkan),
+        ("convert", Henkan),
+        ("kana", KatakanaHiragana),
+        ("kanamode", KatakanaHiragana),
+        ("layer1", Modifier(ModifierKey::Layer1)),
+        ("modifier1", Modifier(ModifierKey::Layer1)),
+        ("layer2", Modifier(ModifierKey::Layer2)),
+        ("modifier2", Modifier(ModifierKey::Layer2)),
+        ("layer3", Modifier(ModifierKey::Layer3)),
+        ("modifier3", Modifier(ModifierKey::Layer3)),
+    ]
+};
+
+impl KeyId {
+    /// Number of keys yielded by [`KeyId::all`].
+    pub const COUNT: usize = 26
+        + (MAX_DIGIT as usize + 1)
+        + SymbolKey::ALL.len()
+        + 15
+        + MAX_FUNCTION_KEY as usize
+        + ArrowKey::ALL.len()
+        + ModifierKey::ALL.len()
+        + 5
+        + (MAX_DIGIT as usize + 1)
+        + 7
+        + 3;
+
+    /// Canonical label, as written by key_logger.
+    ///
+    /// # Panics
+    /// If a numbered variant is out of range (e.g. `Digit(10)` or `Function(0)`).
+    pub const fn label(self) -> &'static str {
+        use KeyId::*;
+        match self {
+            Letter(l) => LETTER_LABELS[l as usize],
+            Digit(d) => DIGIT_LABELS[d as usize],
+            Symbol(s) => match s {
+                SymbolKey::Backtick => "Grave",
+                SymbolKey::Minus => "Minus",
+                SymbolKey::Equal => "Equal",
+                SymbolKey::LBracket => "LeftBracket",
+                SymbolKey::RBracket => "RightBracket",
+                SymbolKey::Backslash => "Backslash",
+                SymbolKey::Semicolon => "Semicolon",
+                SymbolKey::Quote => "Apostrophe",
+                SymbolKey::Comma => "Comma",
+                SymbolKey::Period => "Period",
+                SymbolKey::Slash => "Slash",
+                SymbolKey::IntlBackslash => "IntlBackslash",
+                SymbolKey::Yen => "Yen",
+                SymbolKey::Ro => "Ro",
+            },
+            Tab => "Tab",
+            Escape => "Escape",
+            CapsLock => "CapsLock",
+            Delete => "Delete",
+            Backspace => "Backspace",
+            Space => "Space",
+            Enter => "Enter",
+            ShiftL => "LeftShift",
+            ShiftR => "RightShift",
+            CtrlL => "LeftControl",
+            CtrlR => "RightControl",
+            AltL => "LeftAlt",
+            AltR => "RightAlt",
+            MetaL => "LeftMeta",
+            MetaR => "RightMeta",
+            Function(n) => FUNCTION_LABELS[n as usize - 1],
+            Arrow(a) => match a {
+                ArrowKey::Left => "ArrowLeft",
+                ArrowKey::Down => "ArrowDown",
+                ArrowKey::Up => "ArrowUp",
+                ArrowKey::Right => "ArrowRight",
+            },
+            Modifier(m) => match m {
+                ModifierKey::Layer1 => "ModifierLayer1",
+                ModifierKey::Layer2 => "ModifierLayer2",
+                ModifierKey::Layer3 => "ModifierLayer3",
+            },
+            Home => "Home",
+            End => "End",
+            PageUp => "PageUp",
+            PageDown => "PageDown",
+            Insert => "Insert",
+            NumpadDigit(d) => NUMPAD_DIGIT_LABELS[d as usize],
+            NumpadAdd => "NumpadAdd",
+            NumpadSubtract => "NumpadSubtract",
+            NumpadMultiply => "NumpadMultiply",
+            NumpadDivide => "NumpadDivide",
+            NumpadEnter => "NumpadEnter",
+            NumpadEquals => "NumpadEquals",
+            NumpadDecimal => "NumpadDecimal",
+            Muhenkan => "Muhenkan",
+            Henkan => "Henkan",
+            KatakanaHiragana => "KatakanaHiragana",
+        }
+    }
+
+    /// Parses a canonical label or an alias, ignoring case and surrounding whitespace.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let s = label.trim();
+        if s.is_empty() {
+            return None;
+        }
+        Self::all()
+            .find(|k| k.label().eq_ignore_ascii_case(s))
+            .or_else(|| {
+                ALIASES
+                    .iter()
+                    .find(|(alias, _)| alias.eq_ignore_ascii_case(s))
+                    .map(|&(_, k)| k)
+            })
+    }
+
+    /// Every key that has a label, in declaration order.
+    pub fn all() -> impl Iterator<Item = KeyId> {
+        use KeyId::*;
+        let letters = LetterKey::ALL.into_iter().map(Letter);
+        let digits = (0..=MAX_DIGIT).map(Digit);
+        let symbols = SymbolKey::ALL.into_iter().map(Symbol);
+        let singles = [
+            Tab, Escape, CapsLock, Delete, Backspace, Space, Enter, ShiftL, ShiftR, CtrlL, CtrlR,
+            AltL, AltR, MetaL, MetaR,
+        ];
+        let functions = (1..=MAX_FUNCTION_KEY).map(Function);
+        let arrows = ArrowKey::ALL.into_iter().map(Arrow);
+        let modifiers = ModifierKey::ALL.into_iter().map(Modifier);
+        let navigation = [Home, End, PageUp, PageDown, Insert];
+        let numpad_digits = (0..=MAX_DIGIT).map(NumpadDigit);
+        let numpad = [
+            NumpadAdd,
+            NumpadSubtract,
+            NumpadMultiply,
+            NumpadDivide,
+            NumpadEnter,
+            NumpadEquals,
+            NumpadDecimal,
+        ];
+        let input_method = [Muhenkan, Henkan, KatakanaHiragana];
+        letters
+            .chain(digits)
+            .chain(symbols)
+            .chain(singles)
+            .chain(functions)
+            .chain(arrows)
+            .chain(modifiers)
+            .chain(navigation)
+            .chain(numpad_digits)
+            .chain(numpad)
+            .chain(input_method)
+    }
+
+    /// Position of the key in [`KeyId::all`], below [`KeyId::COUNT`].
+    ///
+    /// Lets per-key data live in a fixed-size array instead of a map.
+    ///
+    /// # Panics
+    /// If a numbered variant is out of range (e.g. `Digit(10)` or `Function(0)`).
+    pub const fn index(self) -> usize {
+        use KeyId::*;
+        const DIGITS: usize = 26;
+        const SYMBOLS: usize = DIGITS + MAX_DIGIT as usize + 1;
+        const SINGLES: usize = SYMBOLS + SymbolKey::ALL.len();
+        const FUNCTIONS: usize = SINGLES + 15;
+        const ARROWS: usize = FUNCTIONS + MAX_FUNCTION_KEY as usize;
+        const MODIFIERS: usize = ARROWS + ArrowKey::ALL.len();
+        const NAVIGATION: usize = MODIFIERS + ModifierKey::ALL.len();
+        const NUMPAD_DIGITS: usize = NAVIGATION + 5;
+        const NUMPAD: usize = NUMPAD_DIGITS + MAX_DIGIT as usize + 1;
+        const INPUT_METHOD: usize = NUMPAD + 7;
+        match self {
+            Letter(l) => l as usize,
+            Digit(d) => {
+                assert!(d <= MAX_DIGIT);
+                DIGITS + d as usize
+            }
+            Symbol(s) => SYMBOLS + s as usize,
+            Tab => SINGLES,
+            Escape => SINGLES + 1,
+            CapsLock => SINGLES + 2,
+            Delete => SINGLES + 3,
+            Backspace => SINGLES + 4,
+            Space => SINGLES + 5,
+            Enter => SINGLES + 6,
+            ShiftL => SINGLES + 7,
+            ShiftR => SINGLES + 8,
+            CtrlL => SINGLES + 9,
+            CtrlR => SINGLES + 10,
+            AltL => SINGLES + 11,
+            AltR => SINGLES + 12,
+            MetaL => SINGLES + 13,
+            MetaR => SINGLES + 14,
+            Function(n) => {
+                assert!(n >= 1 && n <= MAX_FUNCTION_KEY);
+                FUNCTIONS + n as usize - 1
+            }
+            Arrow(a) => ARROWS + a as usize,
+            Modifier(m) => MODIFIERS + m as usize,
+            Home => NAVIGATION,
+            End => NAVIGATION + 1,
+            PageUp => NAVIGATION + 2,
+            PageDown => NAVIGATION + 3,
+            Insert => NAVIGATION + 4,
+            NumpadDigit(d) => {
+                assert!(d <= MAX_DIGIT);
+                NUMPAD_DIGITS + d as usize
+            }
+            NumpadAdd => NUMPAD,
+            NumpadSubtract => NUMPAD + 1,
+            NumpadMultiply => NUMPAD + 2,
+            NumpadDivide => NUMPAD + 3,
+            NumpadEnter => NUMPAD + 4,
+            NumpadEquals => NUMPAD + 5,
+            NumpadDecimal => NUMPAD + 6,
+            Muhenkan => INPUT_METHOD,
+            Henkan => INPUT_METHOD + 1,
+            KatakanaHiragana => INPUT_METHOD + 2,
+        }
+    }
+
+    /// Left/right Shift, Control, Alt and Meta.
+    pub const fn is_modifier(self) -> bool {
+        use KeyId::*;
+        matches!(
+            self,
+            ShiftL | ShiftR | CtrlL | CtrlR | AltL | AltR | MetaL | MetaR
+        )
+    }
+}
+
+/// Physical keyboard layout the data was typed on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PhysicalLayout {
+    #[default]
+    Us,
+    Iso,
+    Jis,
+}
+
+impl PhysicalLayout {
+    pub const ALL: [PhysicalLayout; 3] =
+        [PhysicalLayout::Us, PhysicalLayout::Iso, PhysicalLayout::Jis];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            PhysicalLayout::Us => "US",
+            PhysicalLayout::Iso => "ISO",
+            PhysicalLayout::Jis => "JIS",
+        }
+    }
+
+    /// Parses `US`, `ISO` or `JIS`, ignoring case.
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.label().eq_ignore_ascii_case(label.trim()))
+    }
+
+    /// Keys this layout has on top of the US (ANSI) ones.
+    ///
+    /// Keys at a US position keep the US label whatever is printed on them, e.g. the JIS
+    /// 半角/全角 key is `Grave` and `@` is `LeftBracket`.
+    pub const fn extra_keys(self) -> &'static [KeyId] {
+        use KeyId::*;
+        match self {
+            PhysicalLayout::Us => &[],
+            PhysicalLayout::Iso => &[Symbol(SymbolKey::IntlBackslash)],
+            PhysicalLayout::Jis => &[
+                Symbol(SymbolKey::Yen),
+                Symbol(SymbolKey::Ro),
+                Muhenkan,
+                Henkan,
+                KatakanaHiragana,
+            ],
+        }
+    }
+
+    /// Whether a board of this layout has `key`.
+    pub fn has_key(self, key: KeyId) -> bool {
+        self.extra_keys().contains(&key)
+            || Self::ALL
+                .iter()
+                .all(|layout| !layout.extra_keys().contains(&key))
+    }
+}
+
+impl fmt::Display for PhysicalLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_label_round_trip() {
+        for key in KeyId::all() {
+            assert_eq!(KeyId::from_label(key.label()), Some(key), "{key:?}");
+            assert_eq!(
+                KeyId::from_label(&key.label().to_ascii_lowercase()),
+                Some(key)
+            );
+        }
+    }
+
+    #[test]
+    fn test_labels_are_unique() {
+        let labels: HashSet<String> = KeyId::all()
+            .map(|k| k.label().to_ascii_lowercase())
+            .collect();
+        assert_eq!(labels.len(), KeyId::all().count());
+    }
+
+    #[test]
+    fn test_all_covers_every_variant() {
+        // Adding a variant without listing it in `all` fails to compile here
+        fn variant_index(key: KeyId) -> usize {
+            use KeyId::*;
+            match key {
+                Letter(_) => 0,
+                Digit(_) => 1,
+                Symbol(_) => 2,
+                Tab | Escape | CapsLock | Delete | Backspace | Space | Enter => 3,
+                ShiftL | ShiftR | CtrlL | CtrlR | AltL | AltR | MetaL | MetaR => 4,
+                Function(_) => 5,
+                Arrow(_) => 6,
+                Modifier(_) => 7,
+                Home | End | PageUp | PageDown | Insert => 8,
+                NumpadDigit(_) => 9,
+                NumpadAdd | NumpadSubtract | NumpadMultiply | NumpadDivide | NumpadEnter
+                | NumpadEquals | NumpadDecimal => 10,
+                Muhenkan | Henkan | KatakanaHiragana => 11,
+            }
+        }
+        let groups: HashSet<usize> = KeyId::all().map(variant_index).collect();
+        assert_eq!(groups.len(), 12);
+        assert_eq!(
+            KeyId::all().count(),
+            26 + 10 + 14 + 15 + 20 + 4 + 3 + 5 + 10 + 7 + 3
+        );
+        assert_eq!(KeyId::all().count(), KeyId::COUNT);
+    }
+
+    #[test]
+    fn test_index_matches_all() {
+        for (i, key) in KeyId::all().enumerate() {
+            assert_eq!(key.index(), i, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn test_aliases() {
+        for &(alias, key) in ALIASES {
+            assert_eq!(KeyId::from_label(alias), Some(key), "alias '{alias}'");
+            // An alias must never shadow another key's canonical label
+            assert!(
+                KeyId::all()
+                    .filter(|&k| k != key)
+                    .all(|k| !k.label().eq_ignore_ascii_case(alias)),
+                "alias '{alias}' collides with a canonical label"
+            );
+        }
+        assert_eq!(KeyId::from_label(" Return "), Some(KeyId::Enter));
+        assert_eq!(KeyId::from_label("KEY7"), Some(KeyId::Digit(7)));
+        assert_eq!(
+            KeyId::from_label("unknown"),
+            Some(KeyId::Symbol(SymbolKey::Quote))
+        );
+    }
+
+    #[test]
+    fn test_unknown_labels() {
+        assert_eq!(KeyId::from_label(""), None);
+        assert_eq!(KeyId::from_label("F21"), None);
+        assert_eq!(KeyId::from_label("Numpad10"), None);
+        assert_eq!(KeyId::from_label("Hyper"), None);
+    }
+
+    #[test]
+    fn test_layout_labels() {
+        for layout in PhysicalLayout::ALL {
+            assert_eq!(PhysicalLayout::from_label(layout.label()), Some(layout));
+        }
+        assert_eq!(PhysicalLayout::from_label("jis"), Some(PhysicalLayout::Jis));
+        assert_eq!(PhysicalLayout::from_label("Dvorak"), None);
+    }
+
+    #[test]
+    fn test_layout_extra_keys() {
+        let yen = KeyId::Symbol(SymbolKey::Yen);
+        let iso = KeyId::Symbol(SymbolKey::IntlBackslash);
+        assert!(PhysicalLayout::Us.extra_keys().is_empty());
+        assert!(PhysicalLayout::Jis.has_key(yen));
+        assert!(!PhysicalLayout::Us.has_key(yen));
+        assert!(!PhysicalLayout::Jis.has_key(iso));
+        assert!(PhysicalLayout::Iso.has_key(iso));
+        for layout in PhysicalLayout::ALL {
+            assert!(layout.has_key(KeyId::Symbol(SymbolKey::Backslash)));
+        }
+        assert_eq!(KeyId::from_label("Convert"), Some(KeyId::Henkan));
+        assert_eq!(KeyId::from_label("¥"), Some(yen));
+    }
+
+    #[test]
+    fn test_display_matches_label() {
+        assert_eq!(KeyId::Symbol(SymbolKey::Backtick).to_string(), "Grave");
+        assert_eq!(KeyId::Function(12).to_string(), "F12");
+        assert_eq!(KeyId::NumpadDigit(3).to_string(), "Numpad3");
+        assert!(KeyId::ShiftR.is_modifier());
+        assert!(!KeyId::Space.is_modifier());
+    }
+}
@@ -0,0 +1,126 @@
+//! Compares the lock-free key counters with the `Mutex<HashMap>` store they replaced.
+//!
+//! Run with `cargo bench -p key_logger --bench stats`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use key_id::KeyId;
+use key_logger::stats;
+use std::{
+    collections::HashMap,
+    hint::black_box,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+/// The previous store: one lock around a map from label to count
+mod mutex_map {
+    use super::*;
+
+    pub type KeyStatistics = Arc<Mutex<HashMap<&'static str, u64>>>;
+
+    pub fn create_statistics() -> KeyStatistics {
+        Arc::new(Mutex::new(HashMap::with_capacity(64)))
+    }
+
+    pub fn get_statistics_snapshot(stats: &KeyStatistics) -> HashMap<&'static str, u64> {
+        stats.lock().unwrap().clone()
+    }
+
+    pub fn add_many(stats: &KeyStatistics, keys: impl IntoIterator<Item = &'static str>) {
+        let mut guard = stats.lock().unwrap();
+        for key in keys {
+            *guard.entry(key).or_insert(0) += 1;
+        }
+    }
+}
+
+/// A typical capture-loop batch: a few presses of common keys
+const BATCH: [&str; 8] = ["T", "H", "E", "Space", "LeftShift", "A", "Period", "Enter"];
+
+/// [`BATCH`] as the capture loop hands it to the atomic counters
+fn batch_ids() -> [KeyId; 8] {
+    BATCH.map(|label| KeyId::from_label(label).unwrap())
+}
+
+/// Runs `read` in a loop on another thread while `body` runs, like the status socket,
+/// autosave and TUI do while capturing.
+fn with_reader(read: impl Fn() + Send + 'static, body: impl FnOnce()) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                read();
+            }
+        })
+    };
+    body();
+    stop.store(true, Ordering::Relaxed);
+    reader.join().unwrap();
+}
+
+fn bench_record(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record");
+    group.bench_function("mutex_map", |b| {
+        let stats = mutex_map::create_statistics();
+        b.iter(|| mutex_map::add_many(&stats, black_box(BATCH)));
+    });
+    group.bench_function("atomic", |b| {
+        let stats = stats::create_statistics();
+        let batch = batch_ids();
+        b.iter(|| stats::add_many(&stats, black_box(batch)));
+    });
+    group.finish();
+}
+
+fn bench_record_while_reading(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record_while_reading");
+    group.bench_function("mutex_map", |b| {
+        let stats = mutex_map::create_statistics();
+        let reader = Arc::clone(&stats);
+        with_reader(
+            move || {
+                black_box(mutex_map::get_statistics_snapshot(&reader));
+            },
+            || b.iter(|| mutex_map::add_many(&stats, black_box(BATCH))),
+        );
+    });
+    group.bench_function("atomic", |b| {
+        let stats = stats::create_statistics();
+        let reader = Arc::clone(&stats);
+        let batch = batch_ids();
+        with_reader(
+            move || {
+                black_box(stats::get_statistics_snapshot(&reader).unwrap());
+            },
+            || b.iter(|| stats::add_many(&stats, black_box(batch))),
+        );
+    });
+    group.finish();
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    group.bench_function("mutex_map", |b| {
+        let stats = mutex_map::create_statistics();
+        mutex_map::add_many(&stats, BATCH);
+        b.iter(|| mutex_map::get_statistics_snapshot(&stats));
+    });
+    group.bench_function("atomic", |b| {
+        let stats = stats::create_statistics();
+        stats::add_many(&stats, batch_ids());
+        b.iter(|| stats::get_statistics_snapshot(&stats).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_record,
+    bench_record_while_reading,
+    bench_snapshot
+);
+criterion_main!(benches);
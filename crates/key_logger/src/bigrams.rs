@@ -0,0 +1,87 @@
+use crate::error::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// Most transitions fall between letters, space and a handful of punctuation keys
+const INITIAL_BIGRAM_CAPACITY: usize = 512;
+
+/// (from, to) -> number of times `to` was pressed directly after `from`
+pub type BigramMap = HashMap<(&'static str, &'static str), u64>;
+
+pub type BigramStatistics = Arc<Mutex<BigramMap>>;
+
+pub fn create_bigram_statistics() -> BigramStatistics {
+    Arc::new(Mutex::new(HashMap::with_capacity(INITIAL_BIGRAM_CAPACITY)))
+}
+
+pub fn get_bigram_snapshot(bigrams: &BigramStatistics) -> Result<BigramMap> {
+    Ok(bigrams.lock().unwrap_or_else(|p| p.into_inner()).clone())
+}
+
+/// Returns the transition counts and resets them under a single lock.
+pub fn take_bigram_snapshot(bigrams: &BigramStatistics) -> Result<BigramMap> {
+    Ok(std::mem::take(
+        &mut *bigrams.lock().unwrap_or_else(|p| p.into_inner()),
+    ))
+}
+
+/// Adds previously taken counts back (e.g. after a failed export).
+pub fn merge_bigrams(bigrams: &BigramStatistics, counts: &BigramMap) -> Result<()> {
+    let mut guard = bigrams.lock().unwrap_or_else(|p| p.into_inner());
+    for (&pair, &count) in counts {
+        *guard.entry(pair).or_insert(0) += count;
+    }
+    Ok(())
+}
+
+pub fn add_many<I>(bigrams: &BigramStatistics, pairs: I) -> Result<()>
+where
+    I: IntoIterator<Item = (&'static str, &'static str)>,
+{
+    let mut guard = bigrams.lock().unwrap_or_else(|p| p.into_inner());
+    for pair in pairs {
+        *guard.entry(pair).or_insert(0) += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_add_many_pairs() {
+        let bigrams = create_bigram_statistics();
+
+        add_many(&bigrams, [("T", "H"), ("H", "E"), ("T", "H")]).unwrap();
+
+        let snapshot = get_bigram_snapshot(&bigrams).unwrap();
+        assert_eq!(snapshot.get(&("T", "H")), Some(&2));
+        assert_eq!(snapshot.get(&("H", "E")), Some(&1));
+        assert_eq!(snapshot.get(&("H", "T")), None);
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        let bigrams = create_bigram_statistics();
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let bigrams = Arc::clone(&bigrams);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        add_many(&bigrams, [("A", "B")]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let snapshot = get_bigram_snapshot(&bigrams).unwrap();
+        assert_eq!(snapshot.get(&("A", "B")), Some(&2000));
+    }
+}
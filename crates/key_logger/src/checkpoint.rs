@@ -0,0 +1,352 @@
+//! Crash-safe snapshots of the key counts and auto-repeats.
+//!
+//! The final CSV is only written on a clean shutdown, so a panic (the release profile
+//! aborts), SIGKILL or power loss would otherwise lose the whole session. The autosave
+//! thread periodically replaces a checkpoint file atomically (write to a temp file, then
+//! rename), and the next start merges it back before counting resumes. With encryption
+//! configured the checkpoint is sealed like the exported CSVs.
+//!
+//! Only the key counts and auto-repeats are checkpointed. Bigrams, chords, timing,
+//! sessions and per-device counts live in memory until an export, so a crash still
+//! loses them; recovered key counts are therefore not matched by these statistics.
+
+use crate::{
+    error::{KeyLoggerError, Result},
+    export, platform_common, stats,
+};
+use csv::{ReaderBuilder, Trim, WriterBuilder};
+use keylog_crypt::Secret;
+use log::{debug, error, info, warn};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Not a `.csv` file on purpose, so the analyzer never mistakes it for a finished session
+const CHECKPOINT_FILE_NAME: &str = ".keylog.checkpoint";
+const CHECKPOINT_TMP_FILE_NAME: &str = ".keylog.checkpoint.tmp";
+
+// How often the autosave thread checks for shutdown while waiting
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+// Serializes checkpoint writes with rotation, which must not race a stale snapshot
+static CHECKPOINT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held while a checkpoint is written or while counts move out of the live statistics.
+pub fn lock() -> MutexGuard<'static, ()> {
+    CHECKPOINT_LOCK.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+pub fn checkpoint_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(CHECKPOINT_FILE_NAME)
+}
+
+/// Counts read back from a checkpoint.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub counts: HashMap<&'static str, u64>,
+    pub repeats: HashMap<&'static str, u64>,
+}
+
+/// Atomically replaces the checkpoint in `output_dir` with `snapshot` and `repeats`.
+pub fn write_checkpoint(
+    snapshot: &HashMap<&'static str, u64>,
+    repeats: &HashMap<&'static str, u64>,
+    output_dir: &Path,
+    encryption: Option<&Secret>,
+) -> Result<PathBuf> {
+    fs::create_dir_all(output_dir).map_err(|e| KeyLoggerError::CreateDir {
+        path: output_dir.to_path_buf(),
+        source: e,
+    })?;
+
+    let tmp_path = output_dir.join(CHECKPOINT_TMP_FILE_NAME);
+    let final_path = checkpoint_path(output_dir);
+
+    let mut wtr = WriterBuilder::new().from_writer(Vec::new());
+    export::write_key_counts(&mut wtr, snapshot, repeats)?;
+    let data = wtr
+        .into_inner()
+        .map_err(|e| KeyLoggerError::Io(e.into_error()))?;
+    let data = match encryption {
+        Some(secret) => keylog_crypt::encrypt(secret, &data)?,
+        None => data,
+    };
+
+    let mut file = File::create(&tmp_path).map_err(|e| KeyLoggerError::CreateFile {
+        path: tmp_path.clone(),
+        source: e,
+    })?;
+    // Make sure the data is on disk before the rename makes it visible
+    file.write_all(&data)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| KeyLoggerError::WriteFile {
+            path: tmp_path.clone(),
+            source: e,
+        })?;
+
+    fs::rename(&tmp_path, &final_path).map_err(|e| KeyLoggerError::WriteFile {
+        path: final_path.clone(),
+        source: e,
+    })?;
+    Ok(final_path)
+}
+
+/// Reads the checkpoint left in `output_dir` by a previous run, if any.
+///
+/// Checkpoints written before repeats were counted have no `Repeat` column and load
+/// with no repeats. A plaintext checkpoint also loads when `encryption` is set, so turning
+/// encryption on does not lose the counts of a crashed run.
+pub fn load_checkpoint(
+    output_dir: &Path,
+    encryption: Option<&Secret>,
+) -> Result<Option<Checkpoint>> {
+    let path = checkpoint_path(output_dir);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let data = match encryption {
+        _ if !keylog_crypt::is_encrypted(&data) => data,
+        Some(secret) => keylog_crypt::decrypt(secret, &data)?,
+        None => {
+            return Err(KeyLoggerError::InvalidConfiguration(format!(
+                "Checkpoint {} is encrypted; configure its key file or passphrase",
+                path.display()
+            )));
+        }
+    };
+
+    let mut rdr = ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_reader(data.as_slice());
+    let mut checkpoint = Checkpoint::default();
+    for result in rdr.records() {
+        let record = result?;
+        let (Some(label), Some(count)) = (record.get(0), record.get(1)) else {
+            continue;
+        };
+        let Some(key) = stats::intern_key_label(label) else {
+            warn!("Ignoring unknown key '{label}' in checkpoint");
+            continue;
+        };
+        match count.parse::<u64>() {
+            Ok(0) => {}
+            Ok(count) => *checkpoint.counts.entry(key).or_insert(0) += count,
+            Err(_) => warn!("Ignoring invalid count '{count}' for '{label}' in checkpoint"),
+        }
+        match record.get(2).map(str::parse::<u64>) {
+            None | Some(Ok(0)) => {}
+            Some(Ok(repeats)) => *checkpoint.repeats.entry(key).or_insert(0) += repeats,
+            Some(Err(_)) => warn!("Ignoring invalid repeat count for '{label}' in checkpoint"),
+        }
+    }
+    Ok(Some(checkpoint))
+}
+
+/// Deletes the checkpoint once its counts have been saved elsewhere.
+pub fn remove_checkpoint(output_dir: &Path) -> Result<()> {
+    match fs::remove_file(checkpoint_path(output_dir)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Merges a leftover checkpoint into `stats` and `repeats`, returning the number of
+/// recovered key presses.
+///
+/// The other statistics of the crashed run are not in the checkpoint (see the module
+/// documentation) and stay lost.
+pub fn restore_checkpoint(
+    stats: &stats::KeyStatistics,
+    repeats: &stats::KeyStatistics,
+    output_dir: &Path,
+    encryption: Option<&Secret>,
+) -> Result<u64> {
+    let Some(checkpoint) = load_checkpoint(output_dir, encryption)? else {
+        return Ok(0);
+    };
+    stats::merge_counts(stats, &checkpoint.counts)?;
+    stats::merge_counts(repeats, &checkpoint.repeats)?;
+    Ok(checkpoint.counts.values().sum())
+}
+
+/// Starts a background thread that checkpoints `stats` and `repeats` every `interval`
+/// until exit.
+pub fn spawn_autosave(
+    stats: stats::KeyStatistics,
+    repeats: stats::KeyStatistics,
+    output_dir: PathBuf,
+    encryption: Option<Secret>,
+    interval: Duration,
+) -> Result<thread::JoinHandle<()>> {
+    info!(
+        "Autosaving every {}s to {}",
+        interval.as_secs(),
+        checkpoint_path(&output_dir).display()
+    );
+
+    let handle = thread::Builder::new()
+        .name("autosave".into())
+        .spawn(move || {
+            let mut last_save = Instant::now();
+            while !platform_common::should_exit() {
+                thread::sleep(EXIT_CHECK_INTERVAL.min(interval));
+                if last_save.elapsed() < interval {
+                    continue;
+                }
+                last_save = Instant::now();
+
+                let _guard = lock();
+                let snapshots = stats::get_statistics_snapshot(&stats)
+                    .and_then(|s| Ok((s, stats::get_statistics_snapshot(&repeats)?)));
+                let (snapshot, repeat_snapshot) = match snapshots {
+                    Ok(snapshots) => snapshots,
+                    Err(e) => {
+                        error!("Failed to snapshot statistics: {e}");
+                        continue;
+                    }
+                };
+                if snapshot.is_empty() && repeat_snapshot.is_empty() {
+                    continue;
+                }
+                match write_checkpoint(
+                    &snapshot,
+                    &repeat_snapshot,
+                    &output_dir,
+                    encryption.as_ref(),
+                ) {
+                    Ok(path) => debug!("Checkpoint written to {}", path.display()),
+                    Err(e) => error!("Failed to write checkpoint: {e}"),
+                }
+            }
+        })?;
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::key;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut snapshot = HashMap::new();
+        snapshot.insert("A", 5);
+        snapshot.insert("Space", 12);
+        let repeats = HashMap::from([("Space", 40), ("Backspace", 7)]);
+
+        let path = write_checkpoint(&snapshot, &repeats, temp_dir.path(), None).unwrap();
+        assert_eq!(path, checkpoint_path(temp_dir.path()));
+        assert!(!temp_dir.path().join(CHECKPOINT_TMP_FILE_NAME).exists());
+
+        let loaded = load_checkpoint(temp_dir.path(), None).unwrap().unwrap();
+        assert_eq!(loaded.counts, snapshot);
+        assert_eq!(loaded.repeats, repeats);
+    }
+
+    #[test]
+    fn test_checkpoint_replaces_previous() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut snapshot = HashMap::new();
+        snapshot.insert("A", 1);
+        write_checkpoint(&snapshot, &HashMap::new(), temp_dir.path(), None).unwrap();
+
+        snapshot.insert("A", 2);
+        write_checkpoint(&snapshot, &HashMap::new(), temp_dir.path(), None).unwrap();
+
+        let loaded = load_checkpoint(temp_dir.path(), None).unwrap().unwrap();
+        assert_eq!(loaded.counts.get("A"), Some(&2));
+    }
+
+    #[test]
+    fn test_missing_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(load_checkpoint(temp_dir.path(), None).unwrap(), None);
+        remove_checkpoint(temp_dir.path()).unwrap();
+    }
+
+    #[test]
+    fn test_restore_merges_into_statistics() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut snapshot = HashMap::new();
+        snapshot.insert("A", 5);
+        snapshot.insert("B", 2);
+        write_checkpoint(&snapshot, &HashMap::from([("B", 9)]), temp_dir.path(), None).unwrap();
+
+        let stats = stats::create_statistics();
+        let repeats = stats::create_statistics();
+        stats::add_many(&stats, [key("A")]);
+        stats::add_many(&repeats, [key("B")]);
+        let recovered = restore_checkpoint(&stats, &repeats, temp_dir.path(), None).unwrap();
+        assert_eq!(recovered, 7);
+        let merged_repeats = stats::get_statistics_snapshot(&repeats).unwrap();
+        assert_eq!(merged_repeats.get("B"), Some(&10));
+
+        let merged = stats::get_statistics_snapshot(&stats).unwrap();
+        assert_eq!(merged.get("A"), Some(&6));
+        assert_eq!(merged.get("B"), Some(&2));
+
+        remove_checkpoint(temp_dir.path()).unwrap();
+        assert!(!checkpoint_path(temp_dir.path()).exists());
+    }
+
+    #[test]
+    fn test_load_skips_unknown_labels() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            checkpoint_path(temp_dir.path()),
+            "Key,Count\nA,3\nNotAKey,9\nB,oops\n",
+        )
+        .unwrap();
+
+        // Written before repeats were counted
+        let loaded = load_checkpoint(temp_dir.path(), None).unwrap().unwrap();
+        assert_eq!(loaded.counts.len(), 1);
+        assert_eq!(loaded.counts.get("A"), Some(&3));
+        assert!(loaded.repeats.is_empty());
+    }
+
+    #[test]
+    fn test_encrypted_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let secret = Secret::Key([3; 32]);
+        let snapshot = HashMap::from([("A", 5)]);
+        let path =
+            write_checkpoint(&snapshot, &HashMap::new(), temp_dir.path(), Some(&secret)).unwrap();
+        assert!(keylog_crypt::is_encrypted(&fs::read(path).unwrap()));
+
+        let loaded = load_checkpoint(temp_dir.path(), Some(&secret))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.counts, snapshot);
+        assert!(matches!(
+            load_checkpoint(temp_dir.path(), None),
+            Err(KeyLoggerError::InvalidConfiguration(_))
+        ));
+        assert!(load_checkpoint(temp_dir.path(), Some(&Secret::Key([4; 32]))).is_err());
+
+        // A plaintext checkpoint from before encryption was enabled still loads
+        write_checkpoint(&snapshot, &HashMap::new(), temp_dir.path(), None).unwrap();
+        let loaded = load_checkpoint(temp_dir.path(), Some(&secret))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.counts, snapshot);
+    }
+
+    #[test]
+    fn test_checkpoint_not_a_csv() {
+        // The analyzer merges every *.csv in the directory; a checkpoint must not be picked up
+        let path = checkpoint_path(Path::new("csv"));
+        assert_ne!(path.extension().and_then(|e| e.to_str()), Some("csv"));
+    }
+}
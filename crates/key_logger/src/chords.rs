@@ -0,0 +1,159 @@
+//! Opt-in modifier chord statistics.
+//!
+//! Counts how often each non-modifier key is pressed while a given set of modifiers is held
+//! (e.g. `LeftControl` + `C`). Only the (modifier set, key) pair is counted; the order of
+//! presses and anything typed around the chord are not recorded.
+
+use crate::error::Result;
+use key_id::KeyId;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Modifier keys tracked for chords, in the order they appear in exported labels.
+pub const MODIFIERS: [&str; 8] = [
+    KeyId::CtrlL.label(),
+    KeyId::CtrlR.label(),
+    KeyId::ShiftL.label(),
+    KeyId::ShiftR.label(),
+    KeyId::AltL.label(),
+    KeyId::AltR.label(),
+    KeyId::MetaL.label(),
+    KeyId::MetaR.label(),
+];
+
+/// Set of currently held modifiers, one bit per entry of [`MODIFIERS`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModifierSet(u8);
+
+impl ModifierSet {
+    fn bit(key: &str) -> Option<u8> {
+        MODIFIERS
+            .iter()
+            .position(|&m| m == key)
+            .map(|index| 1 << index)
+    }
+
+    /// Marks `key` as held; returns false if it is not a modifier.
+    pub fn insert(&mut self, key: &str) -> bool {
+        match Self::bit(key) {
+            Some(bit) => {
+                self.0 |= bit;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if let Some(bit) = Self::bit(key) {
+            self.0 &= !bit;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> + '_ {
+        MODIFIERS
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| self.0 & (1 << index) != 0)
+            .map(|(_, &m)| m)
+    }
+
+    /// Modifier names joined with `+`, e.g. `LeftControl+LeftShift`.
+    pub fn label(&self) -> String {
+        self.iter().collect::<Vec<_>>().join("+")
+    }
+}
+
+/// (held modifiers, key) -> number of presses
+pub type ChordMap = HashMap<(ModifierSet, &'static str), u64>;
+
+pub type ChordStatistics = Arc<Mutex<ChordMap>>;
+
+pub fn create_chord_statistics() -> ChordStatistics {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+pub fn get_chord_snapshot(chords: &ChordStatistics) -> Result<ChordMap> {
+    Ok(chords.lock().unwrap_or_else(|p| p.into_inner()).clone())
+}
+
+/// Returns the chord counts and resets them under a single lock.
+pub fn take_chord_snapshot(chords: &ChordStatistics) -> Result<ChordMap> {
+    Ok(std::mem::take(
+        &mut *chords.lock().unwrap_or_else(|p| p.into_inner()),
+    ))
+}
+
+/// Adds previously taken counts back (e.g. after a failed export).
+pub fn merge_chords(chords: &ChordStatistics, counts: &ChordMap) -> Result<()> {
+    let mut guard = chords.lock().unwrap_or_else(|p| p.into_inner());
+    for (&chord, &count) in counts {
+        *guard.entry(chord).or_insert(0) += count;
+    }
+    Ok(())
+}
+
+pub fn add_many<I>(chords: &ChordStatistics, presses: I) -> Result<()>
+where
+    I: IntoIterator<Item = (ModifierSet, &'static str)>,
+{
+    let mut guard = chords.lock().unwrap_or_else(|p| p.into_inner());
+    for chord in presses {
+        *guard.entry(chord).or_insert(0) += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modifier_set() {
+        let mut held = ModifierSet::default();
+        assert!(held.is_empty());
+
+        assert!(held.insert("LeftShift"));
+        assert!(held.insert("LeftControl"));
+        assert!(!held.insert("A"));
+        assert_eq!(held.label(), "LeftControl+LeftShift");
+
+        held.remove("LeftControl");
+        held.remove("A");
+        assert_eq!(held.label(), "LeftShift");
+
+        held.remove("LeftShift");
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn test_label_order_is_stable() {
+        let mut a = ModifierSet::default();
+        a.insert("RightMeta");
+        a.insert("LeftAlt");
+        let mut b = ModifierSet::default();
+        b.insert("LeftAlt");
+        b.insert("RightMeta");
+        assert_eq!(a, b);
+        assert_eq!(a.label(), "LeftAlt+RightMeta");
+    }
+
+    #[test]
+    fn test_add_many() {
+        let chords = create_chord_statistics();
+        let mut ctrl = ModifierSet::default();
+        ctrl.insert("LeftControl");
+
+        add_many(&chords, [(ctrl, "C"), (ctrl, "V"), (ctrl, "C")]).unwrap();
+
+        let snapshot = get_chord_snapshot(&chords).unwrap();
+        assert_eq!(snapshot.get(&(ctrl, "C")), Some(&2));
+        assert_eq!(snapshot.get(&(ctrl, "V")), Some(&1));
+    }
+}
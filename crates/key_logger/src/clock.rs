@@ -0,0 +1,20 @@
+//! Wall clock for everything that dates the output.
+//!
+//! A replay can be dated with `--replay-start`, which shifts this clock for the whole run,
+//! so file names, rotation periods, sessions and metadata all agree with each other and
+//! re-running the same script produces the same names.
+
+use chrono::{DateTime, Local, TimeDelta};
+use std::sync::OnceLock;
+
+static OFFSET: OnceLock<TimeDelta> = OnceLock::new();
+
+/// Makes [`now`] return `start` at this instant and advance normally from there.
+/// Only the first call has an effect.
+pub fn start_at(start: DateTime<Local>) {
+    let _ = OFFSET.set(start - Local::now());
+}
+
+pub fn now() -> DateTime<Local> {
+    Local::now() + OFFSET.get().copied().unwrap_or_default()
+}
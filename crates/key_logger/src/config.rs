@@ -1,25 +1,474 @@
-use crate::error::{KeyLoggerError, Result};
-use std::{env, path::PathBuf};
+use crate::{
+    daemon::DEFAULT_PID_FILE_NAME,
+    error::{KeyLoggerError, Result},
+    export::OutputFormat,
+    platform_common::POLLING_INTERVAL,
+    privacy::Privacy,
+    recorder::KeyFilter,
+    rotation::Rotation,
+};
+use chrono::{
+    DateTime, FixedOffset, Local,
+    format::{Item, StrftimeItems},
+};
+use clap::{Parser, builder::BoolishValueParser};
+use key_id::{KeyId, PhysicalLayout};
+use keylog_crypt::Secret;
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+const ENV_KEY_CONFIG: &str = "KEY_LOGGER_CONFIG";
 const ENV_KEY_OUTPUT_DIR: &str = "KEY_LOGGER_OUTPUT_DIR";
+const ENV_KEY_FILENAME_PATTERN: &str = "KEY_LOGGER_FILENAME_PATTERN";
+const ENV_KEY_FORMAT: &str = "KEY_LOGGER_FORMAT";
+const ENV_KEY_POLLING_INTERVAL_MS: &str = "KEY_LOGGER_POLLING_INTERVAL_MS";
+const ENV_KEY_AUTOSAVE_SECS: &str = "KEY_LOGGER_AUTOSAVE_SECS";
+const ENV_KEY_SESSION_GAP_SECS: &str = "KEY_LOGGER_SESSION_GAP_SECS";
+const ENV_KEY_UNIGRAMS: &str = "KEY_LOGGER_UNIGRAMS";
+const ENV_KEY_BIGRAMS: &str = "KEY_LOGGER_BIGRAMS";
+const ENV_KEY_TIMING: &str = "KEY_LOGGER_TIMING";
+const ENV_KEY_CHORDS: &str = "KEY_LOGGER_CHORDS";
+const ENV_KEY_PER_DEVICE: &str = "KEY_LOGGER_PER_DEVICE";
+const ENV_KEY_INCLUDE: &str = "KEY_LOGGER_INCLUDE";
+const ENV_KEY_EXCLUDE: &str = "KEY_LOGGER_EXCLUDE";
+const ENV_KEY_LAYOUT: &str = "KEY_LOGGER_LAYOUT";
+const ENV_KEY_METADATA: &str = "KEY_LOGGER_METADATA";
+const ENV_KEY_ROTATE: &str = "KEY_LOGGER_ROTATE";
+const ENV_KEY_STATUS_SOCKET: &str = "KEY_LOGGER_STATUS_SOCKET";
+const ENV_KEY_TUI: &str = "KEY_LOGGER_TUI";
+const ENV_KEY_DAEMON: &str = "KEY_LOGGER_DAEMON";
+const ENV_KEY_PID_FILE: &str = "KEY_LOGGER_PID_FILE";
+const ENV_KEY_LOG_FILE: &str = "KEY_LOGGER_LOG_FILE";
+const ENV_KEY_DP_EPSILON: &str = "KEY_LOGGER_DP_EPSILON";
+const ENV_KEY_DP_MIN_COUNT: &str = "KEY_LOGGER_DP_MIN_COUNT";
+const ENV_KEY_ENCRYPTION_KEY_FILE: &str = "KEY_LOGGER_ENCRYPTION_KEY_FILE";
+const ENV_KEY_ENCRYPTION_PASSPHRASE_FILE: &str = "KEY_LOGGER_ENCRYPTION_PASSPHRASE_FILE";
 
-#[derive(Debug, Clone, Default)]
+const DEFAULT_OUTPUT_DIR: &str = "csv";
+pub const DEFAULT_FILENAME_PATTERN: &str = "keylog_%Y-%m-%d_%H-%M-%S";
+const MIN_POLLING_INTERVAL_MS: u64 = 1;
+const MAX_POLLING_INTERVAL_MS: u64 = 1000;
+
+/// Command-line arguments. Every option can also be set through its environment variable
+/// or the TOML config file; precedence is CLI > environment > file > default.
+#[derive(Debug, Default, Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// TOML configuration file
+    #[arg(short, long, env = ENV_KEY_CONFIG)]
+    pub config: Option<PathBuf>,
+
+    /// Directory for exported CSV files [default: csv]
+    #[arg(short, long, env = ENV_KEY_OUTPUT_DIR)]
+    pub output_dir: Option<PathBuf>,
+
+    /// strftime pattern for the count CSV name, without extension [default: keylog_%Y-%m-%d_%H-%M-%S]
+    #[arg(long, env = ENV_KEY_FILENAME_PATTERN)]
+    pub filename_pattern: Option<String>,
+
+    /// Output format: csv, json (one document per export) or sqlite (one growing database) [default: csv]
+    #[arg(long, env = ENV_KEY_FORMAT)]
+    pub format: Option<String>,
+
+    /// Polling interval of the polling backend in milliseconds [default: 10]
+    #[arg(long, env = ENV_KEY_POLLING_INTERVAL_MS)]
+    pub polling_interval_ms: Option<u64>,
+
+    /// Checkpoint interval in seconds; 0 disables autosave [default: 0]
+    #[arg(long, env = ENV_KEY_AUTOSAVE_SECS)]
+    pub autosave_secs: Option<u64>,
+
+    /// Idle seconds that end a typing session; 0 disables session statistics [default: 0]
+    #[arg(long, env = ENV_KEY_SESSION_GAP_SECS)]
+    pub session_gap_secs: Option<u64>,
+
+    /// Count key presses [default: true]
+    #[arg(long, env = ENV_KEY_UNIGRAMS, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub unigrams: Option<bool>,
+
+    /// Count key-to-key transitions [default: false]
+    #[arg(long, env = ENV_KEY_BIGRAMS, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub bigrams: Option<bool>,
+
+    /// Collect hold-duration and inter-key interval histograms [default: false]
+    #[arg(long, env = ENV_KEY_TIMING, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub timing: Option<bool>,
+
+    /// Count (held modifier set, key) pairs [default: false]
+    #[arg(long, env = ENV_KEY_CHORDS, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub chords: Option<bool>,
+
+    /// Also count key presses per keyboard (evdev backend and replays only) [default: false]
+    #[arg(long, env = ENV_KEY_PER_DEVICE, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub per_device: Option<bool>,
+
+    /// Comma-separated key labels to record; every other key is dropped (e.g. A,B,C,Space)
+    #[arg(long, env = ENV_KEY_INCLUDE, value_delimiter = ',')]
+    pub include: Option<Vec<String>>,
+
+    /// Comma-separated key labels that are never recorded (e.g. Numpad0,Numpad1)
+    #[arg(long, env = ENV_KEY_EXCLUDE, value_delimiter = ',')]
+    pub exclude: Option<Vec<String>>,
+
+    /// Physical keyboard layout recorded in the metadata: US, ISO or JIS [default: US]
+    #[arg(long, env = ENV_KEY_LAYOUT)]
+    pub layout: Option<String>,
+
+    /// Write a `.meta.json` sidecar next to the count CSV [default: true]
+    #[arg(long, env = ENV_KEY_METADATA, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub metadata: Option<bool>,
+
+    /// Start new files at every day or hour boundary: daily, hourly or off [default: off]
+    #[arg(long, env = ENV_KEY_ROTATE)]
+    pub rotate: Option<String>,
+
+    /// Answer JSON status queries on this Unix domain socket (Unix only)
+    #[arg(long, env = ENV_KEY_STATUS_SOCKET, value_name = "PATH")]
+    pub status_socket: Option<PathBuf>,
+
+    /// Show a live heatmap and top-key table in the terminal [default: false]
+    #[arg(long, env = ENV_KEY_TUI, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub tui: Option<bool>,
+
+    /// Run as a service: pidfile, systemd readiness and watchdog notifications [default: false]
+    #[arg(long, env = ENV_KEY_DAEMON, value_name = "BOOL", num_args = 0..=1,
+          default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub daemon: Option<bool>,
+
+    /// Pidfile written in daemon mode [default: <output-dir>/key_logger.pid]
+    #[arg(long, env = ENV_KEY_PID_FILE, value_name = "PATH")]
+    pub pid_file: Option<PathBuf>,
+
+    /// Append log output to this file instead of writing it to stderr
+    #[arg(long, env = ENV_KEY_LOG_FILE, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Add Laplace noise with this privacy budget to the exported key counts
+    #[arg(long, env = ENV_KEY_DP_EPSILON, value_name = "EPSILON")]
+    pub dp_epsilon: Option<f64>,
+
+    /// Leave keys counted fewer times than this out of the count CSV [default: 0]
+    #[arg(long, env = ENV_KEY_DP_MIN_COUNT, value_name = "COUNT")]
+    pub dp_min_count: Option<u64>,
+
+    /// Encrypt exported CSVs and the checkpoint with the 256-bit key in this file (64 hex digits)
+    #[arg(long, env = ENV_KEY_ENCRYPTION_KEY_FILE, value_name = "PATH")]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// Encrypt exported CSVs and the checkpoint with the passphrase on the first line of this file
+    #[arg(long, env = ENV_KEY_ENCRYPTION_PASSPHRASE_FILE, value_name = "PATH")]
+    pub encryption_passphrase_file: Option<PathBuf>,
+
+    /// Replay a text file or JSON event list instead of reading the keyboard
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Date the replay as if it started at this RFC 3339 time, e.g. 2025-07-01T09:00:00+09:00 [default: now]
+    #[arg(long, value_name = "TIME", requires = "replay")]
+    pub replay_start: Option<DateTime<FixedOffset>>,
+}
+
+/// Layout of the optional TOML config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    output_dir: Option<PathBuf>,
+    filename_pattern: Option<String>,
+    format: Option<String>,
+    polling_interval_ms: Option<u64>,
+    autosave_interval_secs: Option<u64>,
+    session_gap_secs: Option<u64>,
+    include_keys: Option<Vec<String>>,
+    exclude_keys: Option<Vec<String>>,
+    layout: Option<String>,
+    metadata: Option<bool>,
+    rotate: Option<String>,
+    status_socket: Option<PathBuf>,
+    tui: Option<bool>,
+    daemon: Option<bool>,
+    pid_file: Option<PathBuf>,
+    log_file: Option<PathBuf>,
+    statistics: FileStatistics,
+    privacy: FilePrivacy,
+    encryption: FileEncryption,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileStatistics {
+    unigram: Option<bool>,
+    bigram: Option<bool>,
+    timing: Option<bool>,
+    chords: Option<bool>,
+    per_device: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FilePrivacy {
+    epsilon: Option<f64>,
+    min_count: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileEncryption {
+    key_file: Option<PathBuf>,
+    passphrase_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Config {
     pub output_dir: Option<PathBuf>,
+    /// strftime pattern for the count CSV name, without extension
+    pub filename_pattern: String,
+    /// How exports are stored
+    pub format: OutputFormat,
+    /// Sleep between polls of the polling backend
+    pub polling_interval: Duration,
+    /// Checkpoint interval; `None` disables autosave
+    pub autosave_interval: Option<Duration>,
+    /// Idle time that ends a typing session; `None` disables session statistics
+    pub session_gap: Option<Duration>,
+    /// Count key presses
+    pub unigrams: bool,
+    /// Count key-to-key transitions (opt-in, pairs only)
+    pub bigrams: bool,
+    /// Collect hold-duration and inter-key interval histograms
+    pub timing: bool,
+    /// Count (held modifier set, key) pairs (opt-in)
+    pub chords: bool,
+    /// Count key presses per input device, for backends that can tell devices apart (opt-in)
+    pub per_device: bool,
+    /// Only keys that are recorded; `None` records every key
+    pub include_keys: Option<HashSet<&'static str>>,
+    /// Keys that are dropped before any statistic sees them
+    pub exclude_keys: HashSet<&'static str>,
+    /// Physical layout the data is typed on
+    pub layout: PhysicalLayout,
+    /// Write a metadata sidecar next to the count CSV
+    pub metadata: bool,
+    /// Flush and reset the statistics at every day or hour boundary
+    pub rotation: Option<Rotation>,
+    /// Unix domain socket for live status queries
+    pub status_socket: Option<PathBuf>,
+    /// Live terminal dashboard instead of log output while capturing
+    pub tui: bool,
+    /// Run as a service (pidfile, systemd notifications)
+    pub daemon: bool,
+    /// Pidfile of daemon mode; always set when `daemon` is
+    pub pid_file: Option<PathBuf>,
+    /// File the log is appended to instead of stderr
+    pub log_file: Option<PathBuf>,
+    /// Noise and suppression applied to the exported key counts
+    pub privacy: Privacy,
+    /// Secret the exported CSVs and the checkpoint are encrypted with
+    pub encryption: Option<Secret>,
+    /// Script to replay instead of capturing from the keyboard
+    pub replay: Option<PathBuf>,
+    /// Time the replay's output is dated from; `None` dates it from now
+    pub replay_start: Option<DateTime<Local>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_dir: Some(PathBuf::from(DEFAULT_OUTPUT_DIR)),
+            filename_pattern: DEFAULT_FILENAME_PATTERN.to_string(),
+            format: OutputFormat::default(),
+            polling_interval: POLLING_INTERVAL,
+            autosave_interval: None,
+            session_gap: None,
+            unigrams: true,
+            bigrams: false,
+            timing: false,
+            chords: false,
+            per_device: false,
+            include_keys: None,
+            exclude_keys: HashSet::new(),
+            layout: PhysicalLayout::default(),
+            metadata: true,
+            rotation: None,
+            status_socket: None,
+            tui: false,
+            daemon: false,
+            pid_file: None,
+            log_file: None,
+            privacy: Privacy::default(),
+            encryption: None,
+            replay: None,
+            replay_start: None,
+        }
+    }
 }
 
 impl Config {
-    pub fn from_env() -> Result<Self> {
-        let mut config = Self::default();
+    /// Builds the configuration from the process arguments, environment and config file.
+    pub fn load() -> Result<Self> {
+        Self::from_args(Args::parse())
+    }
 
-        // Set default output directory to "./csv"
-        let default_output_dir = PathBuf::from("csv");
+    pub fn from_args(args: Args) -> Result<Self> {
+        let file = match args.config {
+            Some(ref path) => read_config_file(path)?,
+            None => FileConfig::default(),
+        };
 
-        if let Ok(output_dir) = env::var(ENV_KEY_OUTPUT_DIR)
-            && !output_dir.trim().is_empty()
-        {
-            let path = PathBuf::from(output_dir);
+        let defaults = Self::default();
+
+        // Treat an empty directory value like an unset one
+        let output_dir = args
+            .output_dir
+            .or(file.output_dir)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .or(defaults.output_dir);
+
+        let polling_interval_ms = args
+            .polling_interval_ms
+            .or(file.polling_interval_ms)
+            .unwrap_or(defaults.polling_interval.as_millis() as u64);
+
+        // 0 disables autosave
+        let autosave_interval = args
+            .autosave_secs
+            .or(file.autosave_interval_secs)
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let session_gap = args
+            .session_gap_secs
+            .or(file.session_gap_secs)
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+
+        // The pidfile defaults to the output directory, next to the lock file
+        let daemon = args.daemon.or(file.daemon).unwrap_or(defaults.daemon);
+        let pid_file = match args.pid_file.or(file.pid_file) {
+            Some(path) if !path.as_os_str().is_empty() => Some(path),
+            _ if daemon => output_dir
+                .as_ref()
+                .map(|dir| dir.join(DEFAULT_PID_FILE_NAME)),
+            _ => None,
+        };
+
+        let include_keys = args.include.or(file.include_keys).unwrap_or_default();
+        let exclude_keys = args.exclude.or(file.exclude_keys).unwrap_or_default();
+
+        let layout = match args.layout.or(file.layout) {
+            Some(label) => PhysicalLayout::from_label(&label).ok_or_else(|| {
+                KeyLoggerError::InvalidConfiguration(format!(
+                    "Unknown layout '{label}' (expected US, ISO or JIS)"
+                ))
+            })?,
+            None => defaults.layout,
+        };
+
+        let format = match args.format.or(file.format) {
+            Some(label) => OutputFormat::from_label(&label).ok_or_else(|| {
+                KeyLoggerError::InvalidConfiguration(format!(
+                    "Unknown output format '{label}' (expected csv, json or sqlite)"
+                ))
+            })?,
+            None => defaults.format,
+        };
+
+        let rotation = match args.rotate.or(file.rotate) {
+            Some(label) if label.trim().eq_ignore_ascii_case("off") => None,
+            Some(label) => Some(Rotation::from_label(&label).ok_or_else(|| {
+                KeyLoggerError::InvalidConfiguration(format!(
+                    "Unknown rotation '{label}' (expected daily, hourly or off)"
+                ))
+            })?),
+            None => defaults.rotation,
+        };
+
+        // A secret given on the command line or in the environment replaces the file's
+        // entirely, so the two sources never combine into a key file plus a passphrase
+        let (key_file, passphrase_file) =
+            if args.encryption_key_file.is_some() || args.encryption_passphrase_file.is_some() {
+                (args.encryption_key_file, args.encryption_passphrase_file)
+            } else {
+                (file.encryption.key_file, file.encryption.passphrase_file)
+            };
+        let encryption = load_secret(key_file.as_deref(), passphrase_file.as_deref())?;
 
+        let config = Self {
+            output_dir,
+            filename_pattern: args
+                .filename_pattern
+                .or(file.filename_pattern)
+                .unwrap_or(defaults.filename_pattern),
+            format,
+            polling_interval: Duration::from_millis(polling_interval_ms),
+            autosave_interval,
+            session_gap,
+            unigrams: args
+                .unigrams
+                .or(file.statistics.unigram)
+                .unwrap_or(defaults.unigrams),
+            bigrams: args
+                .bigrams
+                .or(file.statistics.bigram)
+                .unwrap_or(defaults.bigrams),
+            timing: args
+                .timing
+                .or(file.statistics.timing)
+                .unwrap_or(defaults.timing),
+            chords: args
+                .chords
+                .or(file.statistics.chords)
+                .unwrap_or(defaults.chords),
+            per_device: args
+                .per_device
+                .or(file.statistics.per_device)
+                .unwrap_or(defaults.per_device),
+            // An empty list (e.g. KEY_LOGGER_INCLUDE="") means no allow-list
+            include_keys: Some(parse_key_list("include", &include_keys)?)
+                .filter(|keys| !keys.is_empty()),
+            exclude_keys: parse_key_list("exclude", &exclude_keys)?,
+            layout,
+            metadata: args.metadata.or(file.metadata).unwrap_or(defaults.metadata),
+            rotation,
+            status_socket: args
+                .status_socket
+                .or(file.status_socket)
+                .filter(|path| !path.as_os_str().is_empty()),
+            tui: args.tui.or(file.tui).unwrap_or(defaults.tui),
+            daemon,
+            pid_file,
+            log_file: args
+                .log_file
+                .or(file.log_file)
+                .filter(|path| !path.as_os_str().is_empty()),
+            privacy: Privacy {
+                epsilon: args.dp_epsilon.or(file.privacy.epsilon),
+                min_count: args
+                    .dp_min_count
+                    .or(file.privacy.min_count)
+                    .unwrap_or(defaults.privacy.min_count),
+                layout,
+            },
+            encryption,
+            replay: args.replay,
+            replay_start: args.replay_start.map(|start| start.with_timezone(&Local)),
+        };
+
+        config.validate(polling_interval_ms)?;
+        Ok(config)
+    }
+
+    fn validate(&self, polling_interval_ms: u64) -> Result<()> {
+        if let Some(ref path) = self.output_dir {
             // If the path already exists but is not a directory, reject early.
             if path.exists() && !path.is_dir() {
                 return Err(KeyLoggerError::InvalidConfiguration(format!(
@@ -27,11 +476,490 @@ impl Config {
                     path.display()
                 )));
             }
-            config.output_dir = Some(path);
-        } else {
-            // Use default csv directory when environment variable is not set
-            config.output_dir = Some(default_output_dir);
         }
-        Ok(config)
+
+        if !(MIN_POLLING_INTERVAL_MS..=MAX_POLLING_INTERVAL_MS).contains(&polling_interval_ms) {
+            return Err(KeyLoggerError::InvalidConfiguration(format!(
+                "Polling interval must be between {MIN_POLLING_INTERVAL_MS} and {MAX_POLLING_INTERVAL_MS} ms, got {polling_interval_ms}"
+            )));
+        }
+
+        validate_filename_pattern(&self.filename_pattern)?;
+        if self.rotation.is_some() && self.filename_pattern != DEFAULT_FILENAME_PATTERN {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "Filename pattern cannot be combined with rotation; rotated files are named keylog_<period>.csv"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(ref include) = self.include_keys
+            && include.is_subset(&self.exclude_keys)
+        {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "Every included key is also excluded, so nothing would be recorded".to_string(),
+            ));
+        }
+
+        if self.format == OutputFormat::Sqlite {
+            if self.encryption.is_some() {
+                return Err(KeyLoggerError::InvalidConfiguration(
+                    "The SQLite store cannot be encrypted; use the csv or json format".to_string(),
+                ));
+            }
+            if self.filename_pattern != DEFAULT_FILENAME_PATTERN {
+                return Err(KeyLoggerError::InvalidConfiguration(
+                    "Filename pattern does not apply to the SQLite store, which is always keylog.sqlite"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if self.daemon {
+            if self.tui {
+                return Err(KeyLoggerError::InvalidConfiguration(
+                    "The dashboard needs a terminal, so it cannot be combined with daemon mode"
+                        .to_string(),
+                ));
+            }
+            if self.replay.is_some() {
+                return Err(KeyLoggerError::InvalidConfiguration(
+                    "A replay is not a service; drop --daemon".to_string(),
+                ));
+            }
+        } else if self.pid_file.is_some() {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "A pidfile is only written in daemon mode; add --daemon".to_string(),
+            ));
+        }
+
+        if self.tui && !self.unigrams {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "The dashboard shows key counts, so it needs unigrams enabled".to_string(),
+            ));
+        }
+
+        if let Some(epsilon) = self.privacy.epsilon
+            && !(epsilon.is_finite() && epsilon > 0.0)
+        {
+            return Err(KeyLoggerError::InvalidConfiguration(format!(
+                "Privacy epsilon must be a positive number, got {epsilon}"
+            )));
+        }
+        if self.privacy.is_enabled() && !(self.unigrams && self.metadata) {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "Privacy noise applies to the key counts and is recorded in the metadata, so it needs unigrams and metadata enabled"
+                    .to_string(),
+            ));
+        }
+
+        if cfg!(not(unix)) && self.status_socket.is_some() {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "The status socket is only supported on Unix".to_string(),
+            ));
+        }
+
+        if !(self.unigrams
+            || self.bigrams
+            || self.timing
+            || self.chords
+            || self.per_device
+            || self.session_gap.is_some())
+        {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "At least one statistic (unigrams, bigrams, timing, chords, per-device, sessions) must be enabled"
+                    .to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Where the crash-recovery checkpoint lives; `None` for replays, which must only
+    /// count what is in the script and never restore, write or remove a checkpoint.
+    pub fn checkpoint_dir(&self) -> Option<&Path> {
+        self.output_dir.as_deref().filter(|_| self.replay.is_none())
+    }
+
+    /// Include and exclude sets the recorder applies to every event.
+    pub fn key_filter(&self) -> KeyFilter {
+        KeyFilter {
+            include: self.include_keys.clone(),
+            exclude: self.exclude_keys.clone(),
+        }
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<FileConfig> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        KeyLoggerError::InvalidConfiguration(format!(
+            "Failed to read config file '{}': {e}",
+            path.display()
+        ))
+    })?;
+    toml::from_str(&content).map_err(|e| {
+        KeyLoggerError::InvalidConfiguration(format!(
+            "Failed to parse config file '{}': {e}",
+            path.display()
+        ))
+    })
+}
+
+fn load_secret(key_file: Option<&Path>, passphrase_file: Option<&Path>) -> Result<Option<Secret>> {
+    let secret = match (key_file, passphrase_file) {
+        (None, None) => return Ok(None),
+        (Some(path), None) => Secret::from_key_file(path),
+        (None, Some(path)) => Secret::from_passphrase_file(path),
+        (Some(_), Some(_)) => {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "Use either an encryption key file or a passphrase file, not both".to_string(),
+            ));
+        }
+    };
+    secret
+        .map(Some)
+        .map_err(|e| KeyLoggerError::InvalidConfiguration(e.to_string()))
+}
+
+fn validate_filename_pattern(pattern: &str) -> Result<()> {
+    if pattern.trim().is_empty() {
+        return Err(KeyLoggerError::InvalidConfiguration(
+            "Filename pattern must not be empty".to_string(),
+        ));
+    }
+    if pattern.contains(['/', '\\']) {
+        return Err(KeyLoggerError::InvalidConfiguration(format!(
+            "Filename pattern must not contain path separators: '{pattern}'"
+        )));
+    }
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        return Err(KeyLoggerError::InvalidConfiguration(format!(
+            "Invalid strftime specifier in filename pattern: '{pattern}'"
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves key labels or their aliases to the canonical labels the capture backends emit.
+fn parse_key_list(option: &str, labels: &[String]) -> Result<HashSet<&'static str>> {
+    labels
+        .iter()
+        .map(|label| label.trim())
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            KeyId::from_label(label).map(KeyId::label).ok_or_else(|| {
+                KeyLoggerError::InvalidConfiguration(format!("Unknown key in {option}: '{label}'"))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats;
+    use tempfile::TempDir;
+
+    fn args(cli: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("key_logger").chain(cli.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::from_args(Args::default()).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("csv")));
+        assert_eq!(config.filename_pattern, DEFAULT_FILENAME_PATTERN);
+        assert_eq!(config.format, OutputFormat::Csv);
+        assert_eq!(config.polling_interval, Duration::from_millis(10));
+        assert_eq!(config.autosave_interval, None);
+        assert_eq!(config.session_gap, None);
+        assert!(config.unigrams);
+        assert!(!config.bigrams && !config.timing && !config.chords);
+        assert!(!config.per_device);
+        assert_eq!(config.include_keys, None);
+        assert!(config.exclude_keys.is_empty());
+        assert_eq!(config.layout, PhysicalLayout::Us);
+        assert!(config.metadata);
+        assert_eq!(config.rotation, None);
+        assert_eq!(config.status_socket, None);
+        assert!(!config.tui);
+        assert!(!config.daemon);
+        assert_eq!(config.pid_file, None);
+        assert_eq!(config.log_file, None);
+        assert!(!config.privacy.is_enabled());
+        assert_eq!(config.encryption, None);
+    }
+
+    #[test]
+    fn test_cli_arguments() {
+        let config = Config::from_args(args(&[
+            "--output-dir",
+            "out",
+            "--polling-interval-ms",
+            "5",
+            "--autosave-secs",
+            "60",
+            "--session-gap-secs",
+            "300",
+            "--bigrams",
+            "--timing=false",
+            "--include",
+            "a,B,Space,Numpad0",
+            "--exclude",
+            "Numpad0,numpad1",
+            "--rotate",
+            "Hourly",
+            "--format",
+            "SQLite",
+            "--dp-epsilon",
+            "0.5",
+        ]))
+        .unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("out")));
+        assert_eq!(config.polling_interval, Duration::from_millis(5));
+        assert_eq!(config.autosave_interval, Some(Duration::from_secs(60)));
+        assert_eq!(config.session_gap, Some(Duration::from_secs(300)));
+        assert!(config.bigrams);
+        assert!(!config.timing);
+        assert_eq!(
+            config.include_keys,
+            Some(HashSet::from(["A", "B", "Space", "Numpad0"]))
+        );
+        assert_eq!(config.exclude_keys, HashSet::from(["Numpad0", "Numpad1"]));
+        // Exclusion wins over inclusion
+        let filter = config.key_filter();
+        assert!(filter.allows("A"));
+        assert!(!filter.allows("Numpad0"));
+        assert!(!filter.allows("C"));
+        assert_eq!(config.rotation, Some(Rotation::Hourly));
+        assert_eq!(config.format, OutputFormat::Sqlite);
+        assert_eq!(
+            config.privacy,
+            Privacy {
+                epsilon: Some(0.5),
+                min_count: 0,
+                layout: config.layout,
+            }
+        );
+
+        let config = Config::from_args(args(&["--rotate", "off"])).unwrap();
+        assert_eq!(config.rotation, None);
+    }
+
+    #[test]
+    fn test_config_file_and_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("key_logger.toml");
+        std::fs::write(
+            &path,
+            r#"
+output_dir = "from-file"
+filename_pattern = "typing_%Y%m%d"
+format = "json"
+polling_interval_ms = 20
+autosave_interval_secs = 0
+session_gap_secs = 0
+include_keys = ["A", "S", "D", "F"]
+exclude_keys = ["CapsLock"]
+layout = "jis"
+metadata = false
+daemon = true
+log_file = "key_logger.log"
+
+[statistics]
+bigram = true
+chords = true
+per_device = true
+"#,
+        )
+        .unwrap();
+
+        let path_s = path.to_str().unwrap();
+        let config = Config::from_args(args(&["--config", path_s, "--chords=no"])).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("from-file")));
+        assert_eq!(config.filename_pattern, "typing_%Y%m%d");
+        assert_eq!(config.format, OutputFormat::Json);
+        assert_eq!(config.polling_interval, Duration::from_millis(20));
+        assert_eq!(config.autosave_interval, None);
+        assert_eq!(config.session_gap, None);
+        assert!(config.bigrams);
+        // CLI overrides the file
+        assert!(!config.chords);
+        assert!(config.per_device);
+        assert_eq!(
+            config.include_keys,
+            Some(HashSet::from(["A", "S", "D", "F"]))
+        );
+        assert_eq!(config.exclude_keys, HashSet::from(["CapsLock"]));
+        assert_eq!(config.layout, PhysicalLayout::Jis);
+        assert!(config.daemon);
+        assert_eq!(
+            config.pid_file,
+            Some(PathBuf::from("from-file/key_logger.pid"))
+        );
+        assert_eq!(config.log_file, Some(PathBuf::from("key_logger.log")));
+
+        let config = Config::from_args(args(&[
+            "--config",
+            path_s,
+            "--pid-file",
+            "/run/user/1000/key_logger.pid",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.pid_file,
+            Some(PathBuf::from("/run/user/1000/key_logger.pid"))
+        );
+
+        // An empty list on the command line lifts the file's allow-list
+        let config = Config::from_args(args(&["--config", path_s, "--include", ""])).unwrap();
+        assert_eq!(config.include_keys, None);
+        assert!(!config.metadata);
+    }
+
+    #[test]
+    fn test_privacy_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("key_logger.toml");
+        std::fs::write(&path, "[privacy]\nepsilon = 1.0\nmin_count = 10\n").unwrap();
+
+        let path_s = path.to_str().unwrap();
+        let config = Config::from_args(args(&["--config", path_s, "--dp-min-count", "3"])).unwrap();
+        assert_eq!(
+            config.privacy,
+            Privacy {
+                epsilon: Some(1.0),
+                min_count: 3,
+                ..Privacy::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_encryption_secret() {
+        let temp_dir = TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("key");
+        std::fs::write(&key_file, "ab".repeat(32)).unwrap();
+        let passphrase_file = temp_dir.path().join("passphrase");
+        std::fs::write(&passphrase_file, "open sesame\n").unwrap();
+        let path = temp_dir.path().join("key_logger.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[encryption]\nkey_file = {:?}\n",
+                key_file.to_str().unwrap()
+            ),
+        )
+        .unwrap();
+
+        let path_s = path.to_str().unwrap();
+        let config = Config::from_args(args(&["--config", path_s])).unwrap();
+        assert_eq!(config.encryption, Some(Secret::Key([0xab; 32])));
+
+        // The command line replaces the file's key file instead of conflicting with it
+        let passphrase_s = passphrase_file.to_str().unwrap();
+        let config = Config::from_args(args(&[
+            "--config",
+            path_s,
+            "--encryption-passphrase-file",
+            passphrase_s,
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.encryption,
+            Some(Secret::Passphrase("open sesame".to_string()))
+        );
+
+        let key_s = key_file.to_str().unwrap();
+        for cli in [
+            &[
+                "--encryption-key-file",
+                key_s,
+                "--encryption-passphrase-file",
+                passphrase_s,
+            ][..],
+            &["--encryption-key-file", passphrase_s],
+            &["--encryption-passphrase-file", "/nonexistent/passphrase"],
+            &["--encryption-key-file", key_s, "--format", "sqlite"],
+        ] {
+            let err = Config::from_args(args(cli)).unwrap_err();
+            assert!(
+                matches!(err, KeyLoggerError::InvalidConfiguration(_)),
+                "{cli:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filters_accept_every_key_label() {
+        // The labels the capture backends emit are exactly the canonical KeyId labels
+        let labels: Vec<String> = KeyId::all().map(|key| key.label().to_string()).collect();
+        let keys = parse_key_list("include", &labels).unwrap();
+        assert_eq!(keys.len(), labels.len());
+        assert!(
+            keys.iter()
+                .all(|&key| stats::intern_key_label(key) == Some(key))
+        );
+    }
+
+    #[test]
+    fn test_unknown_file_field_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("key_logger.toml");
+        std::fs::write(&path, "poling_interval_ms = 5\n").unwrap();
+
+        let err = Config::from_args(args(&["--config", path.to_str().unwrap()])).unwrap_err();
+        assert!(matches!(err, KeyLoggerError::InvalidConfiguration(_)));
+    }
+
+    #[test]
+    fn test_missing_file_rejected() {
+        let err =
+            Config::from_args(args(&["--config", "/nonexistent/key_logger.toml"])).unwrap_err();
+        assert!(matches!(err, KeyLoggerError::InvalidConfiguration(_)));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        for cli in [
+            &["--polling-interval-ms", "0"][..],
+            &["--polling-interval-ms", "5000"],
+            &["--filename-pattern", ""],
+            &["--filename-pattern", "logs/keylog_%Y"],
+            &["--filename-pattern", "keylog_%Q"],
+            &["--exclude", "NotAKey"],
+            &["--include", "A,Hyper"],
+            &["--include", "A,b", "--exclude", "a,B,C"],
+            &["--layout", "dvorak"],
+            &["--rotate", "weekly"],
+            &["--format", "parquet"],
+            &["--format", "sqlite", "--filename-pattern", "typing_%Y"],
+            &["--tui", "--unigrams=false", "--bigrams"],
+            &["--daemon", "--tui"],
+            &["--daemon", "--replay", "script.txt"],
+            &["--pid-file", "key_logger.pid"],
+            &["--rotate", "daily", "--filename-pattern", "typing_%Y"],
+            &["--unigrams=false"],
+            &["--dp-epsilon", "0"],
+            &["--dp-epsilon=-1"],
+            &["--dp-epsilon", "inf"],
+            &["--dp-min-count", "5", "--metadata=false"],
+            &["--dp-epsilon", "1", "--unigrams=false", "--bigrams"],
+        ] {
+            let err = Config::from_args(args(cli)).unwrap_err();
+            assert!(
+                matches!(err, KeyLoggerError::InvalidConfiguration(_)),
+                "{cli:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_output_path_must_be_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("not_a_dir");
+        std::fs::write(&file, "").unwrap();
+
+        let err = Config::from_args(args(&["--output-dir", file.to_str().unwrap()])).unwrap_err();
+        assert!(matches!(err, KeyLoggerError::InvalidConfiguration(_)));
     }
 }
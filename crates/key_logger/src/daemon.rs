@@ -0,0 +1,242 @@
+//! Running as a background service.
+//!
+//! Every live capture holds an exclusive lock on `key_logger.lock` in the output directory,
+//! so a second logger started on the same directory fails instead of double-counting and
+//! overwriting the checkpoint. Replays skip the lock: they never read or write the
+//! checkpoint, and their exports take a `.2`, `.3`, ... suffix rather than replace an
+//! existing file, so one can run next to the live logger. Daemon mode adds a pidfile and, when started by systemd,
+//! `sd_notify` readiness and watchdog pings; without `NOTIFY_SOCKET` these do nothing.
+//! Shutdown still goes through the exit handler, so `systemctl stop` (SIGTERM) saves the
+//! statistics like Ctrl+C does.
+
+use crate::error::{KeyLoggerError, Result};
+use log::{debug, info};
+use std::{
+    fs::{self, File, TryLockError},
+    path::{Path, PathBuf},
+};
+
+pub const LOCK_FILE_NAME: &str = "key_logger.lock";
+pub const DEFAULT_PID_FILE_NAME: &str = "key_logger.pid";
+
+/// Single-instance lock, released (and the pidfile removed) when dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+    pid_file: Option<PathBuf>,
+}
+
+impl InstanceLock {
+    /// Locks `output_dir` for this process and writes the pidfile, if any.
+    ///
+    /// The lock file itself is left in place on exit; the lock is what matters, and it
+    /// is released by the OS even if the process is killed.
+    pub fn acquire(output_dir: &Path, pid_file: Option<&Path>) -> Result<Self> {
+        fs::create_dir_all(output_dir).map_err(|e| KeyLoggerError::CreateDir {
+            path: output_dir.to_path_buf(),
+            source: e,
+        })?;
+        let lock_path = output_dir.join(LOCK_FILE_NAME);
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| KeyLoggerError::CreateFile {
+                path: lock_path.clone(),
+                source: e,
+            })?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(KeyLoggerError::AlreadyRunning(output_dir.to_path_buf()));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        debug!("Locked {}", lock_path.display());
+
+        if let Some(path) = pid_file {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).map_err(|e| KeyLoggerError::CreateDir {
+                    path: parent.to_path_buf(),
+                    source: e,
+                })?;
+            }
+            fs::write(path, format!("{}\n", std::process::id())).map_err(|e| {
+                KeyLoggerError::WriteFile {
+                    path: path.to_path_buf(),
+                    source: e,
+                }
+            })?;
+            info!("Pidfile: {}", path.display());
+        }
+
+        Ok(Self {
+            _file: file,
+            pid_file: pid_file.map(Path::to_path_buf),
+        })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.pid_file {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use systemd::{notify, spawn_notifier};
+
+#[cfg(unix)]
+mod systemd {
+    use crate::{error::Result, platform_common};
+    use log::{debug, warn};
+    use std::{
+        os::unix::net::UnixDatagram,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const ENV_NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+    const ENV_WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+    const ENV_WATCHDOG_PID: &str = "WATCHDOG_PID";
+    // How often the notifier checks for capture start and shutdown
+    const CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+    /// Sends `state` (e.g. `READY=1`) to the service manager; a no-op outside systemd.
+    pub fn notify(state: &str) {
+        let Ok(socket) = std::env::var(ENV_NOTIFY_SOCKET) else {
+            return;
+        };
+        if let Err(e) = notify_to(&socket, state) {
+            warn!("sd_notify to {socket} failed: {e}");
+        }
+    }
+
+    /// `socket` is a path, or an abstract socket name prefixed with `@`.
+    pub(super) fn notify_to(socket: &str, state: &str) -> std::io::Result<()> {
+        let sender = UnixDatagram::unbound()?;
+        match socket.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+                let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+                sender.send_to_addr(state.as_bytes(), &addr)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "abstract sockets are Linux-only",
+                ));
+            }
+            None => {
+                sender.send_to(state.as_bytes(), socket)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Watchdog period requested by systemd for this process, if any.
+    fn watchdog_interval() -> Option<Duration> {
+        let usec: u64 = std::env::var(ENV_WATCHDOG_USEC).ok()?.parse().ok()?;
+        if let Ok(pid) = std::env::var(ENV_WATCHDOG_PID)
+            && pid.parse() != Ok(std::process::id())
+        {
+            return None;
+        }
+        Some(Duration::from_micros(usec)).filter(|d| !d.is_zero())
+    }
+
+    /// Reports readiness once capture has started, then pings the watchdog at half its
+    /// period for as long as the capture loop keeps going, until exit.
+    ///
+    /// Returns `None` when not started by systemd.
+    pub fn spawn_notifier() -> Result<Option<thread::JoinHandle<()>>> {
+        if std::env::var_os(ENV_NOTIFY_SOCKET).is_none() {
+            return Ok(None);
+        }
+        let watchdog = watchdog_interval().map(|period| period / 2);
+        if let Some(interval) = watchdog {
+            debug!("Pinging the systemd watchdog every {interval:?}");
+        }
+
+        let handle = thread::Builder::new()
+            .name("sd-notify".into())
+            .spawn(move || {
+                let mut ready = false;
+                let mut last_ticks = platform_common::capture_ticks();
+                let mut last_ping = Instant::now();
+                while !platform_common::should_exit() {
+                    thread::sleep(CHECK_INTERVAL);
+                    if !ready {
+                        if let Some(backend) = platform_common::active_backend() {
+                            notify(&format!("READY=1\nSTATUS=Recording ({backend})"));
+                            ready = true;
+                        }
+                        continue;
+                    }
+                    let Some(interval) = watchdog else {
+                        continue;
+                    };
+                    if last_ping.elapsed() < interval {
+                        continue;
+                    }
+                    // A stuck capture loop gets no ping, so systemd restarts the service
+                    let ticks = platform_common::capture_ticks();
+                    if ticks != last_ticks {
+                        notify("WATCHDOG=1");
+                        last_ticks = ticks;
+                        last_ping = Instant::now();
+                    } else {
+                        warn!("Capture loop has not advanced; skipping watchdog ping");
+                    }
+                }
+            })?;
+        Ok(Some(handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_instance_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = temp_dir.path().join("out");
+        let pid_file = temp_dir.path().join("run/key_logger.pid");
+
+        let lock = InstanceLock::acquire(&out, Some(&pid_file)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&pid_file).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+        assert!(matches!(
+            InstanceLock::acquire(&out, None),
+            Err(KeyLoggerError::AlreadyRunning(_))
+        ));
+
+        drop(lock);
+        assert!(!pid_file.exists());
+        InstanceLock::acquire(&out, None).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify_to_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        systemd::notify_to(path.to_str().unwrap(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
@@ -0,0 +1,126 @@
+//! Opt-in per-device key counts.
+//!
+//! Backends that can tell keyboards apart (evdev, and JSON replays) tag each event with the
+//! name the device reports, e.g. `AT Translated Set 2 keyboard`. Names are used rather than
+//! device nodes because `/dev/input/eventN` numbers change between boots; devices reporting
+//! the same name are counted together. Events without a device are not attributed.
+
+use crate::error::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Presses and auto-repeats of one key on one device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceCounts {
+    pub presses: u64,
+    pub repeats: u64,
+}
+
+/// Counts keyed by (device name, key label)
+pub type DeviceMap = HashMap<(&'static str, &'static str), DeviceCounts>;
+
+pub type DeviceStatistics = Arc<Mutex<DeviceMap>>;
+
+// Every device name seen so far; there are only ever a handful
+static DEVICE_NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Returns a static copy of `name`, allocating it only the first time it is seen.
+pub fn intern_device_name(name: &str) -> &'static str {
+    let mut names = DEVICE_NAMES.lock().unwrap_or_else(|p| p.into_inner());
+    if let Some(&interned) = names.iter().find(|&&interned| interned == name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_owned().into_boxed_str());
+    names.push(interned);
+    interned
+}
+
+pub fn create_device_statistics() -> DeviceStatistics {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+pub fn get_device_snapshot(devices: &DeviceStatistics) -> Result<DeviceMap> {
+    Ok(devices.lock().unwrap_or_else(|p| p.into_inner()).clone())
+}
+
+/// Returns the counts and resets them to zero under a single lock.
+pub fn take_device_snapshot(devices: &DeviceStatistics) -> Result<DeviceMap> {
+    Ok(std::mem::take(
+        &mut *devices.lock().unwrap_or_else(|p| p.into_inner()),
+    ))
+}
+
+/// Adds previously taken counts back (e.g. after a failed export).
+pub fn merge_devices(devices: &DeviceStatistics, counts: &DeviceMap) -> Result<()> {
+    let mut guard = devices.lock().unwrap_or_else(|p| p.into_inner());
+    for (&entry, counts) in counts {
+        let total = guard.entry(entry).or_default();
+        total.presses += counts.presses;
+        total.repeats += counts.repeats;
+    }
+    Ok(())
+}
+
+/// Counts `presses` and `repeats`, both given as (device, key) pairs.
+pub fn add_many<I, J>(devices: &DeviceStatistics, presses: I, repeats: J) -> Result<()>
+where
+    I: IntoIterator<Item = (&'static str, &'static str)>,
+    J: IntoIterator<Item = (&'static str, &'static str)>,
+{
+    let mut guard = devices.lock().unwrap_or_else(|p| p.into_inner());
+    for entry in presses {
+        guard.entry(entry).or_default().presses += 1;
+    }
+    for entry in repeats {
+        guard.entry(entry).or_default().repeats += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_per_device() {
+        let devices = create_device_statistics();
+        add_many(
+            &devices,
+            [("Laptop", "A"), ("Split", "A"), ("Split", "A")],
+            [("Split", "Backspace")],
+        )
+        .unwrap();
+
+        let taken = take_device_snapshot(&devices).unwrap();
+        assert_eq!(
+            taken[&("Split", "A")],
+            DeviceCounts {
+                presses: 2,
+                repeats: 0
+            }
+        );
+        assert_eq!(taken[&("Laptop", "A")].presses, 1);
+        assert_eq!(taken[&("Split", "Backspace")].repeats, 1);
+        assert!(get_device_snapshot(&devices).unwrap().is_empty());
+
+        add_many(&devices, [("Split", "A")], []).unwrap();
+        merge_devices(&devices, &taken).unwrap();
+        assert_eq!(
+            get_device_snapshot(&devices).unwrap()[&("Split", "A")].presses,
+            3
+        );
+    }
+
+    #[test]
+    fn test_intern_device_name() {
+        let name = String::from("ZSA Moonlander Mark I");
+        let interned = intern_device_name(&name);
+        assert_eq!(interned, name);
+        assert!(std::ptr::eq(
+            interned,
+            intern_device_name("ZSA Moonlander Mark I")
+        ));
+    }
+}
@@ -7,9 +7,18 @@ pub enum KeyLoggerError {
     #[error("Invalid configuration: {0}")]
     InvalidConfiguration(String),
 
+    #[error("Invalid replay script: {0}")]
+    InvalidReplay(String),
+
+    #[error("Not a canonical key label: {0}")]
+    UnknownKey(String),
+
     #[error("Failed to acquire statistics lock")]
     StatisticsLockError,
 
+    #[error("Another key_logger is already recording to {}", .0.display())]
+    AlreadyRunning(PathBuf),
+
     #[error("Platform not supported")]
     PlatformNotSupported,
 
@@ -46,6 +55,12 @@ pub enum KeyLoggerError {
     #[error(transparent)]
     Csv(#[from] csv::Error),
 
+    #[error(transparent)]
+    Encryption(#[from] keylog_crypt::Error),
+
+    #[error(transparent)]
+    Database(#[from] rusqlite::Error),
+
     #[error(transparent)]
     EnvVar(#[from] std::env::VarError),
 
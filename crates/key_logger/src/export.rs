@@ -1,36 +1,611 @@
-use crate::error::{KeyLoggerError, Result};
-use chrono::Local;
-use csv::WriterBuilder;
+mod json;
+mod sqlite;
+
+use crate::{
+    chords::ModifierSet,
+    clock,
+    devices::{DeviceCounts, DeviceMap},
+    error::{KeyLoggerError, Result},
+    metadata::{self, SessionMetadata},
+    privacy::Privacy,
+    recorder::Snapshot,
+    sessions::Session,
+    timing::{self, TimingData},
+};
+use chrono::SecondsFormat;
+use csv::{Writer, WriterBuilder};
+use keylog_crypt::{ENCRYPTED_EXTENSION, Secret};
+use log::{debug, info};
 use std::{
     collections::HashMap,
-    fs::File,
-    io::BufWriter,
+    io::Write,
     path::{Path, PathBuf},
 };
 
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+const COUNTS_FILE_PREFIX: &str = "keylog_";
+const BIGRAMS_FILE_PREFIX: &str = "keylog_bigrams_";
+const TIMING_FILE_PREFIX: &str = "keylog_timing_";
+const CHORDS_FILE_PREFIX: &str = "keylog_chords_";
+const SESSIONS_FILE_PREFIX: &str = "keylog_sessions_";
+const DEVICES_FILE_PREFIX: &str = "keylog_devices_";
+
+/// How exports are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One CSV per statistic and export, plus the metadata sidecar
+    #[default]
+    Csv,
+    /// One JSON document per export, metadata included
+    Json,
+    /// Every export appended to a single SQLite database in the output directory
+    Sqlite,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] =
+        [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Sqlite];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Sqlite => "sqlite",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label.trim();
+        Self::ALL
+            .into_iter()
+            .find(|f| f.label().eq_ignore_ascii_case(label))
+    }
+
+    fn writer(self) -> &'static dyn SnapshotWriter {
+        match self {
+            OutputFormat::Csv => &CsvFiles,
+            OutputFormat::Json => &json::JsonDocument,
+            OutputFormat::Sqlite => &sqlite::SqliteStore,
+        }
+    }
+}
+
+/// Storage for one export of the statistics.
+///
+/// Every format receives the same snapshot and settings: `privacy` applies to the key
+/// counts only, `encryption` to everything the format writes.
+pub trait SnapshotWriter {
+    /// Writes every non-empty statistic in `snapshot` and returns the file that now holds
+    /// the key counts, if any were written.
+    fn write(
+        &self,
+        snapshot: &Snapshot,
+        privacy: &Privacy,
+        output_dir: Option<&Path>,
+        encryption: Option<&Secret>,
+        names: &ExportNames,
+        session: Option<&SessionMetadata>,
+    ) -> Result<Option<PathBuf>>;
+}
+
+/// File names of one export: `<counts>.csv` for the key counts and
+/// `keylog_<kind>_<timestamp>.csv` for the auxiliary statistics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportNames {
+    pub counts: String,
+    pub timestamp: String,
+}
+
+impl ExportNames {
+    /// Names for an export made now, with the count file named by the strftime `filename_pattern`.
+    ///
+    /// Like [`ExportNames::for_period`], a name already taken in `output_dir` (two flushes
+    /// within one second, or a pattern such as `keylog_%Y-%m-%d`) gets `.2`, `.3`, ...
+    pub fn now(filename_pattern: &str, output_dir: Option<&Path>) -> Self {
+        let now = clock::now();
+        Self::unused(
+            &now.format(filename_pattern).to_string(),
+            &now.format(TIMESTAMP_FORMAT).to_string(),
+            output_dir,
+        )
+    }
+
+    /// Names for a rotation period such as `2025-07-27`, e.g. `keylog_2025-07-27.csv`.
+    ///
+    /// If files for the period already exist in `output_dir` (the logger was restarted
+    /// within the period), `.2`, `.3`, ... is appended so earlier data is never overwritten.
+    pub fn for_period(period: &str, output_dir: Option<&Path>) -> Self {
+        Self::unused(&format!("{COUNTS_FILE_PREFIX}{period}"), period, output_dir)
+    }
+
+    /// `counts` and `timestamp`, both suffixed with the first `.N` for which none of the
+    /// export's files exist in `output_dir` yet.
+    fn unused(counts: &str, timestamp: &str, output_dir: Option<&Path>) -> Self {
+        let dir = output_dir.unwrap_or(Path::new(""));
+        let taken = |names: &ExportNames| {
+            [
+                BIGRAMS_FILE_PREFIX,
+                TIMING_FILE_PREFIX,
+                CHORDS_FILE_PREFIX,
+                SESSIONS_FILE_PREFIX,
+                DEVICES_FILE_PREFIX,
+            ]
+            .iter()
+            .map(|prefix| format!("{prefix}{}.csv", names.timestamp))
+            .chain([
+                format!("{}.csv", names.counts),
+                format!("{}.json", names.counts),
+            ])
+            .any(|name| {
+                let path = dir.join(name);
+                path.exists() || encrypted_path(&path).exists()
+            })
+        };
+
+        let mut names = Self {
+            counts: counts.to_string(),
+            timestamp: timestamp.to_string(),
+        };
+        let mut n = 1;
+        while taken(&names) {
+            n += 1;
+            names = Self {
+                counts: format!("{counts}.{n}"),
+                timestamp: format!("{timestamp}.{n}"),
+            };
+        }
+        names
+    }
+}
+
+/// Writes `snapshot` in `format`, with the metadata when `session` is given, and returns
+/// the file holding the key counts if they were written.
+pub fn export_snapshot(
+    snapshot: &Snapshot,
+    format: OutputFormat,
+    privacy: &Privacy,
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+    session: Option<&SessionMetadata>,
+) -> Result<Option<PathBuf>> {
+    format
+        .writer()
+        .write(snapshot, privacy, output_dir, encryption, names, session)
+}
+
+/// One CSV per statistic, named after `names`, and a metadata sidecar for the count CSV.
+struct CsvFiles;
+
+impl SnapshotWriter for CsvFiles {
+    fn write(
+        &self,
+        snapshot: &Snapshot,
+        privacy: &Privacy,
+        output_dir: Option<&Path>,
+        encryption: Option<&Secret>,
+        names: &ExportNames,
+        session: Option<&SessionMetadata>,
+    ) -> Result<Option<PathBuf>> {
+        let mut counts_path = None;
+        if let Some(ref keys) = snapshot.keys
+            && !(keys.is_empty() && snapshot.repeats.as_ref().is_none_or(HashMap::is_empty))
+        {
+            let no_repeats = HashMap::new();
+            let repeats = snapshot.repeats.as_ref().unwrap_or(&no_repeats);
+            let path =
+                export_to_csv_with_path(keys, repeats, privacy, output_dir, encryption, names)?;
+            info!("Statistics saved to: {}", path.display());
+
+            if let Some(session) = session {
+                let meta_path = metadata::write_metadata(&path, session, encryption)?;
+                debug!("Metadata saved to: {}", meta_path.display());
+            }
+            counts_path = Some(path);
+        }
+
+        if let Some(ref bigrams) = snapshot.bigrams
+            && !bigrams.is_empty()
+        {
+            let path = export_bigrams_to_csv_with_path(bigrams, output_dir, encryption, names)?;
+            info!("Bigram statistics saved to: {}", path.display());
+        }
+
+        if let Some(ref timing) = snapshot.timing
+            && !timing.is_empty()
+        {
+            let path = export_timing_to_csv_with_path(timing, output_dir, encryption, names)?;
+            info!("Timing histograms saved to: {}", path.display());
+        }
+
+        if let Some(ref chords) = snapshot.chords
+            && !chords.is_empty()
+        {
+            let path = export_chords_to_csv_with_path(chords, output_dir, encryption, names)?;
+            info!("Chord statistics saved to: {}", path.display());
+        }
+
+        if let Some(ref sessions) = snapshot.sessions
+            && !sessions.is_empty()
+        {
+            let path = export_sessions_to_csv_with_path(sessions, output_dir, encryption, names)?;
+            info!("Session statistics saved to: {}", path.display());
+        }
+
+        if let Some(ref devices) = snapshot.devices
+            && !devices.is_empty()
+        {
+            let path = export_devices_to_csv_with_path(devices, output_dir, encryption, names)?;
+            info!("Per-device statistics saved to: {}", path.display());
+        }
+
+        Ok(counts_path)
+    }
+}
+
+/// Writes key counts and auto-repeats to `<names.counts>.csv`.
+///
+/// When `privacy` is enabled the counts are noised and small ones suppressed first; the
+/// parameters used end up in the metadata sidecar.
 pub fn export_to_csv_with_path(
     stats: &HashMap<&'static str, u64>,
+    repeats: &HashMap<&'static str, u64>,
+    privacy: &Privacy,
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+) -> Result<PathBuf> {
+    let filename = format!("{}.csv", names.counts);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    if privacy.is_enabled() {
+        let (stats, repeats) = privacy.apply(stats, repeats, &mut rand::rng());
+        write_key_counts(&mut wtr, &stats, &repeats)?;
+    } else {
+        write_key_counts(&mut wtr, stats, repeats)?;
+    }
+
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// Writes `Key,Count,Repeat` rows sorted by descending count.
+///
+/// `Count` holds presses only; a key that was held past a flush may appear with repeats alone.
+pub(crate) fn write_key_counts<W: Write>(
+    wtr: &mut Writer<W>,
+    stats: &HashMap<&'static str, u64>,
+    repeats: &HashMap<&'static str, u64>,
+) -> Result<()> {
+    wtr.write_record(["Key", "Count", "Repeat"])?;
+    for (key, count, repeat) in key_count_rows(stats, repeats) {
+        let count_s = count.to_string();
+        let repeat_s = repeat.to_string();
+        wtr.write_record([key, count_s.as_str(), repeat_s.as_str()])?;
+    }
+    Ok(())
+}
+
+/// `(key, presses, repeats)` of every key with either, by descending presses.
+fn key_count_rows(
+    stats: &HashMap<&'static str, u64>,
+    repeats: &HashMap<&'static str, u64>,
+) -> Vec<(&'static str, u64, u64)> {
+    let mut rows: Vec<(&'static str, u64, u64)> = stats
+        .iter()
+        .map(|(&k, &v)| (k, v, repeats.get(k).copied().unwrap_or(0)))
+        .chain(
+            repeats
+                .iter()
+                .filter(|(k, _)| !stats.contains_key(*k))
+                .map(|(&k, &r)| (k, 0, r)),
+        )
+        .collect();
+    rows.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    rows
+}
+
+/// Key counts as they are exported: noised and suppressed when `privacy` is enabled.
+fn exported_key_counts(
+    snapshot: &Snapshot,
+    privacy: &Privacy,
+) -> Option<Vec<(&'static str, u64, u64)>> {
+    let keys = snapshot.keys.as_ref()?;
+    let no_repeats = HashMap::new();
+    let repeats = snapshot.repeats.as_ref().unwrap_or(&no_repeats);
+    if keys.is_empty() && repeats.is_empty() {
+        return None;
+    }
+    Some(if privacy.is_enabled() {
+        let (keys, repeats) = privacy.apply(keys, repeats, &mut rand::rng());
+        key_count_rows(&keys, &repeats)
+    } else {
+        key_count_rows(keys, repeats)
+    })
+}
+
+/// Writes key-to-key transition counts as `From,To,Count`.
+pub fn export_bigrams_to_csv_with_path(
+    bigrams: &HashMap<(&'static str, &'static str), u64>,
     output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
 ) -> Result<PathBuf> {
-    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
-    let filename = format!("keylog_{timestamp}.csv");
+    let filename = format!("{BIGRAMS_FILE_PREFIX}{}.csv", names.timestamp);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    wtr.write_record(["From", "To", "Count"])?;
+    for (from, to, count) in bigram_rows(bigrams) {
+        let count_s = count.to_string();
+        wtr.write_record([from, to, count_s.as_str()])?;
+    }
 
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// `(from, to, count)` by descending count.
+fn bigram_rows(
+    bigrams: &HashMap<(&'static str, &'static str), u64>,
+) -> Vec<(&'static str, &'static str, u64)> {
+    let mut rows: Vec<_> = bigrams
+        .iter()
+        .map(|(&(from, to), &v)| (from, to, v))
+        .collect();
+    rows.sort_unstable_by(|a, b| {
+        b.2.cmp(&a.2)
+            .then_with(|| a.0.cmp(b.0))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    rows
+}
+
+/// Writes modifier chord counts as `Modifiers,Key,Count`, where `Modifiers` is the
+/// `+`-joined set of held modifiers (e.g. `LeftControl+LeftShift`).
+pub fn export_chords_to_csv_with_path(
+    chords: &HashMap<(ModifierSet, &'static str), u64>,
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+) -> Result<PathBuf> {
+    let filename = format!("{CHORDS_FILE_PREFIX}{}.csv", names.timestamp);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    wtr.write_record(["Modifiers", "Key", "Count"])?;
+    for (modifiers, key, count) in chord_rows(chords) {
+        let count_s = count.to_string();
+        wtr.write_record([modifiers.as_str(), key, count_s.as_str()])?;
+    }
+
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// `(modifiers label, key, count)` by descending count.
+fn chord_rows(
+    chords: &HashMap<(ModifierSet, &'static str), u64>,
+) -> Vec<(String, &'static str, u64)> {
+    let mut rows: Vec<_> = chords
+        .iter()
+        .map(|(&(modifiers, key), &v)| (modifiers.label(), key, v))
+        .collect();
+    rows.sort_unstable_by(|a, b| {
+        b.2.cmp(&a.2)
+            .then_with(|| a.0.cmp(&b.0))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    rows
+}
+
+/// Writes typing sessions as
+/// `Session,Start,End,DurationSecs,Keystrokes,KeysPerMinute,Key,Count`, one row per key
+/// pressed in a session, most pressed first.
+///
+/// `Session` numbers the sessions of the file from 1; the columns up to `KeysPerMinute`
+/// repeat on every row of a session. `KeysPerMinute` is empty for a single-instant session.
+pub fn export_sessions_to_csv_with_path(
+    sessions: &[Session],
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+) -> Result<PathBuf> {
+    let filename = format!("{SESSIONS_FILE_PREFIX}{}.csv", names.timestamp);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    wtr.write_record([
+        "Session",
+        "Start",
+        "End",
+        "DurationSecs",
+        "Keystrokes",
+        "KeysPerMinute",
+        "Key",
+        "Count",
+    ])?;
+    for (index, session) in sessions.iter().enumerate() {
+        let number = (index + 1).to_string();
+        let start = session.start.to_rfc3339_opts(SecondsFormat::Secs, false);
+        let end = session.end.to_rfc3339_opts(SecondsFormat::Secs, false);
+        let duration = format!("{:.3}", session.duration().as_seconds_f64());
+        let keystrokes = session.keystrokes().to_string();
+        let rate = session
+            .keys_per_minute()
+            .map(|kpm| format!("{kpm:.1}"))
+            .unwrap_or_default();
+
+        for (key, count) in session_key_rows(session) {
+            let count_s = count.to_string();
+            wtr.write_record([
+                number.as_str(),
+                start.as_str(),
+                end.as_str(),
+                duration.as_str(),
+                keystrokes.as_str(),
+                rate.as_str(),
+                key,
+                count_s.as_str(),
+            ])?;
+        }
+    }
+
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// `(key, count)` of a session by descending count.
+fn session_key_rows(session: &Session) -> Vec<(&'static str, u64)> {
+    let mut rows: Vec<_> = session.counts.iter().map(|(&k, &v)| (k, v)).collect();
+    rows.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    rows
+}
+
+/// Writes per-device key counts as `Device,Key,Count,Repeat`, grouped by device and most
+/// pressed first within each device.
+pub fn export_devices_to_csv_with_path(
+    devices: &DeviceMap,
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+) -> Result<PathBuf> {
+    let filename = format!("{DEVICES_FILE_PREFIX}{}.csv", names.timestamp);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    wtr.write_record(["Device", "Key", "Count", "Repeat"])?;
+    for (device, key, counts) in device_rows(devices) {
+        wtr.write_record([
+            device,
+            key,
+            counts.presses.to_string().as_str(),
+            counts.repeats.to_string().as_str(),
+        ])?;
+    }
+
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// `(device, key, counts)` grouped by device, most pressed first within each device.
+fn device_rows(devices: &DeviceMap) -> Vec<(&'static str, &'static str, DeviceCounts)> {
+    let mut rows: Vec<_> = devices
+        .iter()
+        .map(|(&(device, key), &counts)| (device, key, counts))
+        .collect();
+    rows.sort_unstable_by(|a, b| {
+        a.0.cmp(b.0)
+            .then_with(|| b.2.presses.cmp(&a.2.presses))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    rows
+}
+
+/// Writes hold-duration and inter-key interval histograms as
+/// `Metric,Key,LowerMs,UpperMs,Count`, one row per non-empty bucket.
+///
+/// `Metric` is `Hold` (per key) or `Interval` (all keys, empty `Key`).
+/// `UpperMs` is empty for the overflow bucket.
+pub fn export_timing_to_csv_with_path(
+    timing: &TimingData,
+    output_dir: Option<&Path>,
+    encryption: Option<&Secret>,
+    names: &ExportNames,
+) -> Result<PathBuf> {
+    let filename = format!("{TIMING_FILE_PREFIX}{}.csv", names.timestamp);
+    let (file_path, mut wtr) = create_csv_writer(output_dir, &filename, encryption)?;
+
+    wtr.write_record(["Metric", "Key", "LowerMs", "UpperMs", "Count"])?;
+    for row in timing_rows(timing) {
+        let lower = format!("{:.3}", row.lower_ms);
+        let upper = row
+            .upper_ms
+            .map(|ms| format!("{ms:.3}"))
+            .unwrap_or_default();
+        let count = row.count.to_string();
+        wtr.write_record([
+            row.metric,
+            row.key,
+            lower.as_str(),
+            upper.as_str(),
+            count.as_str(),
+        ])?;
+    }
+
+    save_csv(&file_path, wtr, encryption)?;
+    Ok(file_path)
+}
+
+/// One non-empty histogram bucket.
+struct TimingRow {
+    /// `Hold` or `Interval`
+    metric: &'static str,
+    /// Empty for `Interval`
+    key: &'static str,
+    lower_ms: f64,
+    /// `None` for the overflow bucket
+    upper_ms: Option<f64>,
+    count: u64,
+}
+
+/// Hold buckets by key, then the interval buckets.
+fn timing_rows(timing: &TimingData) -> Vec<TimingRow> {
+    let mut keys: Vec<&'static str> = timing.hold.keys().copied().collect();
+    keys.sort_unstable();
+    let histograms = keys
+        .into_iter()
+        .map(|key| ("Hold", key, &timing.hold[key]))
+        .chain([("Interval", "", &timing.interval)]);
+
+    let mut rows = Vec::new();
+    for (metric, key, histogram) in histograms {
+        for (index, count) in histogram.buckets() {
+            rows.push(TimingRow {
+                metric,
+                key,
+                lower_ms: timing::bucket_lower_ms(index),
+                upper_ms: timing::bucket_upper_ms(index),
+                count,
+            });
+        }
+    }
+    rows
+}
+
+/// Path of the encrypted counterpart of `path`, e.g. `keylog_x.csv.enc`.
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{ENCRYPTED_EXTENSION}"));
+    path.with_file_name(name)
+}
+
+/// Destination of `filename` in `output_dir`, which is created if needed; `encrypted`
+/// adds the `.enc` suffix.
+fn output_path(output_dir: Option<&Path>, filename: &str, encrypted: bool) -> Result<PathBuf> {
     let file_path = if let Some(dir) = output_dir {
         std::fs::create_dir_all(dir).map_err(|e| KeyLoggerError::CreateDir {
             path: dir.to_path_buf(),
             source: e,
         })?;
-        dir.join(&filename)
+        dir.join(filename)
     } else {
         filename.into()
     };
+    Ok(if encrypted {
+        encrypted_path(&file_path)
+    } else {
+        file_path
+    })
+}
 
-    let file = File::create(&file_path).map_err(|e| KeyLoggerError::CreateFile {
-        path: file_path.clone(),
-        source: e,
-    })?;
+/// Returns the destination of `filename` and an in-memory CSV writer for it.
+///
+/// Rows are buffered so that with `encryption` no plaintext ever reaches the disk; the
+/// destination then gets the `.enc` suffix.
+fn create_csv_writer(
+    output_dir: Option<&Path>,
+    filename: &str,
+    encryption: Option<&Secret>,
+) -> Result<(PathBuf, Writer<Vec<u8>>)> {
+    let file_path = output_path(output_dir, filename, encryption.is_some())?;
 
-    let writer = BufWriter::new(file);
     #[allow(unused_mut)]
     let mut builder = WriterBuilder::new();
     #[cfg(windows)]
@@ -39,27 +614,41 @@ pub fn export_to_csv_with_path(
         builder.terminator(Terminator::CRLF);
     }
 
-    let mut wtr = builder.from_writer(writer);
-
-    wtr.write_record(["Key", "Count"])?;
-    let mut rows: Vec<(&str, u64)> = stats.iter().map(|(&k, &v)| (k, v)).collect();
-    rows.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    Ok((file_path, builder.from_writer(Vec::new())))
+}
 
-    for (key, count) in rows {
-        let count_s = count.to_string();
-        wtr.write_record([key, count_s.as_str()])?;
-    }
+/// Writes the buffered CSV to `file_path`, sealed with `encryption` if given.
+fn save_csv(file_path: &Path, wtr: Writer<Vec<u8>>, encryption: Option<&Secret>) -> Result<()> {
+    let data = wtr
+        .into_inner()
+        .map_err(|e| KeyLoggerError::Io(e.into_error()))?;
+    save_output(file_path, data, encryption)
+}
 
-    wtr.flush()?;
-    Ok(file_path)
+/// Writes `data` to `file_path`, sealed with `encryption` if given.
+fn save_output(file_path: &Path, data: Vec<u8>, encryption: Option<&Secret>) -> Result<()> {
+    let data = match encryption {
+        Some(secret) => keylog_crypt::encrypt(secret, &data)?,
+        None => data,
+    };
+    std::fs::write(file_path, data).map_err(|e| KeyLoggerError::CreateFile {
+        path: file_path.to_path_buf(),
+        source: e,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{config::DEFAULT_FILENAME_PATTERN, devices::DeviceCounts};
+    use chrono::Local;
     use std::collections::HashMap;
     use tempfile::TempDir;
 
+    fn names() -> ExportNames {
+        ExportNames::now(DEFAULT_FILENAME_PATTERN, None)
+    }
+
     #[test]
     fn test_export_to_csv_basic() {
         let temp_dir = TempDir::new().unwrap();
@@ -68,7 +657,14 @@ mod tests {
         stats.insert("B", 3);
         stats.insert("Space", 10);
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         // Verify file exists
@@ -81,13 +677,39 @@ mod tests {
         let mut stats = HashMap::new();
         stats.insert("A", 1);
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         let filename = result.unwrap();
         assert!(std::path::Path::new(&filename).exists());
     }
 
+    #[test]
+    fn test_export_with_filename_pattern() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut stats = HashMap::new();
+        stats.insert("A", 1);
+
+        let path = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &ExportNames::now("typing_%Y", Some(temp_dir.path())),
+        )
+        .unwrap();
+        let expected = format!("typing_{}.csv", Local::now().format("%Y"));
+        assert_eq!(path.file_name().unwrap().to_str().unwrap(), expected);
+    }
+
     #[test]
     fn test_csv_content_format() {
         let temp_dir = TempDir::new().unwrap();
@@ -95,7 +717,14 @@ mod tests {
         stats.insert("A", 5);
         stats.insert("B", 3);
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         let filename = result.unwrap();
@@ -112,7 +741,7 @@ mod tests {
             .iter()
             .map(|s| s.to_string())
             .collect();
-        assert_eq!(headers, vec!["Key", "Count"]);
+        assert_eq!(headers, vec!["Key", "Count", "Repeat"]);
 
         let mut records: Vec<(String, u64)> = Vec::new();
         for result in reader.records() {
@@ -148,16 +777,23 @@ mod tests {
         stats.insert("Enter", 10);
         stats.insert("Tab", 5);
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         let filename = result.unwrap();
         let content = std::fs::read_to_string(&filename).unwrap();
 
         // Verify that content contains expected data regardless of line endings
-        assert!(content.contains("Key,Count"));
-        assert!(content.contains("Enter,10"));
-        assert!(content.contains("Tab,5"));
+        assert!(content.contains("Key,Count,Repeat"));
+        assert!(content.contains("Enter,10,0"));
+        assert!(content.contains("Tab,5,0"));
 
         // Test with CSV parser to ensure proper parsing regardless of line endings
         let file = std::fs::File::open(&filename).unwrap();
@@ -172,7 +808,14 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let stats = HashMap::new();
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         let filename = result.unwrap();
@@ -186,7 +829,7 @@ mod tests {
             .iter()
             .map(|s| s.to_string())
             .collect();
-        assert_eq!(headers, vec!["Key", "Count"]);
+        assert_eq!(headers, vec!["Key", "Count", "Repeat"]);
 
         // But no data records
         let record_count = reader.records().count();
@@ -200,7 +843,14 @@ mod tests {
         stats.insert("Space", u64::MAX);
         stats.insert("A", 999_999_999);
 
-        let result = export_to_csv_with_path(&stats, Some(temp_dir.path()));
+        let result = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        );
         assert!(result.is_ok());
 
         let filename = result.unwrap();
@@ -226,4 +876,257 @@ mod tests {
         assert!(found_max, "Failed to find u64::MAX value");
         assert!(found_large, "Failed to find large count value");
     }
+
+    #[test]
+    fn test_repeat_column() {
+        let temp_dir = TempDir::new().unwrap();
+        let stats = HashMap::from([("A", 4), ("B", 2)]);
+        let repeats = HashMap::from([("A", 30), ("Backspace", 12)]);
+
+        let path = export_to_csv_with_path(
+            &stats,
+            &repeats,
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            ["Key,Count,Repeat", "A,4,30", "B,2,0", "Backspace,0,12"]
+        );
+    }
+
+    #[test]
+    fn test_encrypted_export() {
+        let temp_dir = TempDir::new().unwrap();
+        let secret = Secret::Key([9; 32]);
+        let snapshot = Snapshot {
+            keys: Some(HashMap::from([("A", 4)])),
+            bigrams: Some(HashMap::from([(("A", "B"), 1)])),
+            ..Snapshot::default()
+        };
+        let names = ExportNames::for_period("2025-07-27", Some(temp_dir.path()));
+
+        let path = export_snapshot(
+            &snapshot,
+            OutputFormat::Csv,
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            Some(&secret),
+            &names,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(path, temp_dir.path().join("keylog_2025-07-27.csv.enc"));
+        let data = std::fs::read(&path).unwrap();
+        let csv = String::from_utf8(keylog_crypt::decrypt(&secret, &data).unwrap()).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            ["Key,Count,Repeat", "A,4,0"]
+        );
+        assert!(
+            temp_dir
+                .path()
+                .join("keylog_bigrams_2025-07-27.csv.enc")
+                .exists()
+        );
+
+        // Encrypted files of a period count as taken, too
+        let names = ExportNames::for_period("2025-07-27", Some(temp_dir.path()));
+        assert_eq!(names.timestamp, "2025-07-27.2");
+    }
+
+    #[test]
+    fn test_privacy_applied_to_counts() {
+        let temp_dir = TempDir::new().unwrap();
+        let stats = HashMap::from([("A", 40), ("B", 2)]);
+        let privacy = Privacy {
+            epsilon: None,
+            min_count: 5,
+            ..Privacy::default()
+        };
+
+        let path = export_to_csv_with_path(
+            &stats,
+            &HashMap::new(),
+            &privacy,
+            Some(temp_dir.path()),
+            None,
+            &names(),
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            ["Key,Count,Repeat", "A,40,0"]
+        );
+    }
+
+    #[test]
+    fn test_bigram_csv_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut bigrams = HashMap::new();
+        bigrams.insert(("T", "H"), 7);
+        bigrams.insert(("H", "E"), 4);
+
+        let path = export_bigrams_to_csv_with_path(&bigrams, Some(temp_dir.path()), None, &names())
+            .unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("keylog_bigrams_"));
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let headers: Vec<String> = reader.headers().unwrap().iter().map(String::from).collect();
+        assert_eq!(headers, vec!["From", "To", "Count"]);
+
+        let records: Vec<(String, String, u64)> = reader
+            .records()
+            .map(|r| {
+                let r = r.unwrap();
+                (r[0].to_string(), r[1].to_string(), r[2].parse().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                ("T".to_string(), "H".to_string(), 7),
+                ("H".to_string(), "E".to_string(), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timing_csv_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut timing = TimingData::default();
+        timing
+            .hold
+            .entry("A")
+            .or_default()
+            .record(std::time::Duration::from_millis(100));
+        timing.interval.record(std::time::Duration::from_secs(60));
+
+        let path =
+            export_timing_to_csv_with_path(&timing, Some(temp_dir.path()), None, &names()).unwrap();
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let headers: Vec<String> = reader.headers().unwrap().iter().map(String::from).collect();
+        assert_eq!(
+            headers,
+            vec!["Metric", "Key", "LowerMs", "UpperMs", "Count"]
+        );
+
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(&records[0][0], "Hold");
+        assert_eq!(&records[0][1], "A");
+        let lower: f64 = records[0][2].parse().unwrap();
+        let upper: f64 = records[0][3].parse().unwrap();
+        assert!(lower <= 100.0 && 100.0 < upper);
+        assert_eq!(&records[0][4], "1");
+
+        // One minute is past the last bucket
+        assert_eq!(&records[1][0], "Interval");
+        assert_eq!(&records[1][1], "");
+        assert_eq!(&records[1][3], "");
+        assert_eq!(&records[1][4], "1");
+    }
+
+    #[test]
+    fn test_sessions_csv_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let start = Local::now();
+        let sessions = [
+            Session {
+                start,
+                end: start + chrono::TimeDelta::seconds(90),
+                counts: HashMap::from([("E", 2), ("T", 1)]),
+            },
+            Session {
+                start: start + chrono::TimeDelta::hours(1),
+                end: start + chrono::TimeDelta::hours(1),
+                counts: HashMap::from([("W", 1)]),
+            },
+        ];
+
+        let path =
+            export_sessions_to_csv_with_path(&sessions, Some(temp_dir.path()), None, &names())
+                .unwrap();
+        assert!(
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("keylog_sessions_")
+        );
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let rows: Vec<Vec<String>> = reader
+            .records()
+            .map(|r| r.unwrap().iter().map(str::to_string).collect())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0][0], "1");
+        assert_eq!(rows[0][3..], ["90.000", "3", "2.0", "E", "2"]);
+        assert_eq!(rows[1][6..], ["T", "1"]);
+        assert_eq!(rows[2][0], "2");
+        assert_eq!(rows[2][3..], ["0.000", "1", "", "W", "1"]);
+        assert_eq!(
+            chrono::DateTime::parse_from_rfc3339(&rows[0][1])
+                .unwrap()
+                .timestamp(),
+            start.timestamp()
+        );
+    }
+
+    #[test]
+    fn test_chord_csv_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut ctrl_shift = ModifierSet::default();
+        ctrl_shift.insert("LeftShift");
+        ctrl_shift.insert("LeftControl");
+        let mut chords = HashMap::new();
+        chords.insert((ctrl_shift, "T"), 3);
+
+        let path =
+            export_chords_to_csv_with_path(&chords, Some(temp_dir.path()), None, &names()).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("Modifiers,Key,Count"));
+        assert!(content.contains("LeftControl+LeftShift,T,3"));
+    }
+
+    #[test]
+    fn test_devices_csv_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let counts = |presses, repeats| DeviceCounts { presses, repeats };
+        let devices = HashMap::from([
+            (("Split", "E"), counts(5, 0)),
+            (("Laptop", "A"), counts(2, 0)),
+            (("Split", "Backspace"), counts(7, 40)),
+        ]);
+
+        let path = export_devices_to_csv_with_path(&devices, Some(temp_dir.path()), None, &names())
+            .unwrap();
+        assert!(
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("keylog_devices_")
+        );
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Device,Key,Count,Repeat",
+                "Laptop,A,2,0",
+                "Split,Backspace,7,40",
+                "Split,E,5,0"
+            ]
+        );
+    }
 }
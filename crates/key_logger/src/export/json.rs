@@ -0,0 +1,290 @@
+//! One JSON document per export.
+//!
+//! `<names.counts>.json` holds every enabled statistic with the rows and order of the
+//! corresponding CSV, and the metadata under `metadata` instead of a sidecar:
+//!
+//! ```json
+//! {"metadata": {...}, "keys": [{"key": "Space", "count": 245, "repeat": 0}], "bigrams": [...]}
+//! ```
+//!
+//! Statistics that are disabled or empty are left out.
+
+use super::{
+    ExportNames, SnapshotWriter, TimingRow, bigram_rows, chord_rows, device_rows,
+    exported_key_counts, output_path, save_output, session_key_rows, timing_rows,
+};
+use crate::{
+    error::{KeyLoggerError, Result},
+    metadata::SessionMetadata,
+    privacy::Privacy,
+    recorder::Snapshot,
+};
+use chrono::SecondsFormat;
+use keylog_crypt::Secret;
+use log::info;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+pub(super) struct JsonDocument;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a SessionMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    keys: Vec<KeyRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bigrams: Vec<BigramRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    timing: Vec<TimingJson>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chords: Vec<ChordRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sessions: Vec<SessionRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    devices: Vec<DeviceRow>,
+}
+
+#[derive(Serialize)]
+struct KeyRow {
+    key: &'static str,
+    count: u64,
+    repeat: u64,
+}
+
+#[derive(Serialize)]
+struct BigramRow {
+    from: &'static str,
+    to: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct TimingJson {
+    metric: &'static str,
+    key: &'static str,
+    lower_ms: f64,
+    /// `None` for the overflow bucket
+    upper_ms: Option<f64>,
+    count: u64,
+}
+
+impl From<TimingRow> for TimingJson {
+    fn from(row: TimingRow) -> Self {
+        Self {
+            metric: row.metric,
+            key: row.key,
+            lower_ms: round(row.lower_ms, 3),
+            upper_ms: row.upper_ms.map(|ms| round(ms, 3)),
+            count: row.count,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChordRow {
+    modifiers: String,
+    key: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct SessionRow {
+    start: String,
+    end: String,
+    duration_secs: f64,
+    keystrokes: u64,
+    /// `None` for a single-instant session
+    keys_per_minute: Option<f64>,
+    counts: Vec<SessionKeyRow>,
+}
+
+#[derive(Serialize)]
+struct SessionKeyRow {
+    key: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct DeviceRow {
+    device: &'static str,
+    key: &'static str,
+    count: u64,
+    repeat: u64,
+}
+
+impl SnapshotWriter for JsonDocument {
+    fn write(
+        &self,
+        snapshot: &Snapshot,
+        privacy: &Privacy,
+        output_dir: Option<&Path>,
+        encryption: Option<&Secret>,
+        names: &ExportNames,
+        session: Option<&SessionMetadata>,
+    ) -> Result<Option<PathBuf>> {
+        let keys: Vec<KeyRow> = exported_key_counts(snapshot, privacy)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, count, repeat)| KeyRow { key, count, repeat })
+            .collect();
+        let has_keys = !keys.is_empty();
+
+        let document = Document {
+            metadata: session,
+            keys,
+            bigrams: snapshot
+                .bigrams
+                .as_ref()
+                .map(bigram_rows)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(from, to, count)| BigramRow { from, to, count })
+                .collect(),
+            timing: snapshot
+                .timing
+                .as_ref()
+                .map(timing_rows)
+                .unwrap_or_default()
+                .into_iter()
+                .map(TimingJson::from)
+                .collect(),
+            chords: snapshot
+                .chords
+                .as_ref()
+                .map(chord_rows)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(modifiers, key, count)| ChordRow {
+                    modifiers,
+                    key,
+                    count,
+                })
+                .collect(),
+            sessions: snapshot
+                .sessions
+                .iter()
+                .flatten()
+                .map(|session| SessionRow {
+                    start: session.start.to_rfc3339_opts(SecondsFormat::Secs, false),
+                    end: session.end.to_rfc3339_opts(SecondsFormat::Secs, false),
+                    duration_secs: session.duration().as_seconds_f64(),
+                    keystrokes: session.keystrokes(),
+                    keys_per_minute: session.keys_per_minute().map(|kpm| round(kpm, 1)),
+                    counts: session_key_rows(session)
+                        .into_iter()
+                        .map(|(key, count)| SessionKeyRow { key, count })
+                        .collect(),
+                })
+                .collect(),
+            devices: snapshot
+                .devices
+                .as_ref()
+                .map(device_rows)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(device, key, counts)| DeviceRow {
+                    device,
+                    key,
+                    count: counts.presses,
+                    repeat: counts.repeats,
+                })
+                .collect(),
+        };
+
+        let path = output_path(
+            output_dir,
+            &format!("{}.json", names.counts),
+            encryption.is_some(),
+        )?;
+        let mut json = serde_json::to_vec_pretty(&document)
+            .map_err(|e| KeyLoggerError::Io(std::io::Error::other(e)))?;
+        json.push(b'\n');
+        save_output(&path, json, encryption)?;
+        info!("Statistics saved to: {}", path.display());
+
+        Ok(has_keys.then_some(path))
+    }
+}
+
+/// `value` rounded to `decimals` places, as the CSV prints it.
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        export::OutputFormat,
+        recorder::Statistics,
+        source::{KeyEvent, key},
+    };
+    use chrono::Local;
+    use key_id::PhysicalLayout;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_json_document() {
+        let statistics = Statistics::new().with_bigrams().with_timing();
+        let mut recorder = crate::recorder::Recorder::new(statistics.clone());
+        let ms = Duration::from_millis;
+        recorder
+            .record(&[
+                KeyEvent::press(key("T"), ms(0)),
+                KeyEvent::release(key("T"), ms(90)),
+                KeyEvent::press(key("H"), ms(150)),
+                KeyEvent::repeat(key("H"), ms(650)),
+                KeyEvent::release(key("H"), ms(700)),
+            ])
+            .unwrap();
+        let snapshot = statistics.snapshot().unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let names = ExportNames::now(
+            crate::config::DEFAULT_FILENAME_PATTERN,
+            Some(temp_dir.path()),
+        );
+        let session = SessionMetadata::new(
+            Local::now(),
+            Some("replay"),
+            Duration::ZERO,
+            PhysicalLayout::Us,
+        );
+        let path = crate::export::export_snapshot(
+            &snapshot,
+            OutputFormat::Json,
+            &Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &names,
+            Some(&session),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(path.extension().unwrap(), "json");
+
+        let document: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(document["metadata"]["backend"], "replay");
+        assert_eq!(
+            document["keys"],
+            serde_json::json!([
+                {"key": "H", "count": 1, "repeat": 1},
+                {"key": "T", "count": 1, "repeat": 0}
+            ])
+        );
+        assert_eq!(
+            document["bigrams"],
+            serde_json::json!([{"from": "T", "to": "H", "count": 1}])
+        );
+        assert_eq!(document["timing"][0]["metric"], "Hold");
+        assert_eq!(document["timing"][0]["key"], "H");
+        // Disabled statistics are left out
+        assert!(document.get("chords").is_none());
+        // Everything is in the one document
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+CREATE TABLE IF NOT EXISTS exports (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    exported_at TEXT NOT NULL,
+    session_start TEXT,
+    session_end TEXT,
+    metadata TEXT
+);
+CREATE INDEX IF NOT EXISTS exports_timestamp ON exports (timestamp);
+CREATE TABLE IF NOT EXISTS key_counts (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    key TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    repeat INTEGER NOT NULL,
+    PRIMARY KEY (export_id, key)
+);
+CREATE TABLE IF NOT EXISTS bigrams (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    from_key TEXT NOT NULL,
+    to_key TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (export_id, from_key, to_key)
+);
+CREATE TABLE IF NOT EXISTS timing (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    metric TEXT NOT NULL,
+    key TEXT NOT NULL,
+    lower_ms REAL NOT NULL,
+    upper_ms REAL,
+    count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS chords (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    modifiers TEXT NOT NULL,
+    key TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (export_id, modifiers, key)
+);
+CREATE TABLE IF NOT EXISTS sessions (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    session INTEGER NOT NULL,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    duration_secs REAL NOT NULL,
+    keystrokes INTEGER NOT NULL,
+    keys_per_minute REAL,
+    PRIMARY KEY (export_id, session)
+);
+CREATE TABLE IF NOT EXISTS session_keys (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    session INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (export_id, session, key)
+);
+CREATE TABLE IF NOT EXISTS devices (
+    export_id INTEGER NOT NULL REFERENCES exports (id),
+    device TEXT NOT NULL,
+    key TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    repeat INTEGER NOT NULL,
+    PRIMARY KEY (export_id, device, key)
+);
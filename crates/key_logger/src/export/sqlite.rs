@@ -0,0 +1,306 @@
+//! Append-only SQLite store of every export.
+//!
+//! All exports go to `keylog.sqlite` in the output directory. Each one adds a row to
+//! `exports`, keyed by the timestamp the CSV files would have been named after, and its rows
+//! to the statistic tables, which mirror the CSV columns:
+//!
+//! - `key_counts (export_id, key, count, repeat)`
+//! - `bigrams (export_id, from_key, to_key, count)`
+//! - `timing (export_id, metric, key, lower_ms, upper_ms, count)`
+//! - `chords (export_id, modifiers, key, count)`
+//! - `sessions (export_id, session, start_time, end_time, duration_secs, keystrokes,
+//!   keys_per_minute)` and `session_keys (export_id, session, key, count)`
+//! - `devices (export_id, device, key, count, repeat)`
+//!
+//! Rows are only ever inserted, and each export covers the data since the previous one, so
+//! summing a table over all exports gives the full history.
+
+use super::{
+    ExportNames, SnapshotWriter, bigram_rows, chord_rows, device_rows, exported_key_counts,
+    output_path, session_key_rows, timing_rows,
+};
+use crate::{
+    clock,
+    error::{KeyLoggerError, Result},
+    metadata::SessionMetadata,
+    privacy::Privacy,
+    recorder::Snapshot,
+};
+use chrono::{Local, SecondsFormat};
+use keylog_crypt::Secret;
+use log::info;
+use rusqlite::{Connection, Transaction, params};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const DATABASE_FILE_NAME: &str = "keylog.sqlite";
+/// Stored in `PRAGMA user_version`; bumped whenever a table changes
+const SCHEMA_VERSION: i64 = 1;
+/// How long to wait for another process (e.g. a query) holding the write lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Also read by the analyzer's tests, so both sides agree on the tables
+const SCHEMA: &str = include_str!("schema.sql");
+
+pub(super) struct SqliteStore;
+
+impl SnapshotWriter for SqliteStore {
+    fn write(
+        &self,
+        snapshot: &Snapshot,
+        privacy: &Privacy,
+        output_dir: Option<&Path>,
+        encryption: Option<&Secret>,
+        names: &ExportNames,
+        session: Option<&SessionMetadata>,
+    ) -> Result<Option<PathBuf>> {
+        // The configuration rejects this; appending to an encrypted file is not possible
+        if encryption.is_some() {
+            return Err(KeyLoggerError::InvalidConfiguration(
+                "The SQLite store cannot be encrypted".to_string(),
+            ));
+        }
+
+        let path = output_path(output_dir, DATABASE_FILE_NAME, false)?;
+        let mut conn = open_store(&path)?;
+        let tx = conn.transaction()?;
+        let has_keys = insert_export(&tx, snapshot, privacy, names, session)?;
+        tx.commit()?;
+        info!("Statistics appended to: {}", path.display());
+
+        Ok(has_keys.then_some(path))
+    }
+}
+
+/// Opens (or creates) the store and brings its tables up to date.
+fn open_store(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(KeyLoggerError::InvalidConfiguration(format!(
+            "{} was written by a newer key_logger (schema {version}, supported {SCHEMA_VERSION})",
+            path.display()
+        )));
+    }
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    Ok(conn)
+}
+
+/// Inserts one export and returns whether it had key counts.
+fn insert_export(
+    tx: &Transaction<'_>,
+    snapshot: &Snapshot,
+    privacy: &Privacy,
+    names: &ExportNames,
+    session: Option<&SessionMetadata>,
+) -> Result<bool> {
+    let time = |t: &chrono::DateTime<Local>| t.to_rfc3339_opts(SecondsFormat::Millis, false);
+    let metadata = session
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| KeyLoggerError::Io(std::io::Error::other(e)))?;
+    tx.execute(
+        "INSERT INTO exports (timestamp, exported_at, session_start, session_end, metadata)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            names.timestamp,
+            time(&clock::now()),
+            session.map(|s| time(&s.session_start)),
+            session.map(|s| time(&s.session_end)),
+            metadata,
+        ],
+    )?;
+    let id = tx.last_insert_rowid();
+
+    let key_counts = exported_key_counts(snapshot, privacy).unwrap_or_default();
+    let mut insert = tx.prepare(
+        "INSERT INTO key_counts (export_id, key, count, repeat) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for &(key, count, repeat) in &key_counts {
+        insert.execute(params![id, key, count, repeat])?;
+    }
+
+    if let Some(ref bigrams) = snapshot.bigrams {
+        let mut insert = tx.prepare(
+            "INSERT INTO bigrams (export_id, from_key, to_key, count) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (from, to, count) in bigram_rows(bigrams) {
+            insert.execute(params![id, from, to, count])?;
+        }
+    }
+
+    if let Some(ref timing) = snapshot.timing {
+        let mut insert = tx.prepare(
+            "INSERT INTO timing (export_id, metric, key, lower_ms, upper_ms, count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for row in timing_rows(timing) {
+            insert.execute(params![
+                id,
+                row.metric,
+                row.key,
+                row.lower_ms,
+                row.upper_ms,
+                row.count
+            ])?;
+        }
+    }
+
+    if let Some(ref chords) = snapshot.chords {
+        let mut insert = tx.prepare(
+            "INSERT INTO chords (export_id, modifiers, key, count) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (modifiers, key, count) in chord_rows(chords) {
+            insert.execute(params![id, modifiers, key, count])?;
+        }
+    }
+
+    if let Some(ref sessions) = snapshot.sessions {
+        let mut insert_session = tx.prepare(
+            "INSERT INTO sessions (export_id, session, start_time, end_time, duration_secs,
+                                   keystrokes, keys_per_minute)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        let mut insert_key = tx.prepare(
+            "INSERT INTO session_keys (export_id, session, key, count) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (index, session) in sessions.iter().enumerate() {
+            let number = index as i64 + 1;
+            insert_session.execute(params![
+                id,
+                number,
+                session.start.to_rfc3339_opts(SecondsFormat::Secs, false),
+                session.end.to_rfc3339_opts(SecondsFormat::Secs, false),
+                session.duration().as_seconds_f64(),
+                session.keystrokes(),
+                session.keys_per_minute(),
+            ])?;
+            for (key, count) in session_key_rows(session) {
+                insert_key.execute(params![id, number, key, count])?;
+            }
+        }
+    }
+
+    if let Some(ref devices) = snapshot.devices {
+        let mut insert = tx.prepare(
+            "INSERT INTO devices (export_id, device, key, count, repeat)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (device, key, counts) in device_rows(devices) {
+            insert.execute(params![id, device, key, counts.presses, counts.repeats])?;
+        }
+    }
+
+    Ok(!key_counts.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        export::OutputFormat,
+        recorder::Statistics,
+        source::{KeyEvent, key},
+    };
+    use tempfile::TempDir;
+
+    fn export(statistics: &Statistics, dir: &Path, timestamp: &str) -> Option<PathBuf> {
+        let names = ExportNames {
+            counts: format!("keylog_{timestamp}"),
+            timestamp: timestamp.to_string(),
+        };
+        let snapshot = statistics.take_snapshot().unwrap();
+        crate::export::export_snapshot(
+            &snapshot,
+            OutputFormat::Sqlite,
+            &Privacy::default(),
+            Some(dir),
+            None,
+            &names,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_exports_are_appended() {
+        let temp_dir = TempDir::new().unwrap();
+        let statistics = Statistics::new()
+            .with_bigrams()
+            .with_sessions(Duration::from_secs(60));
+        let mut recorder = crate::recorder::Recorder::new(statistics.clone());
+        let ms = Duration::from_millis;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("T"), ms(0)),
+                KeyEvent::release(key("T"), ms(50)),
+                KeyEvent::press(key("H"), ms(100)),
+                KeyEvent::release(key("H"), ms(150)),
+            ])
+            .unwrap();
+        let path = export(&statistics, temp_dir.path(), "2025-07-27").unwrap();
+        assert_eq!(path.file_name().unwrap(), DATABASE_FILE_NAME);
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("T"), ms(200)),
+                KeyEvent::release(key("T"), ms(250)),
+            ])
+            .unwrap();
+        export(&statistics, temp_dir.path(), "2025-07-28");
+
+        let conn = Connection::open(&path).unwrap();
+        let exports: Vec<String> = conn
+            .prepare("SELECT timestamp FROM exports ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(exports, ["2025-07-27", "2025-07-28"]);
+
+        let total_t: i64 = conn
+            .query_row(
+                "SELECT SUM(count) FROM key_counts WHERE key = 'T'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total_t, 2);
+        // T→H from the first export, H→T across the flush
+        let bigrams: i64 = conn
+            .query_row("SELECT COUNT(*) FROM bigrams", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(bigrams, 2);
+        let session_keys: i64 = conn
+            .query_row(
+                "SELECT SUM(count) FROM session_keys JOIN exports ON exports.id = export_id
+                 WHERE timestamp = '2025-07-27'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(session_keys, 2);
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(DATABASE_FILE_NAME);
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        assert!(matches!(
+            open_store(&path),
+            Err(KeyLoggerError::InvalidConfiguration(_))
+        ));
+    }
+}
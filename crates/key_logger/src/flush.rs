@@ -0,0 +1,208 @@
+//! Exports the live statistics without stopping the logger.
+//!
+//! A background thread writes everything counted so far and resets the counters when a
+//! rotation boundary passes or a flush is requested (SIGUSR2). Each flush covers the data
+//! since the previous one, so the analyzer can sum all files without counting anything twice.
+
+use crate::{
+    checkpoint, clock,
+    config::Config,
+    error::Result,
+    export::{self, ExportNames},
+    metadata::SessionMetadata,
+    platform_common,
+    recorder::Statistics,
+};
+use chrono::{DateTime, Local};
+use log::{error, info};
+use std::{path::Path, thread, time::Duration};
+
+// How often the flush thread checks for shutdown, boundaries and flush requests
+const CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Names for exporting data counted since `data_start`: the rotation period it falls in,
+/// or the configured filename pattern when rotation is off.
+pub fn export_names(config: &Config, data_start: DateTime<Local>) -> ExportNames {
+    match config.rotation {
+        Some(rotation) => ExportNames::for_period(
+            &rotation.period_label(data_start),
+            config.output_dir.as_deref(),
+        ),
+        None => ExportNames::now(&config.filename_pattern, config.output_dir.as_deref()),
+    }
+}
+
+/// Starts the flush thread. It runs until exit and returns when the data still in the
+/// counters started, which the final export records as its session start.
+pub fn spawn_flusher(
+    statistics: Statistics,
+    config: Config,
+    session_start: DateTime<Local>,
+) -> Result<thread::JoinHandle<DateTime<Local>>> {
+    if let Some(rotation) = config.rotation {
+        info!("Rotating output {}", rotation.label());
+    }
+
+    let checkpoint_dir = config.checkpoint_dir().map(Path::to_path_buf);
+
+    let handle = thread::Builder::new().name("flush".into()).spawn(move || {
+        let mut data_start = session_start;
+        let mut boundary = config.rotation.map(|r| r.next_boundary(session_start));
+        while !platform_common::should_exit() {
+            thread::sleep(CHECK_INTERVAL);
+            let now = clock::now();
+            let rotate = boundary.is_some_and(|boundary| now >= boundary);
+            if !(platform_common::take_flush_request() || rotate) {
+                continue;
+            }
+
+            let names = export_names(&config, data_start);
+            match flush_statistics(
+                &statistics,
+                &config,
+                &names,
+                data_start,
+                checkpoint_dir.as_deref(),
+            ) {
+                Ok(()) => data_start = now,
+                Err(e) => error!("Failed to flush statistics: {e}"),
+            }
+            if rotate {
+                boundary = config.rotation.map(|r| r.next_boundary(now));
+            }
+        }
+        data_start
+    })?;
+    Ok(handle)
+}
+
+/// Exports everything counted since `data_start` and resets the counters.
+///
+/// Holds the checkpoint lock throughout, so autosave cannot write counts that are already
+/// in the exported files; the checkpoint is then removed like after a final export. If the
+/// export fails the counts are put back and go into the next export instead.
+fn flush_statistics(
+    statistics: &Statistics,
+    config: &Config,
+    names: &ExportNames,
+    data_start: DateTime<Local>,
+    checkpoint_dir: Option<&Path>,
+) -> Result<()> {
+    let _guard = checkpoint::lock();
+
+    let snapshot = statistics.take_snapshot()?;
+    if snapshot.is_empty() {
+        return Ok(());
+    }
+
+    let session = SessionMetadata::new(
+        data_start,
+        platform_common::active_backend(),
+        config.polling_interval,
+        config.layout,
+    )
+    .with_privacy(&config.privacy);
+    let exported = export::export_snapshot(
+        &snapshot,
+        config.format,
+        &config.privacy,
+        config.output_dir.as_deref(),
+        config.encryption.as_ref(),
+        names,
+        config.metadata.then_some(&session),
+    );
+    if let Err(e) = exported {
+        statistics.restore(&snapshot)?;
+        return Err(e);
+    }
+
+    if let Some(dir) = checkpoint_dir {
+        checkpoint::remove_checkpoint(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::key;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_flush_resets_counts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            output_dir: Some(temp_dir.path().to_path_buf()),
+            bigrams: true,
+            ..Config::default()
+        };
+        let statistics = Statistics::new().with_bigrams();
+        crate::stats::add_many(statistics.keys.as_ref().unwrap(), ["A", "B", "A"].map(key));
+        crate::bigrams::add_many(statistics.bigrams.as_ref().unwrap(), [("A", "B")]).unwrap();
+        checkpoint::write_checkpoint(
+            &HashMap::from([("A", 2)]),
+            &HashMap::new(),
+            temp_dir.path(),
+            None,
+        )
+        .unwrap();
+
+        let names = ExportNames::for_period("2025-07-27", config.output_dir.as_deref());
+        let start = Local.with_ymd_and_hms(2025, 7, 27, 9, 0, 0).unwrap();
+        flush_statistics(
+            &statistics,
+            &config,
+            &names,
+            start,
+            config.output_dir.as_deref(),
+        )
+        .unwrap();
+
+        assert!(temp_dir.path().join("keylog_2025-07-27.csv").exists());
+        assert!(temp_dir.path().join("keylog_2025-07-27.meta.json").exists());
+        assert!(
+            temp_dir
+                .path()
+                .join("keylog_bigrams_2025-07-27.csv")
+                .exists()
+        );
+        assert!(!checkpoint::checkpoint_path(temp_dir.path()).exists());
+        assert!(statistics.snapshot().unwrap().is_empty());
+
+        // A restart within the same day must not overwrite the first file
+        let names = ExportNames::for_period("2025-07-27", config.output_dir.as_deref());
+        assert_eq!(names.counts, "keylog_2025-07-27.2");
+    }
+
+    #[test]
+    fn test_flushes_in_same_second_keep_both_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            output_dir: Some(temp_dir.path().to_path_buf()),
+            // Coarse enough that both flushes always get the same name
+            filename_pattern: "typing_%Y".to_string(),
+            ..Config::default()
+        };
+        let statistics = Statistics::new();
+        let start = Local::now();
+
+        for keys in [["A", "A"], ["B", "B"]] {
+            crate::stats::add_many(statistics.keys.as_ref().unwrap(), keys.map(key));
+            let names = export_names(&config, start);
+            flush_statistics(&statistics, &config, &names, start, None).unwrap();
+        }
+
+        let year = start.format("%Y");
+        let first = std::fs::read_to_string(temp_dir.path().join(format!("typing_{year}.csv")));
+        let second = std::fs::read_to_string(temp_dir.path().join(format!("typing_{year}.2.csv")));
+        assert!(first.unwrap().contains("A,2,0"));
+        assert!(second.unwrap().contains("B,2,0"));
+        assert!(
+            temp_dir
+                .path()
+                .join(format!("typing_{year}.2.meta.json"))
+                .exists()
+        );
+    }
+}
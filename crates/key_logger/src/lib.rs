@@ -0,0 +1,5 @@
+//! Key counting and the error type, as a library so the benchmarks can exercise them.
+//! Everything else lives in the `key_logger` binary.
+
+pub mod error;
+pub mod stats;
@@ -1,20 +1,43 @@
+mod bigrams;
+mod checkpoint;
+mod chords;
+mod clock;
 mod config;
-mod error;
+mod daemon;
+mod devices;
 mod export;
+mod flush;
+mod metadata;
 mod platform;
 mod platform_common;
-mod stats;
+mod privacy;
+mod recorder;
+mod replay;
+mod rotation;
+mod sessions;
+mod source;
+#[cfg(unix)]
+mod status;
+mod timing;
+mod tui;
 
 use anyhow::Result;
-use log::{debug, error, info};
-use std::{io::Write, sync::Arc};
+use key_logger::{error, stats};
+use log::{debug, error, info, warn};
+use std::{fs::File, io::Write, path::Path};
 
 fn main() -> Result<()> {
-    // Initialize logger - defaults to RUST_LOG if set, otherwise INFO
-    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .try_init();
-
-    let config = config::Config::from_env()?;
+    let config = config::Config::load()?;
+    init_logger(config.log_file.as_deref())?;
+    if let Some(start) = config.replay_start {
+        clock::start_at(start);
+    }
+    let session_start = clock::now();
+    // Load the script up front so a malformed one fails before anything is recorded
+    let replay_source = match config.replay {
+        Some(ref path) => Some(replay::ReplaySource::from_file(path)?),
+        None => None,
+    };
     info!("Key Logger starting...");
     info!("Press Ctrl+C to stop and save statistics");
 
@@ -23,29 +46,241 @@ fn main() -> Result<()> {
         None => info!("Output directory: (current working directory)"), // This case should no longer occur with default csv dir
     }
 
-    let statistics = stats::create_statistics();
+    // Held until the final export is written, so no second logger can count the same keys.
+    // Replays leave the checkpoint alone (see `Config::checkpoint_dir`) and need no lock.
+    let instance = match (&config.output_dir, &config.replay) {
+        (Some(dir), None) => Some(daemon::InstanceLock::acquire(
+            dir,
+            config.pid_file.as_deref(),
+        )?),
+        _ => None,
+    };
+    if config.daemon {
+        info!("Running as a daemon");
+    }
+
+    let mut statistics = recorder::Statistics::new();
+    if !config.unigrams {
+        info!("Key counting disabled");
+        statistics = statistics.without_keys();
+    }
+    if let Some(ref include) = config.include_keys {
+        info!("Recording only {} selected keys", include.len());
+    }
+    if !config.exclude_keys.is_empty() {
+        info!(
+            "Excluding {} keys from all statistics",
+            config.exclude_keys.len()
+        );
+    }
+    if config.bigrams {
+        info!("Bigram counting enabled (key pairs only)");
+        statistics = statistics.with_bigrams();
+    }
+    if config.timing {
+        info!("Timing histograms enabled");
+        statistics = statistics.with_timing();
+    }
+    if config.chords {
+        info!("Modifier chord counting enabled (modifier set + key only)");
+        statistics = statistics.with_chords();
+    }
+    if config.per_device {
+        info!("Per-device counting enabled (evdev backend and replays only)");
+        statistics = statistics.with_devices();
+    }
+    if let Some(gap) = config.session_gap {
+        info!(
+            "Session statistics enabled (sessions end after {}s idle)",
+            gap.as_secs()
+        );
+        statistics = statistics.with_sessions(gap);
+    }
+    match config.encryption {
+        Some(keylog_crypt::Secret::Key(_)) => info!("Encrypting output with the key file"),
+        Some(keylog_crypt::Secret::Passphrase(_)) => info!("Encrypting output with the passphrase"),
+        None => {}
+    }
+    if config.privacy.is_enabled() {
+        match config.privacy.epsilon {
+            Some(epsilon) => info!("Exported key counts get Laplace noise (epsilon {epsilon})"),
+            None => info!("Exported key counts are not noised"),
+        }
+        if config.privacy.min_count > 0 {
+            info!(
+                "Keys counted fewer than {} times are left out of the export",
+                config.privacy.min_count
+            );
+        }
+        if config.bigrams
+            || config.timing
+            || config.chords
+            || config.per_device
+            || config.session_gap.is_some()
+        {
+            warn!("Privacy noise only applies to the key count CSV; other statistics stay exact");
+        }
+    }
+
+    if let (Some(dir), Some(keys), Some(repeats)) = (
+        config.checkpoint_dir(),
+        &statistics.keys,
+        &statistics.repeats,
+    ) {
+        // Recover counts from a previous run that did not shut down cleanly
+        match checkpoint::restore_checkpoint(keys, repeats, dir, config.encryption.as_ref()) {
+            Ok(0) => {}
+            Ok(recovered) => info!("Recovered {recovered} key presses from checkpoint"),
+            // Autosave would overwrite a checkpoint that only the right secret can open
+            Err(
+                e @ (error::KeyLoggerError::Encryption(_)
+                | error::KeyLoggerError::InvalidConfiguration(_)),
+            ) => return Err(e.into()),
+            Err(e) => warn!("Failed to read checkpoint: {e}"),
+        }
+    }
+
     platform_common::setup_exit_handler()?;
 
-    let result = platform::start_key_monitoring(Arc::clone(&statistics));
-    match result {
-        Ok(()) if platform_common::should_exit() => {
+    let autosave = match (
+        config.checkpoint_dir(),
+        config.autosave_interval,
+        &statistics.keys,
+        &statistics.repeats,
+    ) {
+        (Some(dir), Some(interval), Some(keys), Some(repeats)) => Some(checkpoint::spawn_autosave(
+            keys.clone(),
+            repeats.clone(),
+            dir.to_path_buf(),
+            config.encryption.clone(),
+            interval,
+        )?),
+        _ => None,
+    };
+    if autosave.is_some()
+        && (config.bigrams
+            || config.timing
+            || config.chords
+            || config.per_device
+            || config.session_gap.is_some())
+    {
+        warn!(
+            "Checkpoints only hold key counts and repeats; other statistics of a crashed run are not recovered"
+        );
+    }
+
+    let flusher = flush::spawn_flusher(statistics.clone(), config.clone(), session_start)?;
+
+    #[cfg(unix)]
+    let status_server = match config.status_socket {
+        Some(ref path) => Some(status::spawn_status_server(
+            path.clone(),
+            statistics.keys.clone(),
+            session_start,
+        )?),
+        None => None,
+    };
+
+    #[cfg(unix)]
+    let notifier = if config.daemon {
+        daemon::spawn_notifier()?
+    } else {
+        None
+    };
+
+    let dashboard = match (&statistics.keys, config.tui) {
+        (Some(keys), true) => Some(tui::spawn_dashboard(keys.clone(), session_start)?),
+        _ => None,
+    };
+
+    let result = match (replay_source, &config.replay) {
+        (Some(source), Some(path)) => {
+            info!("Replaying key events from {}", path.display());
+            replay::run_replay(source, statistics.clone(), &config)
+        }
+        _ => platform::start_key_monitoring(statistics.clone(), &config),
+    };
+    let exit_requested = platform_common::should_exit();
+    // Give the terminal back before logging resumes
+    if let Some(handle) = dashboard {
+        platform_common::request_exit();
+        let _ = handle.join();
+    }
+    let backend = match result {
+        Ok(backend) if exit_requested => {
             info!("Received exit signal, saving statistics...");
+            Some(backend)
         }
-        Ok(()) => {
+        Ok(backend) => {
             info!("Monitoring completed, saving statistics...");
+            Some(backend)
         }
         Err(e) => {
             error!("Error during monitoring: {e}");
+            None
         }
+    };
+
+    // Stop autosave so it cannot write a checkpoint after the final export removes it,
+    // and the flush thread so what is left is exported exactly once, below
+    platform_common::request_exit();
+    #[cfg(unix)]
+    if let Some(handle) = notifier {
+        daemon::notify("STOPPING=1");
+        let _ = handle.join();
+    }
+    if let Some(handle) = autosave {
+        let _ = handle.join();
+    }
+    let data_start = flusher.join().unwrap_or(session_start);
+    // Removes the socket file
+    #[cfg(unix)]
+    if let Some(handle) = status_server {
+        let _ = handle.join();
     }
 
-    save_and_exit(&statistics, &config);
+    let names = flush::export_names(&config, data_start);
+    let session =
+        metadata::SessionMetadata::new(data_start, backend, config.polling_interval, config.layout)
+            .with_privacy(&config.privacy);
+    save_and_exit(&statistics, &config, &names, &session, instance);
 }
 
-fn save_and_exit(statistics: &stats::KeyStatistics, config: &config::Config) -> ! {
+/// Logs to `log_file` (appended, owner-only) or stderr; defaults to RUST_LOG if set, otherwise INFO.
+fn init_logger(log_file: Option<&Path>) -> Result<()> {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(path) = log_file {
+        let mut options = File::options();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options
+            .open(path)
+            .map_err(|e| error::KeyLoggerError::CreateFile {
+                path: path.to_path_buf(),
+                source: e,
+            })?;
+        builder
+            .target(env_logger::Target::Pipe(Box::new(file)))
+            .write_style(env_logger::WriteStyle::Never);
+    }
+    let _ = builder.try_init();
+    Ok(())
+}
+
+fn save_and_exit(
+    statistics: &recorder::Statistics,
+    config: &config::Config,
+    names: &export::ExportNames,
+    session: &metadata::SessionMetadata,
+    instance: Option<daemon::InstanceLock>,
+) -> ! {
     info!("Saving statistics...");
 
-    let result = save_statistics_internal(statistics, config);
+    let result = save_statistics_internal(statistics, config, names, session);
+    // process::exit skips destructors; release the lock and remove the pidfile first
+    drop(instance);
 
     match result {
         Ok(()) => std::process::exit(0),
@@ -58,9 +293,7 @@ fn save_and_exit(statistics: &stats::KeyStatistics, config: &config::Config) ->
                 if let Some(ioe) = cause.downcast_ref::<std::io::Error>() {
                     match ioe.kind() {
                         ErrorKind::PermissionDenied => {
-                            error!(
-                                "Hint: Run in a writable directory or set KEY_LOGGER_OUTPUT_DIR."
-                            );
+                            error!("Hint: Run in a writable directory or pass --output-dir.");
                             break;
                         }
                         ErrorKind::OutOfMemory | ErrorKind::WriteZero => {
@@ -82,11 +315,13 @@ fn save_and_exit(statistics: &stats::KeyStatistics, config: &config::Config) ->
 }
 
 fn save_statistics_internal(
-    statistics: &stats::KeyStatistics,
+    statistics: &recorder::Statistics,
     config: &config::Config,
+    names: &export::ExportNames,
+    session: &metadata::SessionMetadata,
 ) -> Result<()> {
-    let stats_snapshot = stats::get_statistics_snapshot(statistics)?;
-    if stats_snapshot.is_empty() {
+    let snapshot = statistics.snapshot()?;
+    if snapshot.keys.as_ref().is_some_and(|keys| keys.is_empty()) {
         info!("No key presses recorded.");
         return Ok(());
     }
@@ -94,24 +329,61 @@ fn save_statistics_internal(
     if let Some(ref dir) = config.output_dir {
         debug!("Exporting to directory: {}", dir.display());
     }
-    let path = export::export_to_csv_with_path(&stats_snapshot, config.output_dir.as_deref())?;
-    info!("Statistics saved to: {}", path.display());
+    export::export_snapshot(
+        &snapshot,
+        config.format,
+        &config.privacy,
+        config.output_dir.as_deref(),
+        config.encryption.as_ref(),
+        names,
+        config.metadata.then_some(session),
+    )?;
+
+    let Some(stats_snapshot) = snapshot.keys else {
+        return Ok(());
+    };
 
     let total_keys: u64 = stats_snapshot.values().copied().sum();
     let unique_keys = stats_snapshot.len();
     info!("Total key presses: {total_keys}");
     info!("Unique keys pressed: {unique_keys}");
-
-    // Top 10 most pressed keys
-    let mut sorted_keys: Vec<_> = stats_snapshot.iter().collect();
-    sorted_keys.sort_unstable_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let total_repeats: u64 = snapshot.repeats.iter().flat_map(|r| r.values()).sum();
+    if total_repeats > 0 {
+        info!("Auto-repeats (not included above): {total_repeats}");
+    }
 
     info!("Top 10 most pressed keys:");
-    for (i, (key, count)) in sorted_keys.iter().take(10).enumerate() {
+    for (i, (key, count)) in stats::top_keys(&stats_snapshot, 10).into_iter().enumerate() {
         info!("{}. {}: {}", i + 1, key, count);
     }
 
+    let mut foreign: Vec<&str> = stats_snapshot
+        .keys()
+        .copied()
+        .filter(|&label| {
+            key_id::KeyId::from_label(label).is_some_and(|key| !config.layout.has_key(key))
+        })
+        .collect();
+    if !foreign.is_empty() {
+        foreign.sort_unstable();
+        warn!(
+            "Recorded keys a {} board does not have ({}); set --layout to match the keyboard{}",
+            config.layout,
+            foreign.join(", "),
+            if config.privacy.epsilon.is_some() {
+                " (they are left out of the noised export)"
+            } else {
+                ""
+            }
+        );
+    }
+
     debug!("Total entries exported: {}", stats_snapshot.len());
 
+    // Everything in the checkpoint is now part of the exported CSV
+    if let Some(dir) = config.checkpoint_dir() {
+        checkpoint::remove_checkpoint(dir)?;
+    }
+
     Ok(())
 }
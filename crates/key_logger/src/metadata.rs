@@ -0,0 +1,216 @@
+//! Sidecar JSON describing how a count CSV was recorded.
+//!
+//! The CSV itself stays plain `Key,Count,Repeat`; `keylog_<timestamp>.csv` gets a
+//! `keylog_<timestamp>.meta.json` next to it so the analyzer can tell datasets apart.
+//! When the CSV is encrypted, so is its sidecar (`keylog_<timestamp>.meta.json.enc`).
+
+use crate::{
+    clock,
+    error::{KeyLoggerError, Result},
+    export::encrypted_path,
+    privacy::Privacy,
+};
+use chrono::{DateTime, Local};
+use key_id::PhysicalLayout;
+use keylog_crypt::{ENCRYPTED_EXTENSION, Secret};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Bumped whenever a field changes meaning or is removed
+pub const METADATA_SCHEMA_VERSION: u32 = 1;
+pub const METADATA_FILE_SUFFIX: &str = ".meta.json";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionMetadata {
+    pub schema_version: u32,
+    pub key_logger_version: &'static str,
+    pub session_start: DateTime<Local>,
+    pub session_end: DateTime<Local>,
+    /// Name of the capture backend, e.g. `evdev`, `polling` or `replay`
+    pub backend: Option<&'static str>,
+    /// Only set for the polling backend, where it bounds the timing resolution
+    pub polling_interval_ms: Option<u64>,
+    pub layout: &'static str,
+    /// Only present when the counts were noised or suppressed on export
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<PrivacyMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrivacyMetadata {
+    /// `laplace`, or `None` when keys were only suppressed
+    pub mechanism: Option<&'static str>,
+    pub epsilon: Option<f64>,
+    /// Keys with a lower (noisy) count were left out of the CSV
+    pub min_count: u64,
+}
+
+impl SessionMetadata {
+    pub fn new(
+        session_start: DateTime<Local>,
+        backend: Option<&'static str>,
+        polling_interval: Duration,
+        layout: PhysicalLayout,
+    ) -> Self {
+        Self {
+            schema_version: METADATA_SCHEMA_VERSION,
+            key_logger_version: env!("CARGO_PKG_VERSION"),
+            session_start,
+            session_end: clock::now(),
+            backend,
+            polling_interval_ms: (backend == Some("polling"))
+                .then_some(polling_interval.as_millis() as u64),
+            layout: layout.label(),
+            privacy: None,
+        }
+    }
+
+    /// Records the privacy parameters the count CSV was exported with.
+    pub fn with_privacy(mut self, privacy: &Privacy) -> Self {
+        self.privacy = privacy.is_enabled().then(|| PrivacyMetadata {
+            mechanism: privacy.epsilon.map(|_| "laplace"),
+            epsilon: privacy.epsilon,
+            min_count: privacy.min_count,
+        });
+        self
+    }
+}
+
+/// Sidecar path for `csv_path`: same directory and stem, `.meta.json` extension.
+///
+/// An encrypted `keylog_x.csv.enc` shares the sidecar name of `keylog_x.csv`.
+pub fn metadata_path(csv_path: &Path) -> PathBuf {
+    let name = csv_path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name
+        .strip_suffix(&format!(".{ENCRYPTED_EXTENSION}"))
+        .unwrap_or(&name);
+    let stem = Path::new(name)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    csv_path.with_file_name(format!("{stem}{METADATA_FILE_SUFFIX}"))
+}
+
+/// Writes the sidecar for `csv_path` and returns its path. With `encryption` the sidecar
+/// is sealed like the CSV and gets the `.enc` suffix.
+pub fn write_metadata(
+    csv_path: &Path,
+    metadata: &SessionMetadata,
+    encryption: Option<&Secret>,
+) -> Result<PathBuf> {
+    let json = serde_json::to_string_pretty(metadata)
+        .map_err(|e| KeyLoggerError::Io(std::io::Error::other(e)))?
+        + "\n";
+    let (path, data) = match encryption {
+        Some(secret) => (
+            encrypted_path(&metadata_path(csv_path)),
+            keylog_crypt::encrypt(secret, json.as_bytes())?,
+        ),
+        None => (metadata_path(csv_path), json.into_bytes()),
+    };
+    std::fs::write(&path, data).map_err(|e| KeyLoggerError::WriteFile {
+        path: path.clone(),
+        source: e,
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_metadata_path() {
+        assert_eq!(
+            metadata_path(Path::new("csv/keylog_2025-07-27_14-30-00.csv")),
+            Path::new("csv/keylog_2025-07-27_14-30-00.meta.json")
+        );
+        assert_eq!(
+            metadata_path(Path::new("csv/keylog_2025-07-27.csv.enc")),
+            Path::new("csv/keylog_2025-07-27.meta.json")
+        );
+    }
+
+    #[test]
+    fn test_write_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let csv_path = temp_dir.path().join("keylog_test.csv");
+        let start = Local::now();
+
+        let metadata = SessionMetadata::new(
+            start,
+            Some("polling"),
+            Duration::from_millis(10),
+            PhysicalLayout::Jis,
+        );
+        let path = write_metadata(&csv_path, &metadata, None).unwrap();
+        assert_eq!(path, temp_dir.path().join("keylog_test.meta.json"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], METADATA_SCHEMA_VERSION);
+        assert_eq!(json["key_logger_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["backend"], "polling");
+        assert_eq!(json["polling_interval_ms"], 10);
+        assert_eq!(json["layout"], "JIS");
+        assert_eq!(json["session_start"], serde_json::to_value(start).unwrap());
+    }
+
+    #[test]
+    fn test_write_encrypted_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let csv_path = temp_dir.path().join("keylog_test.csv.enc");
+        let secret = Secret::Key([5; 32]);
+        let metadata = SessionMetadata::new(
+            Local::now(),
+            Some("evdev"),
+            Duration::ZERO,
+            PhysicalLayout::Us,
+        );
+
+        let path = write_metadata(&csv_path, &metadata, Some(&secret)).unwrap();
+        assert_eq!(path, temp_dir.path().join("keylog_test.meta.json.enc"));
+        assert!(!temp_dir.path().join("keylog_test.meta.json").exists());
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(keylog_crypt::is_encrypted(&data));
+        let json: serde_json::Value =
+            serde_json::from_slice(&keylog_crypt::decrypt(&secret, &data).unwrap()).unwrap();
+        assert_eq!(json["layout"], "US");
+        assert_eq!(json["backend"], "evdev");
+    }
+
+    #[test]
+    fn test_polling_interval_only_for_polling() {
+        let metadata = SessionMetadata::new(
+            Local::now(),
+            Some("evdev"),
+            Duration::from_millis(10),
+            PhysicalLayout::Us,
+        );
+        assert_eq!(metadata.polling_interval_ms, None);
+    }
+
+    #[test]
+    fn test_privacy_parameters() {
+        let metadata =
+            || SessionMetadata::new(Local::now(), None, Duration::ZERO, PhysicalLayout::Us);
+        let json = serde_json::to_value(metadata().with_privacy(&Privacy::default())).unwrap();
+        assert!(json.get("privacy").is_none());
+
+        let privacy = Privacy {
+            epsilon: Some(0.5),
+            min_count: 10,
+            ..Privacy::default()
+        };
+        let json = serde_json::to_value(metadata().with_privacy(&privacy)).unwrap();
+        assert_eq!(
+            json["privacy"],
+            serde_json::json!({"mechanism": "laplace", "epsilon": 0.5, "min_count": 10})
+        );
+    }
+}
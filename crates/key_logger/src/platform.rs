@@ -16,7 +16,8 @@ pub(crate) use linux::start_key_monitoring;
 // Fallback for unsupported platforms
 #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 pub(crate) fn start_key_monitoring(
-    _stats: crate::stats::KeyStatistics,
-) -> crate::error::Result<()> {
+    _statistics: crate::recorder::Statistics,
+    _config: &crate::config::Config,
+) -> crate::error::Result<&'static str> {
     Err(crate::error::KeyLoggerError::PlatformNotSupported)
 }
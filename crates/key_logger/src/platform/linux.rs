@@ -1,5 +1,36 @@
-use crate::{error::Result, platform_common};
+mod evdev;
 
-pub(crate) fn start_key_monitoring(stats: crate::stats::KeyStatistics) -> Result<()> {
-    platform_common::start_key_monitoring(stats)
+use crate::{
+    config::Config,
+    error::Result,
+    platform_common,
+    recorder::{Recorder, Statistics},
+    source::KeySource,
+};
+use log::{info, warn};
+
+pub(crate) fn start_key_monitoring(
+    statistics: Statistics,
+    config: &Config,
+) -> Result<&'static str> {
+    // Prefer the event-driven backend; fall back to polling when no device node is readable
+    match evdev::open_keyboards() {
+        Ok(devices) if !devices.is_empty() => {
+            let mut source = evdev::EvdevSource::new(devices)?;
+            let mut recorder = Recorder::new(statistics).filtering(config.key_filter());
+            platform_common::run_capture_loop(&mut source, &mut recorder)?;
+            Ok(source.name())
+        }
+        Ok(_) => {
+            warn!("No readable keyboard devices found in /dev/input.");
+            info!("Add your user to the 'input' group to use the evdev backend.");
+            info!("Falling back to polling.");
+            platform_common::start_key_monitoring(statistics, config)
+        }
+        Err(e) => {
+            warn!("Failed to enumerate input devices: {e}");
+            info!("Falling back to polling.");
+            platform_common::start_key_monitoring(statistics, config)
+        }
+    }
 }
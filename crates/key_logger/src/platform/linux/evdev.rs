@@ -0,0 +1,642 @@
+//! Event-driven capture backend reading `input_event` records from `/dev/input/event*`.
+//!
+//! Unlike the polling backend, the kernel queues every transition for us, so fast taps and
+//! rollover are never lost and no display server is required. Reading the device nodes
+//! requires root or membership in the `input` group.
+
+use crate::{
+    devices,
+    error::Result,
+    platform_common::POLLING_INTERVAL,
+    source::{KeyEvent, KeySource},
+};
+use key_id::{ArrowKey, KeyId, LetterKey, SymbolKey};
+use log::{debug, info, warn};
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+const INPUT_DIR: &str = "/dev/input";
+const SYSFS_INPUT_DIR: &str = "/sys/class/input";
+
+// `struct input_event` is a `struct timeval` followed by type (u16), code (u16) and value (i32).
+// `timeval` holds two C longs, so the record size depends on the pointer width.
+#[cfg(target_pointer_width = "64")]
+const TIMEVAL_FIELD_SIZE: usize = 8;
+#[cfg(not(target_pointer_width = "64"))]
+const TIMEVAL_FIELD_SIZE: usize = 4;
+
+pub(crate) const INPUT_EVENT_SIZE: usize = TIMEVAL_FIELD_SIZE * 2 + 8;
+
+// Event types and values from linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const SYN_DROPPED: u16 = 3;
+const EV_REP: u16 = 0x14;
+const KEY_RELEASE: i32 = 0;
+const KEY_PRESS: i32 = 1;
+const KEY_REPEAT: i32 = 2;
+
+/// A decoded `struct input_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InputEvent {
+    /// Kernel timestamp (CLOCK_REALTIME unless changed with EVIOCSCLOCKID)
+    pub time: Duration,
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+/// What a key event means for the statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyAction {
+    Press,
+    Release,
+    Repeat,
+}
+
+impl InputEvent {
+    pub(crate) fn from_bytes(buf: &[u8; INPUT_EVENT_SIZE]) -> Self {
+        let (sec, usec) = buf[..TIMEVAL_FIELD_SIZE * 2].split_at(TIMEVAL_FIELD_SIZE);
+        let rest = &buf[TIMEVAL_FIELD_SIZE * 2..];
+
+        let sec = read_c_long(sec).max(0) as u64;
+        let usec = read_c_long(usec).clamp(0, 999_999) as u32;
+
+        Self {
+            time: Duration::new(sec, usec * 1_000),
+            kind: u16::from_ne_bytes([rest[0], rest[1]]),
+            code: u16::from_ne_bytes([rest[2], rest[3]]),
+            value: i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn to_bytes(self) -> [u8; INPUT_EVENT_SIZE] {
+        let mut buf = [0u8; INPUT_EVENT_SIZE];
+        let (sec, usec) = buf[..TIMEVAL_FIELD_SIZE * 2].split_at_mut(TIMEVAL_FIELD_SIZE);
+        write_c_long(sec, self.time.as_secs() as i64);
+        write_c_long(usec, i64::from(self.time.subsec_micros()));
+
+        let rest = &mut buf[TIMEVAL_FIELD_SIZE * 2..];
+        rest[0..2].copy_from_slice(&self.kind.to_ne_bytes());
+        rest[2..4].copy_from_slice(&self.code.to_ne_bytes());
+        rest[4..8].copy_from_slice(&self.value.to_ne_bytes());
+        buf
+    }
+
+    /// Interprets this event as a key transition, ignoring non-key events and unmapped codes.
+    pub(crate) fn key_transition(&self) -> Option<(KeyId, KeyAction)> {
+        if self.kind != EV_KEY {
+            return None;
+        }
+        let action = match self.value {
+            KEY_PRESS => KeyAction::Press,
+            KEY_RELEASE => KeyAction::Release,
+            KEY_REPEAT => KeyAction::Repeat,
+            _ => return None,
+        };
+        linux_keycode_to_key_id(self.code).map(|key| (key, action))
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+fn read_c_long(bytes: &[u8]) -> i64 {
+    i64::from_ne_bytes(bytes.try_into().expect("8-byte timeval field"))
+}
+
+#[cfg(not(target_pointer_width = "64"))]
+fn read_c_long(bytes: &[u8]) -> i64 {
+    i64::from(i32::from_ne_bytes(
+        bytes.try_into().expect("4-byte timeval field"),
+    ))
+}
+
+#[cfg(all(test, target_pointer_width = "64"))]
+fn write_c_long(out: &mut [u8], v: i64) {
+    out.copy_from_slice(&v.to_ne_bytes());
+}
+
+#[cfg(all(test, not(target_pointer_width = "64")))]
+fn write_c_long(out: &mut [u8], v: i64) {
+    out.copy_from_slice(&(v as i32).to_ne_bytes());
+}
+
+/// Reads one event from the stream, returning `None` on a clean end of stream.
+pub(crate) fn read_event<R: Read>(reader: &mut R) -> io::Result<Option<InputEvent>> {
+    let mut buf = [0u8; INPUT_EVENT_SIZE];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(InputEvent::from_bytes(&buf))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Forwards every key transition in the stream to `tx`, attributed to `device`, until the
+/// stream ends or the receiver is gone. Autorepeat events are forwarded as repeats, so a held
+/// key is still a single press.
+///
+/// When the kernel drops events (`SYN_DROPPED`) or the device goes away, releases may have
+/// been lost, so every key still believed held is reported as lost. Keys that are in fact
+/// still down come back through their autorepeats.
+fn forward_key_events<R: Read>(
+    mut reader: R,
+    device: &'static str,
+    tx: &Sender<KeyEvent>,
+) -> io::Result<()> {
+    let mut held = HashSet::new();
+    let mut last_time = Duration::ZERO;
+    let send_lost = |held: &mut HashSet<KeyId>, time| {
+        held.drain()
+            .all(|key| tx.send(KeyEvent::lost(key, time).on_device(device)).is_ok())
+    };
+
+    let mut forward = || -> io::Result<()> {
+        // Everything up to the next SYN_REPORT after SYN_DROPPED is incomplete
+        let mut dropping = false;
+        while let Some(event) = read_event(&mut reader)? {
+            last_time = event.time;
+            if event.kind == EV_SYN {
+                match event.code {
+                    SYN_DROPPED => {
+                        warn!("{device} dropped events; forgetting its held keys");
+                        dropping = true;
+                        if !send_lost(&mut held, event.time) {
+                            break;
+                        }
+                    }
+                    SYN_REPORT => dropping = false,
+                    _ => {}
+                }
+                continue;
+            }
+            if dropping {
+                continue;
+            }
+            let key_event = match event.key_transition() {
+                Some((key, KeyAction::Press)) => {
+                    held.insert(key);
+                    KeyEvent::press(key, event.time)
+                }
+                Some((key, KeyAction::Release)) => {
+                    held.remove(&key);
+                    KeyEvent::release(key, event.time)
+                }
+                Some((key, KeyAction::Repeat)) => {
+                    held.insert(key);
+                    KeyEvent::repeat(key, event.time)
+                }
+                None => continue,
+            };
+            if tx.send(key_event.on_device(device)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    };
+    let result = forward();
+
+    // An unplugged keyboard never sends the releases of the keys held on it
+    send_lost(&mut held, last_time);
+    result
+}
+
+/// A keyboard event device that we managed to open.
+pub(crate) struct KeyboardDevice {
+    pub path: PathBuf,
+    pub name: String,
+    file: File,
+}
+
+/// Opens every readable `/dev/input/event*` node that advertises keyboard capabilities.
+pub(crate) fn open_keyboards() -> Result<Vec<KeyboardDevice>> {
+    let entries = fs::read_dir(INPUT_DIR)?;
+    let mut devices = Vec::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(node) = file_name.to_str() else {
+            continue;
+        };
+        if !node.starts_with("event") {
+            continue;
+        }
+
+        let sysfs = Path::new(SYSFS_INPUT_DIR).join(node).join("device");
+        if !is_keyboard(&sysfs) {
+            continue;
+        }
+
+        let name = fs::read_to_string(sysfs.join("name"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| node.to_string());
+        let path = entry.path();
+
+        match File::open(&path) {
+            Ok(file) => {
+                debug!("Opened keyboard device {} ({name})", path.display());
+                devices.push(KeyboardDevice { path, name, file });
+            }
+            Err(e) => warn!("Cannot open {} ({name}): {e}", path.display()),
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Keyboards report both key and autorepeat events; this excludes power buttons,
+/// lid switches and most mice while keeping keyboards and keyboard-like macro pads.
+fn is_keyboard(sysfs_device: &Path) -> bool {
+    let Ok(caps) = fs::read_to_string(sysfs_device.join("capabilities/ev")) else {
+        return false;
+    };
+    let Ok(bits) = u64::from_str_radix(caps.trim(), 16) else {
+        return false;
+    };
+    bits & (1 << EV_KEY) != 0 && bits & (1 << EV_REP) != 0
+}
+
+/// Merges the event streams of all opened keyboards.
+///
+/// Each device is read by a blocking reader thread; the capture loop drains the shared channel.
+pub(crate) struct EvdevSource {
+    rx: Receiver<KeyEvent>,
+}
+
+impl EvdevSource {
+    pub(crate) fn new(devices: Vec<KeyboardDevice>) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        for device in devices {
+            info!("Listening on {} ({})", device.path.display(), device.name);
+            // Keyed by name, which unlike the event node number survives reboots and replugging
+            let name = devices::intern_device_name(&device.name);
+            let tx = tx.clone();
+            thread::Builder::new()
+                .name(format!("evdev-{}", device.path.display()))
+                .spawn(move || {
+                    if let Err(e) = forward_key_events(device.file, name, &tx) {
+                        warn!("Stopped reading {}: {e}", device.path.display());
+                    }
+                })?;
+        }
+        // Only the reader threads hold senders now, so the channel disconnects when all devices are gone
+        Ok(Self { rx })
+    }
+}
+
+impl KeySource for EvdevSource {
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Result<bool> {
+        match self.rx.recv_timeout(POLLING_INTERVAL) {
+            Ok(event) => {
+                events.push(event);
+                events.extend(self.rx.try_iter());
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(true),
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!("All keyboard devices were closed");
+                Ok(false)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+}
+
+/// Converts a Linux `KEY_*` code into the same keys used by the polling backend.
+fn linux_keycode_to_key_id(code: u16) -> Option<KeyId> {
+    use KeyId::*;
+    let key = match code {
+        1 => Escape,
+        2 => Digit(1),
+        3 => Digit(2),
+        4 => Digit(3),
+        5 => Digit(4),
+        6 => Digit(5),
+        7 => Digit(6),
+        8 => Digit(7),
+        9 => Digit(8),
+        10 => Digit(9),
+        11 => Digit(0),
+        12 => Symbol(SymbolKey::Minus),
+        13 => Symbol(SymbolKey::Equal),
+        14 => Backspace,
+        15 => Tab,
+        16 => Letter(LetterKey::Q),
+        17 => Letter(LetterKey::W),
+        18 => Letter(LetterKey::E),
+        19 => Letter(LetterKey::R),
+        20 => Letter(LetterKey::T),
+        21 => Letter(LetterKey::Y),
+        22 => Letter(LetterKey::U),
+        23 => Letter(LetterKey::I),
+        24 => Letter(LetterKey::O),
+        25 => Letter(LetterKey::P),
+        26 => Symbol(SymbolKey::LBracket),
+        27 => Symbol(SymbolKey::RBracket),
+        28 => Enter,
+        29 => CtrlL,
+        30 => Letter(LetterKey::A),
+        31 => Letter(LetterKey::S),
+        32 => Letter(LetterKey::D),
+        33 => Letter(LetterKey::F),
+        34 => Letter(LetterKey::G),
+        35 => Letter(LetterKey::H),
+        36 => Letter(LetterKey::J),
+        37 => Letter(LetterKey::K),
+        38 => Letter(LetterKey::L),
+        39 => Symbol(SymbolKey::Semicolon),
+        40 => Symbol(SymbolKey::Quote),
+        41 => Symbol(SymbolKey::Backtick),
+        42 => ShiftL,
+        43 => Symbol(SymbolKey::Backslash),
+        44 => Letter(LetterKey::Z),
+        45 => Letter(LetterKey::X),
+        46 => Letter(LetterKey::C),
+        47 => Letter(LetterKey::V),
+        48 => Letter(LetterKey::B),
+        49 => Letter(LetterKey::N),
+        50 => Letter(LetterKey::M),
+        51 => Symbol(SymbolKey::Comma),
+        52 => Symbol(SymbolKey::Period),
+        53 => Symbol(SymbolKey::Slash),
+        54 => ShiftR,
+        55 => NumpadMultiply,
+        56 => AltL,
+        57 => Space,
+        58 => CapsLock,
+        59 => Function(1),
+        60 => Function(2),
+        61 => Function(3),
+        62 => Function(4),
+        63 => Function(5),
+        64 => Function(6),
+        65 => Function(7),
+        66 => Function(8),
+        67 => Function(9),
+        68 => Function(10),
+        71 => NumpadDigit(7),
+        72 => NumpadDigit(8),
+        73 => NumpadDigit(9),
+        74 => NumpadSubtract,
+        75 => NumpadDigit(4),
+        76 => NumpadDigit(5),
+        77 => NumpadDigit(6),
+        78 => NumpadAdd,
+        79 => NumpadDigit(1),
+        80 => NumpadDigit(2),
+        81 => NumpadDigit(3),
+        82 => NumpadDigit(0),
+        83 => NumpadDecimal,
+        // 半角/全角 sits where Grave is on US boards
+        85 => Symbol(SymbolKey::Backtick),
+        86 => Symbol(SymbolKey::IntlBackslash),
+        87 => Function(11),
+        88 => Function(12),
+        89 => Symbol(SymbolKey::Ro),
+        90 | 91 | 93 => KatakanaHiragana,
+        92 => Henkan,
+        94 => Muhenkan,
+        96 => NumpadEnter,
+        97 => CtrlR,
+        98 => NumpadDivide,
+        100 => AltR,
+        102 => Home,
+        103 => Arrow(ArrowKey::Up),
+        104 => PageUp,
+        105 => Arrow(ArrowKey::Left),
+        106 => Arrow(ArrowKey::Right),
+        107 => End,
+        108 => Arrow(ArrowKey::Down),
+        109 => PageDown,
+        110 => Insert,
+        111 => Delete,
+        117 => NumpadEquals,
+        124 => Symbol(SymbolKey::Yen),
+        125 => MetaL,
+        126 => MetaR,
+        183 => Function(13),
+        184 => Function(14),
+        185 => Function(15),
+        186 => Function(16),
+        187 => Function(17),
+        188 => Function(18),
+        189 => Function(19),
+        190 => Function(20),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        platform_common,
+        recorder::{Recorder, Snapshot, Statistics},
+        stats,
+    };
+    use std::io::Cursor;
+
+    const KEY_A: u16 = 30;
+    const KEY_LEFTSHIFT: u16 = 42;
+    const KEY_SPACE: u16 = 57;
+    const BTN_LEFT: u16 = 0x110;
+    const EV_MSC: u16 = 0x04;
+    const MSC_SCAN: u16 = 0x04;
+
+    fn key(ms: u64, code: u16, value: i32) -> InputEvent {
+        InputEvent {
+            time: Duration::from_millis(ms),
+            kind: EV_KEY,
+            code,
+            value,
+        }
+    }
+
+    fn other(ms: u64, kind: u16, code: u16, value: i32) -> InputEvent {
+        InputEvent {
+            time: Duration::from_millis(ms),
+            kind,
+            code,
+            value,
+        }
+    }
+
+    /// Serializes events the way the kernel delivers them from a device node.
+    fn record(events: &[InputEvent]) -> Vec<u8> {
+        events.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    const TEST_DEVICE: &str = "Test Keyboard";
+
+    /// Replays a recorded stream through the same path the device threads use.
+    fn replay(bytes: Vec<u8>) -> Snapshot {
+        let (tx, rx) = mpsc::channel();
+        forward_key_events(Cursor::new(bytes), TEST_DEVICE, &tx).unwrap();
+        drop(tx);
+
+        let statistics = Statistics::new().with_devices();
+        let mut recorder = Recorder::new(statistics.clone());
+        platform_common::run_capture_loop(&mut EvdevSource { rx }, &mut recorder).unwrap();
+        statistics.snapshot().unwrap()
+    }
+
+    #[test]
+    fn test_event_roundtrip() {
+        let event = InputEvent {
+            time: Duration::new(1_700_000_000, 123_456_000),
+            kind: EV_KEY,
+            code: KEY_A,
+            value: KEY_PRESS,
+        };
+        assert_eq!(InputEvent::from_bytes(&event.to_bytes()), event);
+    }
+
+    #[test]
+    fn test_read_event_end_of_stream() {
+        let mut empty = Cursor::new(Vec::new());
+        assert_eq!(read_event(&mut empty).unwrap(), None);
+
+        // A truncated trailing record is treated as end of stream
+        let mut bytes = record(&[key(0, KEY_A, KEY_PRESS)]);
+        bytes.extend_from_slice(&[0u8; 3]);
+        let mut cursor = Cursor::new(bytes);
+        assert!(read_event(&mut cursor).unwrap().is_some());
+        assert_eq!(read_event(&mut cursor).unwrap(), None);
+    }
+
+    #[test]
+    fn test_key_transition() {
+        assert_eq!(
+            key(0, KEY_A, KEY_PRESS).key_transition(),
+            Some((KeyId::Letter(LetterKey::A), KeyAction::Press))
+        );
+        assert_eq!(
+            key(0, KEY_A, KEY_RELEASE).key_transition(),
+            Some((KeyId::Letter(LetterKey::A), KeyAction::Release))
+        );
+        assert_eq!(
+            key(0, KEY_A, KEY_REPEAT).key_transition(),
+            Some((KeyId::Letter(LetterKey::A), KeyAction::Repeat))
+        );
+        assert_eq!(key(0, BTN_LEFT, KEY_PRESS).key_transition(), None);
+        assert_eq!(other(0, EV_MSC, MSC_SCAN, 30).key_transition(), None);
+    }
+
+    #[test]
+    fn test_replay_counts_repeats_separately() {
+        // Typical kernel output: MSC_SCAN + EV_KEY + SYN_REPORT per transition,
+        // with autorepeat while A is held.
+        let bytes = record(&[
+            other(0, EV_MSC, MSC_SCAN, 0x70004),
+            key(0, KEY_A, KEY_PRESS),
+            other(0, EV_SYN, 0, 0),
+            key(500, KEY_A, KEY_REPEAT),
+            key(533, KEY_A, KEY_REPEAT),
+            other(533, EV_SYN, 0, 0),
+            key(600, KEY_A, KEY_RELEASE),
+            other(600, EV_SYN, 0, 0),
+        ]);
+
+        let snapshot = replay(bytes);
+        let keys = snapshot.keys.unwrap();
+        assert_eq!(keys.get("A"), Some(&1));
+        assert_eq!(keys.len(), 1);
+        assert_eq!(snapshot.repeats.unwrap().get("A"), Some(&2));
+
+        let devices = snapshot.devices.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[&(TEST_DEVICE, "A")].presses, 1);
+        assert_eq!(devices[&(TEST_DEVICE, "A")].repeats, 2);
+    }
+
+    #[test]
+    fn test_replay_fast_taps_and_rollover() {
+        // Taps shorter than one polling interval and overlapping presses are all counted
+        let bytes = record(&[
+            key(0, KEY_A, KEY_PRESS),
+            key(1, KEY_A, KEY_RELEASE),
+            key(2, KEY_A, KEY_PRESS),
+            key(3, KEY_A, KEY_RELEASE),
+            key(10, KEY_LEFTSHIFT, KEY_PRESS),
+            key(11, KEY_SPACE, KEY_PRESS),
+            key(12, KEY_A, KEY_PRESS),
+            key(13, KEY_SPACE, KEY_RELEASE),
+            key(14, KEY_A, KEY_RELEASE),
+            key(15, KEY_LEFTSHIFT, KEY_RELEASE),
+            key(16, BTN_LEFT, KEY_PRESS),
+        ]);
+
+        let snapshot = replay(bytes).keys.unwrap();
+        assert_eq!(snapshot.get("A"), Some(&3));
+        assert_eq!(snapshot.get("LeftShift"), Some(&1));
+        assert_eq!(snapshot.get("Space"), Some(&1));
+        assert_eq!(snapshot.len(), 3);
+    }
+
+    #[test]
+    fn test_dropped_events_forget_held_keys() {
+        let bytes = record(&[
+            key(0, KEY_A, KEY_PRESS),
+            other(0, EV_SYN, SYN_REPORT, 0),
+            other(50, EV_SYN, SYN_DROPPED, 0),
+            // Incomplete until the next report, so not counted
+            key(60, KEY_SPACE, KEY_PRESS),
+            other(60, EV_SYN, SYN_REPORT, 0),
+            // The release of A was among the dropped events
+            key(100, KEY_A, KEY_PRESS),
+            other(100, EV_SYN, SYN_REPORT, 0),
+        ]);
+
+        let snapshot = replay(bytes);
+        let keys = snapshot.keys.unwrap();
+        assert_eq!(keys.get("A"), Some(&2));
+        assert_eq!(keys.len(), 1);
+        assert!(snapshot.repeats.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_device_loss_reports_held_keys_lost() {
+        let (tx, rx) = mpsc::channel();
+        let bytes = record(&[
+            key(0, KEY_LEFTSHIFT, KEY_PRESS),
+            key(10, KEY_A, KEY_PRESS),
+            key(20, KEY_A, KEY_RELEASE),
+        ]);
+        forward_key_events(Cursor::new(bytes), TEST_DEVICE, &tx).unwrap();
+        drop(tx);
+
+        let events: Vec<KeyEvent> = rx.iter().collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[3],
+            KeyEvent::lost(KeyId::ShiftL, Duration::from_millis(20)).on_device(TEST_DEVICE)
+        );
+    }
+
+    #[test]
+    fn test_keycode_labels_are_known() {
+        // Every mapped code must use a label the rest of the pipeline understands
+        for code in 0..=u16::MAX {
+            if let Some(key) = linux_keycode_to_key_id(code) {
+                let label = key.label();
+                assert!(
+                    stats::intern_key_label(label).is_some(),
+                    "evdev code {code} maps to unknown label '{label}'"
+                );
+                assert_eq!(KeyId::from_label(label), Some(key));
+            }
+        }
+    }
+}
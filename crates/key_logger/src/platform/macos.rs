@@ -13,7 +13,10 @@ fn is_accessibility_trusted() -> bool {
     unsafe { AXIsProcessTrusted() != 0 }
 }
 
-pub(crate) fn start_key_monitoring(stats: crate::stats::KeyStatistics) -> Result<()> {
+pub(crate) fn start_key_monitoring(
+    statistics: crate::recorder::Statistics,
+    config: &crate::config::Config,
+) -> Result<&'static str> {
     // Check accessibility permissions (informational only)
     let is_trusted = is_accessibility_trusted();
     if !is_trusted {
@@ -25,5 +28,5 @@ pub(crate) fn start_key_monitoring(stats: crate::stats::KeyStatistics) -> Result
     }
 
     // Use the common implementation
-    platform_common::start_key_monitoring(stats)
+    platform_common::start_key_monitoring(statistics, config)
 }
@@ -1,5 +1,8 @@
 use crate::{error::Result, platform_common};
 
-pub(crate) fn start_key_monitoring(stats: crate::stats::KeyStatistics) -> Result<()> {
-    platform_common::start_key_monitoring(stats)
+pub(crate) fn start_key_monitoring(
+    statistics: crate::recorder::Statistics,
+    config: &crate::config::Config,
+) -> Result<&'static str> {
+    platform_common::start_key_monitoring(statistics, config)
 }
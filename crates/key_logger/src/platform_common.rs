@@ -1,44 +1,72 @@
-use crate::{error::Result, stats};
+use crate::{
+    config::Config,
+    error::Result,
+    recorder::{Recorder, Statistics},
+    source::{KeyEvent, KeySource},
+};
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 use device_query::{DeviceQuery, DeviceState, Keycode};
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+use key_id::{ArrowKey, KeyId, LetterKey, SymbolKey};
 use log::{debug, error};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     sync::{
         Mutex, OnceLock,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 // Global flag for graceful shutdown
 static SHOULD_EXIT: AtomicBool = AtomicBool::new(false);
+// Toggled by SIGUSR1; nothing is recorded while set
+static PAUSED: AtomicBool = AtomicBool::new(false);
+// Set by SIGUSR2 and consumed by the flush thread
+static FLUSH_REQUESTED: AtomicBool = AtomicBool::new(false);
+static ACTIVE_BACKEND: OnceLock<&'static str> = OnceLock::new();
+// Iterations of the capture loop, for the systemd watchdog
+static CAPTURE_TICKS: AtomicU64 = AtomicU64::new(0);
 static EXIT_HANDLER_STATE: OnceLock<Mutex<bool>> = OnceLock::new();
 
-const POLLING_INTERVAL: Duration = Duration::from_millis(10);
+/// Default polling interval, also used as the exit-check period of event-driven backends
+pub(crate) const POLLING_INTERVAL: Duration = Duration::from_millis(10);
 
 fn inner_setup() -> Result<()> {
     #[cfg(unix)]
     {
         use signal_hook::{
-            consts::{SIGHUP, SIGINT, SIGTERM},
+            consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2},
             iterator::Signals,
         };
         use std::thread::Builder;
 
-        let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP]).map_err(|e| {
-            crate::error::KeyLoggerError::SignalHandling {
-                source: Box::new(e),
-            }
-        })?;
+        let mut signals =
+            Signals::new([SIGINT, SIGTERM, SIGHUP, SIGUSR1, SIGUSR2]).map_err(|e| {
+                crate::error::KeyLoggerError::SignalHandling {
+                    source: Box::new(e),
+                }
+            })?;
 
         Builder::new()
             .name("signal-listener".into())
             .spawn(move || {
-                if let Some(sig) = signals.forever().next() {
-                    log::info!("Received signal: {sig}");
-                    SHOULD_EXIT.store(true, Ordering::Relaxed);
+                for sig in signals.forever() {
+                    match sig {
+                        SIGUSR1 => {
+                            toggle_pause();
+                        }
+                        SIGUSR2 => {
+                            log::info!("Received SIGUSR2, flushing statistics");
+                            request_flush();
+                        }
+                        _ => {
+                            log::info!("Received signal: {sig}");
+                            SHOULD_EXIT.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
                 }
             })
             .expect("spawn signal-listener thread");
@@ -72,17 +100,74 @@ pub fn should_exit() -> bool {
     SHOULD_EXIT.load(Ordering::Relaxed)
 }
 
+/// Whether recording is paused; key events are still read but dropped.
+#[inline]
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Pauses or resumes recording and returns whether it is now paused.
+#[cfg(unix)]
+pub fn toggle_pause() -> bool {
+    let paused = !PAUSED.fetch_xor(true, Ordering::Relaxed);
+    if paused {
+        log::info!("Recording paused; send SIGUSR1 again to resume");
+    } else {
+        log::info!("Recording resumed");
+    }
+    paused
+}
+
+/// Asks the flush thread to export and reset the statistics without stopping.
+#[cfg(unix)]
+pub fn request_flush() {
+    FLUSH_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Returns whether a flush was requested since the last call.
+pub fn take_flush_request() -> bool {
+    FLUSH_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Name of the key source being captured from, once capture has started.
+pub fn active_backend() -> Option<&'static str> {
+    ACTIVE_BACKEND.get().copied()
+}
+
+/// How many times the capture loop has polled its source; stops advancing if it hangs.
+#[cfg(unix)]
+pub fn capture_ticks() -> u64 {
+    CAPTURE_TICKS.load(Ordering::Relaxed)
+}
+
+/// Asks every background thread to stop, as if an exit signal had been received.
+pub fn request_exit() {
+    SHOULD_EXIT.store(true, Ordering::Relaxed);
+}
+
+/// Captures with the polling backend; returns the backend name on success.
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-pub(crate) fn start_key_monitoring(stats: stats::KeyStatistics) -> Result<()> {
-    debug!("Starting keyboard monitoring...");
-    debug!("Press keys on your keyboard - they will be counted");
+pub(crate) fn start_key_monitoring(
+    statistics: Statistics,
+    config: &Config,
+) -> Result<&'static str> {
+    let mut recorder = Recorder::new(statistics).filtering(config.key_filter());
+    let mut source = PollingSource::new(config.polling_interval);
+    run_capture_loop(&mut source, &mut recorder)?;
+    Ok(source.name())
+}
 
-    // Initialize device state for keyboard polling
-    let device_state = DeviceState::new();
-    let mut prev_keys: HashSet<Keycode> = HashSet::with_capacity(16);
-    let mut curr_keys: HashSet<Keycode> = HashSet::with_capacity(16);
+/// Feeds events from `source` into `recorder` until exit is requested or the source is exhausted.
+pub(crate) fn run_capture_loop<S: KeySource + ?Sized>(
+    source: &mut S,
+    recorder: &mut Recorder,
+) -> Result<()> {
+    debug!("Starting keyboard monitoring ({})...", source.name());
+    let _ = ACTIVE_BACKEND.set(source.name());
+    debug!("Press keys on your keyboard - they will be counted");
 
-    let mut buf: Vec<&'static str> = Vec::with_capacity(16);
+    let mut events: Vec<KeyEvent> = Vec::with_capacity(16);
+    let mut paused = false;
 
     loop {
         // Check if we should exit
@@ -90,140 +175,461 @@ pub(crate) fn start_key_monitoring(stats: stats::KeyStatistics) -> Result<()> {
             break;
         }
 
-        curr_keys.clear();
-        curr_keys.extend(device_state.get_keys());
+        CAPTURE_TICKS.fetch_add(1, Ordering::Relaxed);
+        events.clear();
+        // Keep draining the source while paused so nothing typed meanwhile shows up later
+        let more = source.poll_events(&mut events)?;
 
-        // Process newly pressed keys
-        buf.clear();
-        for &keycode in curr_keys.difference(&prev_keys) {
-            buf.push(keycode_to_str(keycode));
+        if is_paused() {
+            paused = true;
+        } else {
+            if paused {
+                // Keys held across the pause must not produce holds, pairs or chords
+                recorder.reset_state();
+                paused = false;
+            }
+            if let Err(e) = recorder.record(&events) {
+                error!("Failed to record keys: {e}");
+            }
         }
-        if let Err(e) = stats::add_many(&stats, buf.iter().copied()) {
-            error!("Failed to record keys: {e}");
+
+        if !more {
+            debug!("Key source exhausted");
+            break;
         }
+    }
 
-        // Update previous state
-        std::mem::swap(&mut prev_keys, &mut curr_keys);
+    debug!("Keyboard monitoring stopped");
+    Ok(())
+}
+
+/// Polls `device_query` and reports the difference between consecutive key sets.
+///
+/// Transitions are only observed at polling granularity, so taps shorter than
+/// the polling interval can be missed, and so can a key released and pressed again
+/// within one poll. `device_query` never reports OS auto-repeat.
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub(crate) struct PollingSource {
+    device_state: DeviceState,
+    interval: Duration,
+    tracker: KeySetTracker,
+    curr_keys: HashSet<Keycode>,
+    started: Instant,
+}
+
+/// Turns successive polled key sets into events.
+///
+/// A stuck key can vanish from a single poll and come back, which a plain set difference
+/// would report as a release and a second press. Releases are therefore held back for one
+/// poll: a key that is back by the next poll was never released and produces no events,
+/// so polling never reports a `Repeat`.
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+#[derive(Debug, Default)]
+struct KeySetTracker {
+    prev_keys: HashSet<Keycode>,
+    /// Keys missing from the previous poll since the given time, with no release reported yet
+    released: HashMap<Keycode, Duration>,
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl KeySetTracker {
+    /// Compares `keys` with the previous poll; `keys` is left holding the previous set.
+    fn update(&mut self, keys: &mut HashSet<Keycode>, now: Duration, events: &mut Vec<KeyEvent>) {
+        // Still gone after a second poll: a real release, reported at the time it was
+        // first missed. Back again: a glitch, the key stays held.
+        for (keycode, since) in self.released.drain() {
+            if keys.contains(&keycode) {
+                self.prev_keys.insert(keycode);
+            } else {
+                events.push(KeyEvent::release(keycode_to_key_id(keycode), since));
+            }
+        }
 
+        for &keycode in keys.difference(&self.prev_keys) {
+            events.push(KeyEvent::press(keycode_to_key_id(keycode), now));
+        }
+        for &keycode in self.prev_keys.difference(keys) {
+            self.released.insert(keycode, now);
+        }
+
+        std::mem::swap(&mut self.prev_keys, keys);
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl PollingSource {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            // Initialize device state for keyboard polling
+            device_state: DeviceState::new(),
+            interval,
+            tracker: KeySetTracker::default(),
+            curr_keys: HashSet::with_capacity(16),
+            started: Instant::now(),
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl KeySource for PollingSource {
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Result<bool> {
         // Small delay to avoid excessive CPU usage
-        thread::sleep(POLLING_INTERVAL);
+        thread::sleep(self.interval);
+
+        self.curr_keys.clear();
+        self.curr_keys.extend(self.device_state.get_keys());
+        let now = self.started.elapsed();
+
+        self.tracker.update(&mut self.curr_keys, now, events);
+        Ok(true)
     }
 
-    debug!("Keyboard monitoring stopped");
-    Ok(())
+    fn name(&self) -> &'static str {
+        "polling"
+    }
 }
 
-// Convert Keycode to human-readable string
 #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
-fn keycode_to_str(k: Keycode) -> &'static str {
+fn keycode_to_key_id(k: Keycode) -> KeyId {
+    use KeyId::*;
     match k {
-        Keycode::A => "A",
-        Keycode::B => "B",
-        Keycode::C => "C",
-        Keycode::D => "D",
-        Keycode::E => "E",
-        Keycode::F => "F",
-        Keycode::G => "G",
-        Keycode::H => "H",
-        Keycode::I => "I",
-        Keycode::J => "J",
-        Keycode::K => "K",
-        Keycode::L => "L",
-        Keycode::M => "M",
-        Keycode::N => "N",
-        Keycode::O => "O",
-        Keycode::P => "P",
-        Keycode::Q => "Q",
-        Keycode::R => "R",
-        Keycode::S => "S",
-        Keycode::T => "T",
-        Keycode::U => "U",
-        Keycode::V => "V",
-        Keycode::W => "W",
-        Keycode::X => "X",
-        Keycode::Y => "Y",
-        Keycode::Z => "Z",
-        Keycode::Key0 => "0",
-        Keycode::Key1 => "1",
-        Keycode::Key2 => "2",
-        Keycode::Key3 => "3",
-        Keycode::Key4 => "4",
-        Keycode::Key5 => "5",
-        Keycode::Key6 => "6",
-        Keycode::Key7 => "7",
-        Keycode::Key8 => "8",
-        Keycode::Key9 => "9",
-        Keycode::Space => "Space",
-        Keycode::Enter => "Enter",
-        Keycode::Tab => "Tab",
-        Keycode::Backspace => "Backspace",
-        Keycode::Delete => "Delete",
-        Keycode::Escape => "Escape",
-        Keycode::LShift => "LeftShift",
-        Keycode::RShift => "RightShift",
-        Keycode::LControl => "LeftControl",
-        Keycode::RControl => "RightControl",
-        Keycode::LAlt | Keycode::LOption => "LeftAlt",
-        Keycode::RAlt | Keycode::ROption => "RightAlt",
-        Keycode::LMeta | Keycode::Command => "LeftMeta",
-        Keycode::RMeta | Keycode::RCommand => "RightMeta",
-        Keycode::Up => "ArrowUp",
-        Keycode::Down => "ArrowDown",
-        Keycode::Left => "ArrowLeft",
-        Keycode::Right => "ArrowRight",
-        Keycode::Comma => "Comma",
-        Keycode::Dot => "Period",
-        Keycode::Semicolon => "Semicolon",
-        Keycode::Apostrophe => "Apostrophe",
-        Keycode::Grave => "Grave",
-        Keycode::Minus => "Minus",
-        Keycode::Equal => "Equal",
-        Keycode::LeftBracket => "LeftBracket",
-        Keycode::RightBracket => "RightBracket",
-        Keycode::BackSlash => "Backslash",
-        Keycode::Slash => "Slash",
-        Keycode::F1 => "F1",
-        Keycode::F2 => "F2",
-        Keycode::F3 => "F3",
-        Keycode::F4 => "F4",
-        Keycode::F5 => "F5",
-        Keycode::F6 => "F6",
-        Keycode::F7 => "F7",
-        Keycode::F8 => "F8",
-        Keycode::F9 => "F9",
-        Keycode::F10 => "F10",
-        Keycode::F11 => "F11",
-        Keycode::F12 => "F12",
-        Keycode::F13 => "F13",
-        Keycode::F14 => "F14",
-        Keycode::F15 => "F15",
-        Keycode::F16 => "F16",
-        Keycode::F17 => "F17",
-        Keycode::F18 => "F18",
-        Keycode::F19 => "F19",
-        Keycode::F20 => "F20",
-        Keycode::Home => "Home",
-        Keycode::End => "End",
-        Keycode::PageUp => "PageUp",
-        Keycode::PageDown => "PageDown",
-        Keycode::Insert => "Insert",
-        Keycode::CapsLock => "CapsLock",
-        Keycode::Numpad0 => "Numpad0",
-        Keycode::Numpad1 => "Numpad1",
-        Keycode::Numpad2 => "Numpad2",
-        Keycode::Numpad3 => "Numpad3",
-        Keycode::Numpad4 => "Numpad4",
-        Keycode::Numpad5 => "Numpad5",
-        Keycode::Numpad6 => "Numpad6",
-        Keycode::Numpad7 => "Numpad7",
-        Keycode::Numpad8 => "Numpad8",
-        Keycode::Numpad9 => "Numpad9",
-        Keycode::NumpadAdd => "NumpadAdd",
-        Keycode::NumpadSubtract => "NumpadSubtract",
-        Keycode::NumpadMultiply => "NumpadMultiply",
-        Keycode::NumpadDivide => "NumpadDivide",
-        Keycode::NumpadEnter => "NumpadEnter",
-        Keycode::NumpadEquals => "NumpadEquals",
-        Keycode::NumpadDecimal => "NumpadDecimal",
+        Keycode::A => Letter(LetterKey::A),
+        Keycode::B => Letter(LetterKey::B),
+        Keycode::C => Letter(LetterKey::C),
+        Keycode::D => Letter(LetterKey::D),
+        Keycode::E => Letter(LetterKey::E),
+        Keycode::F => Letter(LetterKey::F),
+        Keycode::G => Letter(LetterKey::G),
+        Keycode::H => Letter(LetterKey::H),
+        Keycode::I => Letter(LetterKey::I),
+        Keycode::J => Letter(LetterKey::J),
+        Keycode::K => Letter(LetterKey::K),
+        Keycode::L => Letter(LetterKey::L),
+        Keycode::M => Letter(LetterKey::M),
+        Keycode::N => Letter(LetterKey::N),
+        Keycode::O => Letter(LetterKey::O),
+        Keycode::P => Letter(LetterKey::P),
+        Keycode::Q => Letter(LetterKey::Q),
+        Keycode::R => Letter(LetterKey::R),
+        Keycode::S => Letter(LetterKey::S),
+        Keycode::T => Letter(LetterKey::T),
+        Keycode::U => Letter(LetterKey::U),
+        Keycode::V => Letter(LetterKey::V),
+        Keycode::W => Letter(LetterKey::W),
+        Keycode::X => Letter(LetterKey::X),
+        Keycode::Y => Letter(LetterKey::Y),
+        Keycode::Z => Letter(LetterKey::Z),
+        Keycode::Key0 => Digit(0),
+        Keycode::Key1 => Digit(1),
+        Keycode::Key2 => Digit(2),
+        Keycode::Key3 => Digit(3),
+        Keycode::Key4 => Digit(4),
+        Keycode::Key5 => Digit(5),
+        Keycode::Key6 => Digit(6),
+        Keycode::Key7 => Digit(7),
+        Keycode::Key8 => Digit(8),
+        Keycode::Key9 => Digit(9),
+        Keycode::Space => Space,
+        Keycode::Enter => Enter,
+        Keycode::Tab => Tab,
+        Keycode::Backspace => Backspace,
+        Keycode::Delete => Delete,
+        Keycode::Escape => Escape,
+        Keycode::LShift => ShiftL,
+        Keycode::RShift => ShiftR,
+        Keycode::LControl => CtrlL,
+        Keycode::RControl => CtrlR,
+        Keycode::LAlt | Keycode::LOption => AltL,
+        Keycode::RAlt | Keycode::ROption => AltR,
+        Keycode::LMeta | Keycode::Command => MetaL,
+        Keycode::RMeta | Keycode::RCommand => MetaR,
+        Keycode::Up => Arrow(ArrowKey::Up),
+        Keycode::Down => Arrow(ArrowKey::Down),
+        Keycode::Left => Arrow(ArrowKey::Left),
+        Keycode::Right => Arrow(ArrowKey::Right),
+        Keycode::Comma => Symbol(SymbolKey::Comma),
+        Keycode::Dot => Symbol(SymbolKey::Period),
+        Keycode::Semicolon => Symbol(SymbolKey::Semicolon),
+        Keycode::Apostrophe => Symbol(SymbolKey::Quote),
+        Keycode::Grave => Symbol(SymbolKey::Backtick),
+        Keycode::Minus => Symbol(SymbolKey::Minus),
+        Keycode::Equal => Symbol(SymbolKey::Equal),
+        Keycode::LeftBracket => Symbol(SymbolKey::LBracket),
+        Keycode::RightBracket => Symbol(SymbolKey::RBracket),
+        Keycode::BackSlash => Symbol(SymbolKey::Backslash),
+        Keycode::Slash => Symbol(SymbolKey::Slash),
+        Keycode::F1 => Function(1),
+        Keycode::F2 => Function(2),
+        Keycode::F3 => Function(3),
+        Keycode::F4 => Function(4),
+        Keycode::F5 => Function(5),
+        Keycode::F6 => Function(6),
+        Keycode::F7 => Function(7),
+        Keycode::F8 => Function(8),
+        Keycode::F9 => Function(9),
+        Keycode::F10 => Function(10),
+        Keycode::F11 => Function(11),
+        Keycode::F12 => Function(12),
+        Keycode::F13 => Function(13),
+        Keycode::F14 => Function(14),
+        Keycode::F15 => Function(15),
+        Keycode::F16 => Function(16),
+        Keycode::F17 => Function(17),
+        Keycode::F18 => Function(18),
+        Keycode::F19 => Function(19),
+        Keycode::F20 => Function(20),
+        Keycode::Home => Home,
+        Keycode::End => End,
+        Keycode::PageUp => PageUp,
+        Keycode::PageDown => PageDown,
+        Keycode::Insert => Insert,
+        Keycode::CapsLock => CapsLock,
+        Keycode::Numpad0 => NumpadDigit(0),
+        Keycode::Numpad1 => NumpadDigit(1),
+        Keycode::Numpad2 => NumpadDigit(2),
+        Keycode::Numpad3 => NumpadDigit(3),
+        Keycode::Numpad4 => NumpadDigit(4),
+        Keycode::Numpad5 => NumpadDigit(5),
+        Keycode::Numpad6 => NumpadDigit(6),
+        Keycode::Numpad7 => NumpadDigit(7),
+        Keycode::Numpad8 => NumpadDigit(8),
+        Keycode::Numpad9 => NumpadDigit(9),
+        Keycode::NumpadAdd => NumpadAdd,
+        Keycode::NumpadSubtract => NumpadSubtract,
+        Keycode::NumpadMultiply => NumpadMultiply,
+        Keycode::NumpadDivide => NumpadDivide,
+        Keycode::NumpadEnter => NumpadEnter,
+        Keycode::NumpadEquals => NumpadEquals,
+        Keycode::NumpadDecimal => NumpadDecimal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        source::{ScriptedSource, key},
+        stats,
+    };
+    use std::time::Duration;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    /// Every `device_query` 4 keycode, in declaration order.
+    const ALL_KEYCODES: [Keycode; 112] = {
+        use Keycode::*;
+        [
+            Key0,
+            Key1,
+            Key2,
+            Key3,
+            Key4,
+            Key5,
+            Key6,
+            Key7,
+            Key8,
+            Key9,
+            A,
+            B,
+            C,
+            D,
+            E,
+            F,
+            G,
+            H,
+            I,
+            J,
+            K,
+            L,
+            M,
+            N,
+            O,
+            P,
+            Q,
+            R,
+            S,
+            T,
+            U,
+            V,
+            W,
+            X,
+            Y,
+            Z,
+            F1,
+            F2,
+            F3,
+            F4,
+            F5,
+            F6,
+            F7,
+            F8,
+            F9,
+            F10,
+            F11,
+            F12,
+            F13,
+            F14,
+            F15,
+            F16,
+            F17,
+            F18,
+            F19,
+            F20,
+            Escape,
+            Space,
+            LControl,
+            RControl,
+            LShift,
+            RShift,
+            LAlt,
+            RAlt,
+            Command,
+            RCommand,
+            LOption,
+            ROption,
+            LMeta,
+            RMeta,
+            Enter,
+            Up,
+            Down,
+            Left,
+            Right,
+            Backspace,
+            CapsLock,
+            Tab,
+            Home,
+            End,
+            PageUp,
+            PageDown,
+            Insert,
+            Delete,
+            Numpad0,
+            Numpad1,
+            Numpad2,
+            Numpad3,
+            Numpad4,
+            Numpad5,
+            Numpad6,
+            Numpad7,
+            Numpad8,
+            Numpad9,
+            NumpadSubtract,
+            NumpadAdd,
+            NumpadDivide,
+            NumpadMultiply,
+            NumpadEquals,
+            NumpadEnter,
+            NumpadDecimal,
+            Grave,
+            Minus,
+            Equal,
+            LeftBracket,
+            RightBracket,
+            BackSlash,
+            Semicolon,
+            Apostrophe,
+            Comma,
+            Dot,
+            Slash,
+        ]
+    };
+
+    #[test]
+    fn test_every_keycode_round_trips() {
+        let mut labels = HashSet::new();
+        for keycode in ALL_KEYCODES {
+            let key = keycode_to_key_id(keycode);
+            let label = key.label();
+            assert_eq!(KeyId::from_label(label), Some(key), "{keycode:?}");
+            assert_eq!(stats::intern_key_label(label), Some(label));
+            labels.insert(label);
+        }
+        // Only the macOS option/command aliases share a label with another keycode
+        assert_eq!(labels.len(), ALL_KEYCODES.len() - 4);
+    }
+
+    #[test]
+    fn test_capture_loop_counts_presses() {
+        let statistics = Statistics::new();
+        let mut source = ScriptedSource::new([
+            KeyEvent::press(key("LeftShift"), ms(0)),
+            KeyEvent::press(key("A"), ms(10)),
+            KeyEvent::release(key("A"), ms(50)),
+            KeyEvent::release(key("LeftShift"), ms(60)),
+            KeyEvent::press(key("A"), ms(100)),
+            KeyEvent::release(key("A"), ms(130)),
+        ]);
+
+        run_capture_loop(&mut source, &mut Recorder::new(statistics.clone())).unwrap();
+
+        let snapshot = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        assert_eq!(snapshot.get("A"), Some(&2));
+        assert_eq!(snapshot.get("LeftShift"), Some(&1));
+        assert_eq!(snapshot.len(), 2);
+    }
+
+    #[test]
+    fn test_capture_loop_ignores_releases() {
+        let statistics = Statistics::new();
+        let mut source = ScriptedSource::new([
+            KeyEvent::release(key("Space"), ms(0)),
+            KeyEvent::release(key("Enter"), ms(5)),
+        ]);
+
+        run_capture_loop(&mut source, &mut Recorder::new(statistics.clone())).unwrap();
+
+        assert!(
+            stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_capture_loop_empty_source() {
+        let statistics = Statistics::new();
+        let mut source = ScriptedSource::new([]);
+
+        run_capture_loop(&mut source, &mut Recorder::new(statistics.clone())).unwrap();
+
+        assert!(
+            stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_tracker_drops_polling_glitches() {
+        let mut tracker = KeySetTracker::default();
+        let mut poll = |keys: &[Keycode], at: u64| {
+            let mut keys: HashSet<Keycode> = keys.iter().copied().collect();
+            let mut events = Vec::new();
+            tracker.update(&mut keys, ms(at), &mut events);
+            events
+        };
+
+        assert_eq!(poll(&[Keycode::A], 0), [KeyEvent::press(key("A"), ms(0))]);
+        // A drops out of one poll while still held
+        assert_eq!(poll(&[], 10), []);
+        assert_eq!(poll(&[Keycode::A], 20), []);
+        // A real release is reported once a second poll misses it, at the time it happened
+        assert_eq!(poll(&[], 30), []);
+        assert_eq!(poll(&[], 40), [KeyEvent::release(key("A"), ms(30))]);
+        // The window is one poll however long the interval is
+        assert_eq!(
+            poll(&[Keycode::B], 100),
+            [KeyEvent::press(key("B"), ms(100))]
+        );
+        assert_eq!(poll(&[], 150), []);
+        assert_eq!(poll(&[Keycode::B], 200), []);
+        assert_eq!(poll(&[], 250), []);
+        assert_eq!(poll(&[], 300), [KeyEvent::release(key("B"), ms(250))]);
+        assert_eq!(
+            poll(&[Keycode::B], 350),
+            [KeyEvent::press(key("B"), ms(350))]
+        );
     }
 }
@@ -0,0 +1,191 @@
+//! Optional differential-privacy protection of the exported key counts.
+//!
+//! With an `epsilon`, Laplace noise of scale `1/epsilon` is added to the press and repeat
+//! count of every key of the configured layout, including keys that were never pressed, so
+//! the set of exported rows does not reveal which keys were used. Keys the layout lacks are
+//! left out of a noised export, since only pressing them could make their rows appear. A
+//! single key event changes one cell by one (L1 sensitivity 1), so each export is
+//! `epsilon`-differentially private per key event.
+//! Keys whose (noisy) count is below `min_count` are then left out entirely.
+//!
+//! Only the count CSV is protected; checkpoints and the other statistics stay exact.
+
+use key_id::{KeyId, PhysicalLayout};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Privacy {
+    /// Privacy budget of the Laplace mechanism; `None` adds no noise
+    pub epsilon: Option<f64>,
+    /// Keys whose count is below this are suppressed; 0 keeps every key
+    pub min_count: u64,
+    /// Board the data is typed on; with noise, only its keys are exported
+    pub layout: PhysicalLayout,
+}
+
+impl Privacy {
+    pub fn is_enabled(&self) -> bool {
+        self.epsilon.is_some() || self.min_count > 0
+    }
+
+    /// Returns noised copies of `counts` and `repeats` without the suppressed keys.
+    ///
+    /// Noisy counts are rounded and clamped at zero, and keys that end up with neither
+    /// presses nor repeats are dropped. With noise, keys outside the layout are dropped too.
+    pub fn apply<R: Rng + ?Sized>(
+        &self,
+        counts: &HashMap<&'static str, u64>,
+        repeats: &HashMap<&'static str, u64>,
+        rng: &mut R,
+    ) -> (HashMap<&'static str, u64>, HashMap<&'static str, u64>) {
+        // Noise covers a fixed set of keys, in a fixed order; a recorded key outside it
+        // would stand out
+        let keys: Vec<&'static str> = match self.epsilon {
+            Some(_) => KeyId::all()
+                .filter(|&key| self.layout.has_key(key))
+                .map(KeyId::label)
+                .collect(),
+            None => counts
+                .keys()
+                .chain(repeats.keys())
+                .copied()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+
+        let mut noised = |count: u64| match self.epsilon {
+            Some(epsilon) => (count as f64 + laplace(rng, 1.0 / epsilon))
+                .round()
+                .max(0.0) as u64,
+            None => count,
+        };
+
+        let mut out_counts = HashMap::new();
+        let mut out_repeats = HashMap::new();
+        for key in keys {
+            let count = noised(counts.get(key).copied().unwrap_or(0));
+            let repeat = noised(repeats.get(key).copied().unwrap_or(0));
+            if count < self.min_count || (count == 0 && repeat == 0) {
+                continue;
+            }
+            out_counts.insert(key, count);
+            if repeat > 0 {
+                out_repeats.insert(key, repeat);
+            }
+        }
+        (out_counts, out_repeats)
+    }
+}
+
+/// Draws from a zero-centred Laplace distribution with the given scale (inverse CDF).
+fn laplace<R: Rng + ?Sized>(rng: &mut R, scale: f64) -> f64 {
+    // Uniform on (-0.5, 0.5]; the open end keeps ln() finite
+    let u = 0.5 - rng.random::<f64>();
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_laplace_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples: Vec<f64> = (0..100_000).map(|_| laplace(&mut rng, 2.0)).collect();
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let mean_abs = samples.iter().map(|x| x.abs()).sum::<f64>() / n;
+        // E[X] = 0 and E[|X|] = scale
+        assert!(mean.abs() < 0.05, "mean {mean}");
+        assert!((mean_abs - 2.0).abs() < 0.05, "mean |x| {mean_abs}");
+    }
+
+    #[test]
+    fn test_suppression_without_noise() {
+        let privacy = Privacy {
+            epsilon: None,
+            min_count: 5,
+            ..Privacy::default()
+        };
+        let counts = HashMap::from([("E", 40), ("Q", 4), ("Z", 5)]);
+        let repeats = HashMap::from([("E", 3), ("Q", 90)]);
+
+        let (counts, repeats) = privacy.apply(&counts, &repeats, &mut StdRng::seed_from_u64(0));
+        assert_eq!(counts, HashMap::from([("E", 40), ("Z", 5)]));
+        // A suppressed key leaves no trace in the repeats either
+        assert_eq!(repeats, HashMap::from([("E", 3)]));
+    }
+
+    #[test]
+    fn test_noise_covers_every_key() {
+        let privacy = Privacy {
+            epsilon: Some(0.5),
+            min_count: 0,
+            ..Privacy::default()
+        };
+        let counts = HashMap::from([("E", 1000)]);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let (noised, _) = privacy.apply(&counts, &HashMap::new(), &mut rng);
+        assert!(noised["E"].abs_diff(1000) < 50);
+        // Unpressed keys are noised too, so some of them show up with small counts
+        assert!(noised.len() > 1);
+        assert!(noised.keys().all(|&k| KeyId::from_label(k).is_some()));
+    }
+
+    #[test]
+    fn test_noise_only_covers_layout_keys() {
+        let counts = HashMap::from([("E", 1000)]);
+        for layout in PhysicalLayout::ALL {
+            let privacy = Privacy {
+                // Large noise, so nearly every key in the domain shows up
+                epsilon: Some(0.01),
+                min_count: 0,
+                layout,
+            };
+            let mut rng = StdRng::seed_from_u64(3);
+            let (noised, _) = privacy.apply(&counts, &HashMap::new(), &mut rng);
+            for &label in noised.keys() {
+                let key = KeyId::from_label(label).unwrap();
+                assert!(layout.has_key(key), "{label} noised on {layout}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_keys_outside_layout_leave_no_trace() {
+        let privacy = Privacy {
+            epsilon: Some(1.0),
+            min_count: 0,
+            layout: PhysicalLayout::Us,
+        };
+        let base = HashMap::from([("E", 1000)]);
+        let with_jis_keys = HashMap::from([("E", 1000), ("Yen", 500), ("Henkan", 40)]);
+
+        // With the same noise, the output is identical whether or not keys a US board
+        // lacks were pressed
+        let export = |counts: &HashMap<&'static str, u64>| {
+            privacy.apply(counts, counts, &mut StdRng::seed_from_u64(5))
+        };
+        let (noised, repeats) = export(&with_jis_keys);
+        assert_eq!(export(&base), (noised.clone(), repeats));
+        assert!(!noised.contains_key("Yen") && !noised.contains_key("Henkan"));
+    }
+
+    #[test]
+    fn test_noisy_counts_are_suppressed() {
+        let privacy = Privacy {
+            epsilon: Some(1.0),
+            min_count: 20,
+            ..Privacy::default()
+        };
+        let counts = HashMap::from([("E", 500), ("Q", 1)]);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let (noised, _) = privacy.apply(&counts, &HashMap::new(), &mut rng);
+        assert_eq!(noised.keys().copied().collect::<Vec<_>>(), ["E"]);
+    }
+}
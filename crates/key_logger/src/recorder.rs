@@ -0,0 +1,787 @@
+use crate::{
+    bigrams::{self, BigramStatistics},
+    chords::{self, ChordStatistics, ModifierSet},
+    devices::{self, DeviceStatistics},
+    error::Result,
+    sessions::{self, SessionStatistics},
+    source::{KeyEvent, KeyEventKind},
+    stats::{self, KeyStatistics},
+    timing::{self, TimingStatistics},
+};
+use key_id::KeyId;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+/// Handles to every statistic being collected. Cheap to clone across threads.
+#[derive(Debug, Clone)]
+pub struct Statistics {
+    /// Absent when key counting is disabled
+    pub keys: Option<KeyStatistics>,
+    /// Auto-repeats and duplicate presses of held keys, kept out of `keys`; present with it
+    pub repeats: Option<KeyStatistics>,
+    /// Only present when bigram counting is enabled
+    pub bigrams: Option<BigramStatistics>,
+    /// Only present when timing histograms are enabled
+    pub timing: Option<TimingStatistics>,
+    /// Only present when chord counting is enabled
+    pub chords: Option<ChordStatistics>,
+    /// Only present when session segmentation is enabled
+    pub sessions: Option<SessionStatistics>,
+    /// Only present when per-device counting is enabled
+    pub devices: Option<DeviceStatistics>,
+}
+
+impl Statistics {
+    /// Key counts only; optional statistics are added with the `with_*` methods.
+    pub fn new() -> Self {
+        Self {
+            keys: Some(stats::create_statistics()),
+            repeats: Some(stats::create_statistics()),
+            bigrams: None,
+            timing: None,
+            chords: None,
+            sessions: None,
+            devices: None,
+        }
+    }
+
+    pub fn without_keys(mut self) -> Self {
+        self.keys = None;
+        self.repeats = None;
+        self
+    }
+
+    pub fn with_bigrams(mut self) -> Self {
+        self.bigrams = Some(bigrams::create_bigram_statistics());
+        self
+    }
+
+    pub fn with_timing(mut self) -> Self {
+        self.timing = Some(timing::create_timing_statistics());
+        self
+    }
+
+    pub fn with_chords(mut self) -> Self {
+        self.chords = Some(chords::create_chord_statistics());
+        self
+    }
+
+    /// Splits presses into sessions separated by at least `idle_gap` without a press.
+    pub fn with_sessions(mut self, idle_gap: Duration) -> Self {
+        self.sessions = Some(sessions::create_session_statistics(idle_gap));
+        self
+    }
+
+    pub fn with_devices(mut self) -> Self {
+        self.devices = Some(devices::create_device_statistics());
+        self
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Point-in-time copy of every enabled statistic.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub keys: Option<HashMap<&'static str, u64>>,
+    pub repeats: Option<HashMap<&'static str, u64>>,
+    pub bigrams: Option<bigrams::BigramMap>,
+    pub timing: Option<timing::TimingData>,
+    pub chords: Option<chords::ChordMap>,
+    pub sessions: Option<Vec<sessions::Session>>,
+    pub devices: Option<devices::DeviceMap>,
+}
+
+impl Snapshot {
+    /// True when no enabled statistic has recorded anything
+    pub fn is_empty(&self) -> bool {
+        self.keys.as_ref().is_none_or(HashMap::is_empty)
+            && self.repeats.as_ref().is_none_or(HashMap::is_empty)
+            && self.bigrams.as_ref().is_none_or(HashMap::is_empty)
+            && self
+                .timing
+                .as_ref()
+                .is_none_or(timing::TimingData::is_empty)
+            && self.chords.as_ref().is_none_or(HashMap::is_empty)
+            && self.sessions.as_ref().is_none_or(Vec::is_empty)
+            && self.devices.as_ref().is_none_or(HashMap::is_empty)
+    }
+}
+
+impl Statistics {
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            keys: self
+                .keys
+                .as_ref()
+                .map(stats::get_statistics_snapshot)
+                .transpose()?,
+            repeats: self
+                .repeats
+                .as_ref()
+                .map(stats::get_statistics_snapshot)
+                .transpose()?,
+            bigrams: self
+                .bigrams
+                .as_ref()
+                .map(bigrams::get_bigram_snapshot)
+                .transpose()?,
+            timing: self
+                .timing
+                .as_ref()
+                .map(timing::get_timing_snapshot)
+                .transpose()?,
+            chords: self
+                .chords
+                .as_ref()
+                .map(chords::get_chord_snapshot)
+                .transpose()?,
+            sessions: self
+                .sessions
+                .as_ref()
+                .map(sessions::get_session_snapshot)
+                .transpose()?,
+            devices: self
+                .devices
+                .as_ref()
+                .map(devices::get_device_snapshot)
+                .transpose()?,
+        })
+    }
+
+    /// Snapshots every statistic and resets it to zero.
+    ///
+    /// Each statistic is taken separately, so a batch recorded while this runs
+    /// may be split between the returned snapshot and the fresh counters.
+    pub fn take_snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            keys: self
+                .keys
+                .as_ref()
+                .map(stats::take_statistics_snapshot)
+                .transpose()?,
+            repeats: self
+                .repeats
+                .as_ref()
+                .map(stats::take_statistics_snapshot)
+                .transpose()?,
+            bigrams: self
+                .bigrams
+                .as_ref()
+                .map(bigrams::take_bigram_snapshot)
+                .transpose()?,
+            timing: self
+                .timing
+                .as_ref()
+                .map(timing::take_timing_snapshot)
+                .transpose()?,
+            chords: self
+                .chords
+                .as_ref()
+                .map(chords::take_chord_snapshot)
+                .transpose()?,
+            sessions: self
+                .sessions
+                .as_ref()
+                .map(sessions::take_session_snapshot)
+                .transpose()?,
+            devices: self
+                .devices
+                .as_ref()
+                .map(devices::take_device_snapshot)
+                .transpose()?,
+        })
+    }
+
+    /// Adds a taken snapshot back, e.g. when exporting it failed.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<()> {
+        if let (Some(keys), Some(counts)) = (&self.keys, &snapshot.keys) {
+            stats::merge_counts(keys, counts)?;
+        }
+        if let (Some(repeats), Some(counts)) = (&self.repeats, &snapshot.repeats) {
+            stats::merge_counts(repeats, counts)?;
+        }
+        if let (Some(bigrams), Some(counts)) = (&self.bigrams, &snapshot.bigrams) {
+            bigrams::merge_bigrams(bigrams, counts)?;
+        }
+        if let (Some(timing), Some(data)) = (&self.timing, &snapshot.timing) {
+            timing::merge_timing(timing, data)?;
+        }
+        if let (Some(chords), Some(counts)) = (&self.chords, &snapshot.chords) {
+            chords::merge_chords(chords, counts)?;
+        }
+        if let (Some(sessions), Some(taken)) = (&self.sessions, &snapshot.sessions) {
+            sessions::merge_sessions(sessions, taken)?;
+        }
+        if let (Some(devices), Some(counts)) = (&self.devices, &snapshot.devices) {
+            devices::merge_devices(devices, counts)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which keys reach the statistics, checked before anything else sees an event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFilter {
+    /// Only these keys are recorded; `None` records every key
+    pub include: Option<HashSet<&'static str>>,
+    /// Never recorded, even when also included
+    pub exclude: HashSet<&'static str>,
+}
+
+impl KeyFilter {
+    pub fn allows(&self, key: &str) -> bool {
+        !self.exclude.contains(key) && self.include.as_ref().is_none_or(|keys| keys.contains(key))
+    }
+}
+
+/// A key on the keyboard that sent it; `device` is `None` for sources that cannot tell
+/// keyboards apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct HeldKey {
+    device: Option<&'static str>,
+    key: KeyId,
+}
+
+impl HeldKey {
+    fn of(event: &KeyEvent) -> Self {
+        Self {
+            device: event.device,
+            key: event.key,
+        }
+    }
+}
+
+/// Turns key events into statistics.
+///
+/// The only state kept between batches is what the enabled statistics strictly need
+/// (the previous press, which keys are held and since when, and which modifiers are held);
+/// events themselves are never stored.
+///
+/// A repeat, or a press of a key that is already held, only counts as a repeat of that
+/// key: it is not a keystroke, so it never forms a pair, interval or chord. Keys are held
+/// per device, so the same key pressed on two keyboards at once counts twice, and a
+/// modifier only forms chords with keys of its own keyboard.
+#[derive(Debug)]
+pub struct Recorder {
+    statistics: Statistics,
+    filter: KeyFilter,
+    last_press: Option<(&'static str, Duration)>,
+    held: HashSet<HeldKey>,
+    pressed_at: HashMap<HeldKey, Duration>,
+    held_modifiers: HashMap<Option<&'static str>, ModifierSet>,
+    presses: Vec<(KeyId, Duration)>,
+    repeats: Vec<KeyId>,
+    pairs: Vec<(&'static str, &'static str)>,
+    holds: Vec<(&'static str, Duration)>,
+    intervals: Vec<Duration>,
+    chords: Vec<(ModifierSet, &'static str)>,
+    device_presses: Vec<(&'static str, &'static str)>,
+    device_repeats: Vec<(&'static str, &'static str)>,
+}
+
+impl Recorder {
+    pub fn new(statistics: Statistics) -> Self {
+        Self {
+            statistics,
+            filter: KeyFilter::default(),
+            last_press: None,
+            held: HashSet::with_capacity(16),
+            pressed_at: HashMap::with_capacity(16),
+            held_modifiers: HashMap::new(),
+            presses: Vec::with_capacity(16),
+            repeats: Vec::with_capacity(16),
+            pairs: Vec::with_capacity(16),
+            holds: Vec::with_capacity(16),
+            intervals: Vec::with_capacity(16),
+            chords: Vec::with_capacity(16),
+            device_presses: Vec::with_capacity(16),
+            device_repeats: Vec::with_capacity(16),
+        }
+    }
+
+    /// Drops events for keys that `filter` does not allow before any statistic sees them.
+    pub fn filtering(mut self, filter: KeyFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Forgets the previous press and held keys, e.g. after recording was paused.
+    pub fn reset_state(&mut self) {
+        self.last_press = None;
+        self.held.clear();
+        self.pressed_at.clear();
+        self.held_modifiers.clear();
+    }
+
+    pub fn record(&mut self, events: &[KeyEvent]) -> Result<()> {
+        self.presses.clear();
+        self.repeats.clear();
+        self.pairs.clear();
+        self.holds.clear();
+        self.intervals.clear();
+        self.chords.clear();
+        self.device_presses.clear();
+        self.device_repeats.clear();
+
+        for event in events {
+            if !self.filter.allows(event.key.label()) {
+                continue;
+            }
+            match event.kind {
+                KeyEventKind::Press => self.on_press(event),
+                KeyEventKind::Release => self.on_release(event),
+                KeyEventKind::Repeat => self.on_repeat(event),
+                KeyEventKind::Lost => self.on_lost(event),
+            }
+        }
+
+        if let Some(ref keys) = self.statistics.keys {
+            stats::add_many(keys, self.presses.iter().map(|&(key, _)| key));
+        }
+        if let Some(ref repeats) = self.statistics.repeats {
+            stats::add_many(repeats, self.repeats.iter().copied());
+        }
+        if let Some(ref bigrams) = self.statistics.bigrams {
+            bigrams::add_many(bigrams, self.pairs.iter().copied())?;
+        }
+        if let Some(ref timing) = self.statistics.timing
+            && !(self.holds.is_empty() && self.intervals.is_empty())
+        {
+            timing::add_many(
+                timing,
+                self.holds.iter().copied(),
+                self.intervals.iter().copied(),
+            )?;
+        }
+        if let Some(ref chords) = self.statistics.chords {
+            chords::add_many(chords, self.chords.iter().copied())?;
+        }
+        if let Some(ref sessions) = self.statistics.sessions {
+            sessions::add_many(
+                sessions,
+                self.presses.iter().map(|&(key, time)| (key.label(), time)),
+            )?;
+        }
+        if let Some(ref devices) = self.statistics.devices
+            && !(self.device_presses.is_empty() && self.device_repeats.is_empty())
+        {
+            devices::add_many(
+                devices,
+                self.device_presses.iter().copied(),
+                self.device_repeats.iter().copied(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn on_press(&mut self, event: &KeyEvent) {
+        // The source missed the release, or reported the same press twice
+        if !self.held.insert(HeldKey::of(event)) {
+            self.on_repeat(event);
+            return;
+        }
+        let key = event.key.label();
+        self.presses.push((event.key, event.timestamp));
+        if let Some(device) = event.device
+            && self.statistics.devices.is_some()
+        {
+            self.device_presses.push((device, key));
+        }
+        if let Some((prev, prev_time)) = self.last_press.replace((key, event.timestamp)) {
+            self.pairs.push((prev, key));
+            self.intervals
+                .push(event.timestamp.saturating_sub(prev_time));
+        }
+        if self.statistics.timing.is_some() {
+            self.pressed_at.insert(HeldKey::of(event), event.timestamp);
+        }
+        if self.statistics.chords.is_some() {
+            let modifiers = self.held_modifiers.entry(event.device).or_default();
+            if !modifiers.insert(key) && !modifiers.is_empty() {
+                self.chords.push((*modifiers, key));
+            }
+        }
+    }
+
+    fn on_repeat(&mut self, event: &KeyEvent) {
+        // A key held since before startup or a pause only shows up through its repeats
+        self.held.insert(HeldKey::of(event));
+        self.repeats.push(event.key);
+        if let Some(device) = event.device
+            && self.statistics.devices.is_some()
+        {
+            self.device_repeats.push((device, event.key.label()));
+        }
+    }
+
+    fn on_release(&mut self, event: &KeyEvent) {
+        self.held.remove(&HeldKey::of(event));
+        if let Some(modifiers) = self.held_modifiers.get_mut(&event.device) {
+            modifiers.remove(event.key.label());
+        }
+        // A release without a matching press (e.g. held since before startup) has no duration
+        if let Some(pressed) = self.pressed_at.remove(&HeldKey::of(event)) {
+            self.holds
+                .push((event.key.label(), event.timestamp.saturating_sub(pressed)));
+        }
+    }
+
+    /// Forgets a held key whose release was lost; the next press of it counts again.
+    /// Nothing is recorded, since when the key really came up is unknown.
+    fn on_lost(&mut self, event: &KeyEvent) {
+        self.held.remove(&HeldKey::of(event));
+        self.pressed_at.remove(&HeldKey::of(event));
+        if let Some(modifiers) = self.held_modifiers.get_mut(&event.device) {
+            modifiers.remove(event.key.label());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::key;
+    use std::time::Duration;
+
+    fn type_keys(recorder: &mut Recorder, keys: &[&'static str]) {
+        let events: Vec<KeyEvent> = keys
+            .iter()
+            .flat_map(|&k| {
+                [
+                    KeyEvent::press(key(k), Duration::ZERO),
+                    KeyEvent::release(key(k), Duration::ZERO),
+                ]
+            })
+            .collect();
+        recorder.record(&events).unwrap();
+    }
+
+    #[test]
+    fn test_bigrams_disabled_by_default() {
+        let statistics = Statistics::new();
+        let mut recorder = Recorder::new(statistics.clone());
+
+        type_keys(&mut recorder, &["T", "H", "E"]);
+
+        assert!(statistics.bigrams.is_none());
+        let snapshot = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        assert_eq!(snapshot.len(), 3);
+    }
+
+    #[test]
+    fn test_excluded_keys_are_dropped() {
+        let statistics = Statistics::new().with_bigrams().with_chords();
+        let mut recorder = Recorder::new(statistics.clone()).filtering(KeyFilter {
+            exclude: HashSet::from(["H"]),
+            ..KeyFilter::default()
+        });
+
+        type_keys(&mut recorder, &["LeftControl", "T", "H", "E"]);
+
+        let snapshot = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        assert_eq!(snapshot.get("H"), None);
+        assert_eq!(snapshot.get("T"), Some(&1));
+        let bigrams = bigrams::get_bigram_snapshot(statistics.bigrams.as_ref().unwrap()).unwrap();
+        assert_eq!(bigrams.get(&("T", "E")), Some(&1));
+        assert!(!bigrams.keys().any(|&(from, to)| from == "H" || to == "H"));
+    }
+
+    #[test]
+    fn test_only_included_keys_are_recorded() {
+        let statistics = Statistics::new().with_bigrams();
+        let mut recorder = Recorder::new(statistics.clone()).filtering(KeyFilter {
+            include: Some(HashSet::from(["T", "H", "E"])),
+            exclude: HashSet::from(["E"]),
+        });
+
+        type_keys(&mut recorder, &["T", "Numpad1", "H", "E", "N"]);
+
+        let snapshot = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        assert_eq!(snapshot, HashMap::from([("T", 1), ("H", 1)]));
+        // Filtered keys do not break up the pairs around them
+        let bigrams = bigrams::get_bigram_snapshot(statistics.bigrams.as_ref().unwrap()).unwrap();
+        assert_eq!(bigrams, HashMap::from([(("T", "H"), 1)]));
+    }
+
+    #[test]
+    fn test_keys_disabled() {
+        let statistics = Statistics::new().without_keys().with_bigrams();
+        let mut recorder = Recorder::new(statistics.clone());
+
+        type_keys(&mut recorder, &["T", "H"]);
+
+        assert!(statistics.keys.is_none());
+        let bigrams = bigrams::get_bigram_snapshot(statistics.bigrams.as_ref().unwrap()).unwrap();
+        assert_eq!(bigrams.get(&("T", "H")), Some(&1));
+    }
+
+    #[test]
+    fn test_bigrams_span_batches() {
+        let statistics = Statistics::new().with_bigrams();
+        let mut recorder = Recorder::new(statistics.clone());
+
+        type_keys(&mut recorder, &["T", "H"]);
+        type_keys(&mut recorder, &["E", "T", "H"]);
+
+        let bigrams = statistics.bigrams.as_ref().unwrap();
+        let snapshot = bigrams::get_bigram_snapshot(bigrams).unwrap();
+        assert_eq!(snapshot.get(&("T", "H")), Some(&2));
+        assert_eq!(snapshot.get(&("H", "E")), Some(&1));
+        assert_eq!(snapshot.get(&("E", "T")), Some(&1));
+        assert_eq!(snapshot.values().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn test_timing_hold_and_interval() {
+        let statistics = Statistics::new().with_timing();
+        let mut recorder = Recorder::new(statistics.clone());
+        let ms = Duration::from_millis;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("A"), ms(0)),
+                KeyEvent::press(key("B"), ms(150)),
+                KeyEvent::release(key("A"), ms(200)),
+            ])
+            .unwrap();
+        recorder
+            .record(&[
+                KeyEvent::release(key("B"), ms(240)),
+                // Released before it was ever seen pressed
+                KeyEvent::release(key("C"), ms(250)),
+            ])
+            .unwrap();
+
+        let timing = statistics.timing.as_ref().unwrap();
+        let snapshot = timing::get_timing_snapshot(timing).unwrap();
+        assert_eq!(snapshot.hold["A"].total(), 1);
+        assert_eq!(snapshot.hold["B"].total(), 1);
+        assert!(!snapshot.hold.contains_key("C"));
+        assert_eq!(snapshot.interval.total(), 1);
+    }
+
+    #[test]
+    fn test_chords() {
+        let statistics = Statistics::new().with_chords();
+        let mut recorder = Recorder::new(statistics.clone());
+        let t = Duration::ZERO;
+
+        recorder
+            .record(&[
+                // Ctrl+C
+                KeyEvent::press(key("LeftControl"), t),
+                KeyEvent::press(key("C"), t),
+                KeyEvent::release(key("C"), t),
+                // Ctrl+Shift+T; the modifier pressed while Ctrl is held is not a chord itself
+                KeyEvent::press(key("LeftShift"), t),
+                KeyEvent::press(key("T"), t),
+                KeyEvent::release(key("T"), t),
+                KeyEvent::release(key("LeftShift"), t),
+                KeyEvent::press(key("C"), t),
+                KeyEvent::release(key("C"), t),
+                KeyEvent::release(key("LeftControl"), t),
+                // Plain typing is not counted
+                KeyEvent::press(key("C"), t),
+            ])
+            .unwrap();
+
+        let chords = statistics.chords.as_ref().unwrap();
+        let snapshot = chords::get_chord_snapshot(chords).unwrap();
+        let labeled: HashMap<(String, &str), u64> = snapshot
+            .iter()
+            .map(|(&(mods, key), &n)| ((mods.label(), key), n))
+            .collect();
+        assert_eq!(labeled.get(&("LeftControl".to_string(), "C")), Some(&2));
+        assert_eq!(
+            labeled.get(&("LeftControl+LeftShift".to_string(), "T")),
+            Some(&1)
+        );
+        assert_eq!(labeled.len(), 2);
+    }
+
+    #[test]
+    fn test_repeats_are_counted_separately() {
+        let statistics = Statistics::new().with_bigrams().with_timing().with_chords();
+        let mut recorder = Recorder::new(statistics.clone());
+        let ms = Duration::from_millis;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("LeftControl"), ms(0)),
+                KeyEvent::press(key("A"), ms(100)),
+                KeyEvent::repeat(key("A"), ms(600)),
+                KeyEvent::repeat(key("A"), ms(633)),
+                // A duplicate press of a held key, e.g. after the source missed a release
+                KeyEvent::press(key("A"), ms(650)),
+                KeyEvent::release(key("A"), ms(700)),
+                KeyEvent::release(key("LeftControl"), ms(720)),
+                // Held since before startup
+                KeyEvent::repeat(key("B"), ms(800)),
+                KeyEvent::release(key("B"), ms(850)),
+            ])
+            .unwrap();
+
+        let snapshot = statistics.snapshot().unwrap();
+        let keys = snapshot.keys.unwrap();
+        assert_eq!(keys["A"], 1);
+        assert!(!keys.contains_key("B"));
+        let repeats = snapshot.repeats.unwrap();
+        assert_eq!(repeats["A"], 3);
+        assert_eq!(repeats["B"], 1);
+
+        let bigrams = snapshot.bigrams.unwrap();
+        assert_eq!(bigrams.len(), 1);
+        assert_eq!(bigrams[&("LeftControl", "A")], 1);
+        let timing = snapshot.timing.unwrap();
+        assert_eq!(timing.hold["A"].total(), 1);
+        assert_eq!(timing.interval.total(), 1);
+        assert_eq!(snapshot.chords.unwrap().values().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn test_counts_per_device() {
+        let statistics = Statistics::new().with_devices();
+        let mut recorder = Recorder::new(statistics.clone());
+        let t = Duration::ZERO;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("A"), t).on_device("Laptop"),
+                KeyEvent::release(key("A"), t).on_device("Laptop"),
+                KeyEvent::press(key("A"), t).on_device("Split"),
+                KeyEvent::repeat(key("A"), t).on_device("Split"),
+                KeyEvent::release(key("A"), t).on_device("Split"),
+                // Sources that cannot tell keyboards apart only feed the totals
+                KeyEvent::press(key("B"), t),
+                KeyEvent::release(key("B"), t),
+            ])
+            .unwrap();
+
+        let snapshot = statistics.snapshot().unwrap();
+        assert_eq!(snapshot.keys.unwrap()["A"], 2);
+        let devices = snapshot.devices.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[&("Laptop", "A")].presses, 1);
+        assert_eq!(devices[&("Split", "A")].presses, 1);
+        assert_eq!(devices[&("Split", "A")].repeats, 1);
+    }
+
+    #[test]
+    fn test_keys_are_held_per_device() {
+        let statistics = Statistics::new().with_timing().with_chords().with_devices();
+        let mut recorder = Recorder::new(statistics.clone());
+        let ms = Duration::from_millis;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("LeftShift"), ms(0)).on_device("Laptop"),
+                KeyEvent::press(key("A"), ms(10)).on_device("Laptop"),
+                // The same key on another keyboard while the first is still down
+                KeyEvent::press(key("A"), ms(20)).on_device("Split"),
+                KeyEvent::release(key("A"), ms(30)).on_device("Laptop"),
+                KeyEvent::repeat(key("A"), ms(520)).on_device("Split"),
+                KeyEvent::release(key("A"), ms(540)).on_device("Split"),
+                // The laptop's Shift does not modify the split keyboard
+                KeyEvent::press(key("B"), ms(600)).on_device("Split"),
+                KeyEvent::release(key("B"), ms(610)).on_device("Split"),
+                KeyEvent::release(key("LeftShift"), ms(700)).on_device("Laptop"),
+            ])
+            .unwrap();
+
+        let snapshot = statistics.snapshot().unwrap();
+        assert_eq!(snapshot.keys.unwrap()["A"], 2);
+        assert_eq!(snapshot.repeats.unwrap()["A"], 1);
+        let devices = snapshot.devices.unwrap();
+        assert_eq!(devices[&("Laptop", "A")].presses, 1);
+        assert_eq!(devices[&("Split", "A")].presses, 1);
+        assert_eq!(devices[&("Split", "A")].repeats, 1);
+        assert_eq!(snapshot.timing.unwrap().hold["A"].total(), 2);
+        let chords = snapshot.chords.unwrap();
+        assert_eq!(chords.len(), 1);
+        assert!(chords.keys().all(|&(_, key)| key == "A"));
+    }
+
+    #[test]
+    fn test_lost_release_does_not_turn_presses_into_repeats() {
+        let statistics = Statistics::new().with_timing().with_chords();
+        let mut recorder = Recorder::new(statistics.clone());
+        let t = Duration::ZERO;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("LeftControl"), t).on_device("Laptop"),
+                KeyEvent::press(key("A"), t).on_device("Laptop"),
+                KeyEvent::lost(key("A"), t).on_device("Laptop"),
+                KeyEvent::lost(key("LeftControl"), t).on_device("Laptop"),
+                KeyEvent::press(key("A"), t).on_device("Laptop"),
+                KeyEvent::release(key("A"), t).on_device("Laptop"),
+            ])
+            .unwrap();
+
+        let snapshot = statistics.snapshot().unwrap();
+        assert_eq!(snapshot.keys.unwrap()["A"], 2);
+        assert!(snapshot.repeats.unwrap().is_empty());
+        assert_eq!(snapshot.timing.unwrap().hold["A"].total(), 1);
+        assert_eq!(snapshot.chords.unwrap().values().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn test_reset_state_forgets_held_keys() {
+        let statistics = Statistics::new().with_bigrams().with_timing().with_chords();
+        let mut recorder = Recorder::new(statistics.clone());
+        let t = Duration::ZERO;
+
+        recorder
+            .record(&[
+                KeyEvent::press(key("LeftControl"), t),
+                KeyEvent::press(key("A"), t),
+            ])
+            .unwrap();
+        recorder.reset_state();
+        recorder
+            .record(&[
+                KeyEvent::release(key("A"), t),
+                KeyEvent::press(key("B"), t),
+                KeyEvent::release(key("B"), t),
+            ])
+            .unwrap();
+
+        let snapshot = statistics.snapshot().unwrap();
+        // Only what happened before the reset pairs up; B follows neither A nor Ctrl
+        let bigrams = snapshot.bigrams.unwrap();
+        assert_eq!(bigrams.len(), 1);
+        assert_eq!(bigrams[&("LeftControl", "A")], 1);
+        let timing = snapshot.timing.unwrap();
+        assert!(!timing.hold.contains_key("A"));
+        assert_eq!(timing.hold["B"].total(), 1);
+        assert_eq!(timing.interval.total(), 1);
+        assert_eq!(snapshot.chords.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_take_snapshot_and_restore() {
+        let statistics = Statistics::new().with_bigrams().with_timing();
+        let mut recorder = Recorder::new(statistics.clone());
+        type_keys(&mut recorder, &["A", "B"]);
+
+        let taken = statistics.take_snapshot().unwrap();
+        assert_eq!(taken.keys.as_ref().unwrap()["A"], 1);
+        assert_eq!(taken.bigrams.as_ref().unwrap()[&("A", "B")], 1);
+        assert!(!taken.is_empty());
+        assert!(statistics.snapshot().unwrap().is_empty());
+
+        type_keys(&mut recorder, &["A"]);
+        statistics.restore(&taken).unwrap();
+        let snapshot = statistics.snapshot().unwrap();
+        assert_eq!(snapshot.keys.as_ref().unwrap()["A"], 2);
+        assert_eq!(snapshot.bigrams.as_ref().unwrap()[&("B", "A")], 1);
+        assert_eq!(snapshot.timing.unwrap().hold["A"].total(), 2);
+    }
+}
@@ -0,0 +1,386 @@
+//! Replays scripted key events through the normal capture pipeline.
+//!
+//! Two script formats are accepted:
+//!
+//! - Plain text, typed on a US layout: every character becomes a press and release of the
+//!   key that produces it, with `LeftShift` held for uppercase letters and shifted symbols.
+//! - A JSON list of events (`.json` files), e.g.
+//!   `[{"key": "A", "kind": "press", "time_ms": 0}, {"key": "A", "kind": "release", "time_ms": 80}]`.
+//!   `kind` is `press`, `release` or `repeat` (an auto-repeat of a held key). An optional
+//!   `device` names the keyboard the event came from, for per-device statistics.
+//!
+//! Nothing here touches an input device, so replays run the same on headless machines.
+
+use crate::{
+    config::Config,
+    devices,
+    error::{KeyLoggerError, Result},
+    platform_common,
+    recorder::{Recorder, Statistics},
+    source::{KeyEvent, KeySource},
+};
+use key_id::{KeyId, LetterKey, SymbolKey};
+use serde::Deserialize;
+use std::{collections::VecDeque, path::Path, time::Duration};
+
+/// Time between the starts of consecutive synthesized keystrokes
+const KEYSTROKE_INTERVAL: Duration = Duration::from_millis(120);
+/// How long each synthesized key stays down
+const HOLD_DURATION: Duration = Duration::from_millis(80);
+/// Gap between Shift and the shifted key
+const MODIFIER_LEAD: Duration = Duration::from_millis(20);
+
+/// Feeds a prepared list of events to the capture loop one at a time.
+#[derive(Debug)]
+pub struct ReplaySource {
+    events: VecDeque<KeyEvent>,
+}
+
+impl ReplaySource {
+    pub fn new(events: impl IntoIterator<Item = KeyEvent>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+        }
+    }
+
+    /// Loads a script, choosing the format by extension (`.json`, otherwise plain text).
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let events = if is_json {
+            parse_json(&content)
+        } else {
+            parse_text(&content)
+        }
+        .map_err(|e| match e {
+            KeyLoggerError::InvalidReplay(message) => {
+                KeyLoggerError::InvalidReplay(format!("{}: {message}", path.display()))
+            }
+            e => e,
+        })?;
+        Ok(Self::new(events))
+    }
+}
+
+impl KeySource for ReplaySource {
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Result<bool> {
+        events.extend(self.events.pop_front());
+        Ok(!self.events.is_empty())
+    }
+
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+}
+
+/// Replays `source` into `statistics`, honouring the configured key exclusions.
+/// Returns the backend name, like the platform capture functions.
+pub(crate) fn run_replay(
+    mut source: ReplaySource,
+    statistics: Statistics,
+    config: &Config,
+) -> Result<&'static str> {
+    let mut recorder = Recorder::new(statistics).filtering(config.key_filter());
+    platform_common::run_capture_loop(&mut source, &mut recorder)?;
+    Ok(source.name())
+}
+
+/// Key and whether Shift is needed to type `c` on a US layout.
+fn char_to_key(c: char) -> Option<(KeyId, bool)> {
+    use KeyId::*;
+    use SymbolKey::*;
+    let key = match c {
+        'a'..='z' | 'A'..='Z' => {
+            let letter = LetterKey::ALL[(c.to_ascii_uppercase() as u8 - b'A') as usize];
+            return Some((Letter(letter), c.is_ascii_uppercase()));
+        }
+        '0'..='9' => (Digit(c as u8 - b'0'), false),
+        ' ' => (Space, false),
+        '\n' => (Enter, false),
+        '\t' => (Tab, false),
+        ',' => (Symbol(Comma), false),
+        '.' => (Symbol(Period), false),
+        ';' => (Symbol(Semicolon), false),
+        '\'' => (Symbol(Quote), false),
+        '`' => (Symbol(Backtick), false),
+        '-' => (Symbol(Minus), false),
+        '=' => (Symbol(Equal), false),
+        '[' => (Symbol(LBracket), false),
+        ']' => (Symbol(RBracket), false),
+        '\\' => (Symbol(Backslash), false),
+        '/' => (Symbol(Slash), false),
+        '!' => (Digit(1), true),
+        '@' => (Digit(2), true),
+        '#' => (Digit(3), true),
+        '$' => (Digit(4), true),
+        '%' => (Digit(5), true),
+        '^' => (Digit(6), true),
+        '&' => (Digit(7), true),
+        '*' => (Digit(8), true),
+        '(' => (Digit(9), true),
+        ')' => (Digit(0), true),
+        '<' => (Symbol(Comma), true),
+        '>' => (Symbol(Period), true),
+        ':' => (Symbol(Semicolon), true),
+        '"' => (Symbol(Quote), true),
+        '~' => (Symbol(Backtick), true),
+        '_' => (Symbol(Minus), true),
+        '+' => (Symbol(Equal), true),
+        '{' => (Symbol(LBracket), true),
+        '}' => (Symbol(RBracket), true),
+        '|' => (Symbol(Backslash), true),
+        '?' => (Symbol(Slash), true),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Synthesizes evenly spaced keystrokes for `text`. Carriage returns are ignored so CRLF
+/// files replay the same as LF ones.
+pub fn parse_text(text: &str) -> Result<Vec<KeyEvent>> {
+    let mut events = Vec::with_capacity(text.len() * 2);
+    let mut start = Duration::ZERO;
+
+    for (line_no, line) in text.split_inclusive('\n').enumerate() {
+        for (column, c) in line.chars().filter(|&c| c != '\r').enumerate() {
+            let (key, shift) = char_to_key(c).ok_or_else(|| {
+                KeyLoggerError::InvalidReplay(format!(
+                    "line {}, column {}: cannot type {c:?}",
+                    line_no + 1,
+                    column + 1
+                ))
+            })?;
+
+            if shift {
+                events.push(KeyEvent::press(KeyId::ShiftL, start));
+            }
+            let down = if shift { start + MODIFIER_LEAD } else { start };
+            events.push(KeyEvent::press(key, down));
+            events.push(KeyEvent::release(key, down + HOLD_DURATION));
+            if shift {
+                events.push(KeyEvent::release(
+                    KeyId::ShiftL,
+                    down + HOLD_DURATION + MODIFIER_LEAD,
+                ));
+            }
+            start += KEYSTROKE_INTERVAL;
+        }
+    }
+    Ok(events)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScriptKind {
+    Press,
+    Release,
+    Repeat,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptEvent {
+    key: String,
+    kind: ScriptKind,
+    time_ms: u64,
+    #[serde(default)]
+    device: Option<String>,
+}
+
+/// Parses a JSON event list. Events are replayed in timestamp order; ties keep file order.
+pub fn parse_json(json: &str) -> Result<Vec<KeyEvent>> {
+    let script: Vec<ScriptEvent> = serde_json::from_str(json)
+        .map_err(|e| KeyLoggerError::InvalidReplay(format!("invalid event list: {e}")))?;
+
+    let mut events = script
+        .into_iter()
+        .enumerate()
+        .map(|(index, event)| {
+            let key = KeyId::from_label(&event.key).ok_or_else(|| {
+                KeyLoggerError::InvalidReplay(format!(
+                    "event {}: unknown key '{}'",
+                    index + 1,
+                    event.key
+                ))
+            })?;
+            let timestamp = Duration::from_millis(event.time_ms);
+            let key_event = match event.kind {
+                ScriptKind::Press => KeyEvent::press(key, timestamp),
+                ScriptKind::Release => KeyEvent::release(key, timestamp),
+                ScriptKind::Repeat => KeyEvent::repeat(key, timestamp),
+            };
+            Ok(match event.device {
+                Some(device) => key_event.on_device(devices::intern_device_name(&device)),
+                None => key_event,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    events.sort_by_key(|event| event.timestamp);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bigrams, chords, export,
+        source::{KeyEventKind, key},
+        stats, timing,
+    };
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn replay(events: Vec<KeyEvent>, statistics: &Statistics) {
+        let mut recorder = Recorder::new(statistics.clone());
+        platform_common::run_capture_loop(&mut ReplaySource::new(events), &mut recorder).unwrap();
+    }
+
+    fn read_counts(path: &Path) -> HashMap<String, u64> {
+        let mut reader = csv::Reader::from_path(path).unwrap();
+        reader
+            .records()
+            .map(|r| {
+                let r = r.unwrap();
+                (r[0].to_string(), r[1].parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_text_shift_and_symbols() {
+        let events = parse_text("aB!\r\n").unwrap();
+        let presses: Vec<&str> = events
+            .iter()
+            .filter(|e| e.kind == KeyEventKind::Press)
+            .map(|e| e.key.label())
+            .collect();
+        assert_eq!(presses, ["A", "LeftShift", "B", "LeftShift", "1", "Enter"]);
+
+        // Every press is released, and timestamps never go backwards
+        let releases = events
+            .iter()
+            .filter(|e| e.kind == KeyEventKind::Release)
+            .count();
+        assert_eq!(releases, presses.len());
+        assert!(events.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+
+    #[test]
+    fn test_parse_text_rejects_untypeable() {
+        let err = parse_text("ok\nnö").unwrap_err();
+        assert!(
+            matches!(err, KeyLoggerError::InvalidReplay(ref m) if m.contains("line 2, column 2"))
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let events = parse_json(
+            r#"[
+                {"key": "A", "kind": "release", "time_ms": 90},
+                {"key": "A", "kind": "press", "time_ms": 10},
+                {"key": "B", "kind": "press", "time_ms": 95, "device": "Macro Pad"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                KeyEvent::press(key("A"), Duration::from_millis(10)),
+                KeyEvent::release(key("A"), Duration::from_millis(90)),
+                KeyEvent::press(key("B"), Duration::from_millis(95)).on_device("Macro Pad"),
+            ]
+        );
+
+        assert!(matches!(
+            parse_json(r#"[{"key": "Hyper", "kind": "press", "time_ms": 0}]"#),
+            Err(KeyLoggerError::InvalidReplay(_))
+        ));
+        assert!(matches!(
+            parse_json(r#"[{"key": "A", "kind": "tap", "time_ms": 0}]"#),
+            Err(KeyLoggerError::InvalidReplay(_))
+        ));
+    }
+
+    #[test]
+    fn test_text_replay_end_to_end() {
+        let statistics = Statistics::new().with_bigrams().with_timing().with_chords();
+        replay(parse_text("the Theme").unwrap(), &statistics);
+
+        let keys = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let path = export::export_to_csv_with_path(
+            &keys,
+            &HashMap::new(),
+            &crate::privacy::Privacy::default(),
+            Some(temp_dir.path()),
+            None,
+            &export::ExportNames::now(
+                crate::config::DEFAULT_FILENAME_PATTERN,
+                Some(temp_dir.path()),
+            ),
+        )
+        .unwrap();
+
+        let counts = read_counts(&path);
+        assert_eq!(counts["T"], 2);
+        assert_eq!(counts["H"], 2);
+        assert_eq!(counts["E"], 3);
+        assert_eq!(counts["M"], 1);
+        assert_eq!(counts["Space"], 1);
+        assert_eq!(counts["LeftShift"], 1);
+
+        let bigram_snapshot = bigrams::get_bigram_snapshot(statistics.bigrams.as_ref().unwrap());
+        assert_eq!(bigram_snapshot.unwrap()[&("T", "H")], 2);
+
+        let timing_snapshot = timing::get_timing_snapshot(statistics.timing.as_ref().unwrap());
+        let timing_snapshot = timing_snapshot.unwrap();
+        assert_eq!(timing_snapshot.hold["E"].total(), 3);
+        // One interval between each pair of consecutive presses
+        assert_eq!(timing_snapshot.interval.total(), 9);
+
+        let chord_snapshot = chords::get_chord_snapshot(statistics.chords.as_ref().unwrap());
+        let chord_snapshot = chord_snapshot.unwrap();
+        assert_eq!(chord_snapshot.len(), 1);
+        assert_eq!(chord_snapshot.values().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn test_replay_from_file_honours_exclusions() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("script.json");
+        std::fs::write(
+            &path,
+            r#"[
+                {"key": "Numpad1", "kind": "press", "time_ms": 0},
+                {"key": "Numpad1", "kind": "release", "time_ms": 50},
+                {"key": "Q", "kind": "press", "time_ms": 100},
+                {"key": "Q", "kind": "release", "time_ms": 150}
+            ]"#,
+        )
+        .unwrap();
+
+        let config = Config {
+            exclude_keys: ["Numpad1"].into(),
+            ..Config::default()
+        };
+        let statistics = Statistics::new();
+        let source = ReplaySource::from_file(&path).unwrap();
+        run_replay(source, statistics.clone(), &config).unwrap();
+
+        let keys = stats::get_statistics_snapshot(statistics.keys.as_ref().unwrap()).unwrap();
+        assert_eq!(keys, HashMap::from([("Q", 1)]));
+    }
+
+    #[test]
+    fn test_from_file_error_names_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("script.txt");
+        std::fs::write(&path, "€").unwrap();
+
+        let err = ReplaySource::from_file(&path).unwrap_err();
+        assert!(matches!(err, KeyLoggerError::InvalidReplay(ref m) if m.contains("script.txt")));
+    }
+}
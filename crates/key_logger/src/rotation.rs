@@ -0,0 +1,130 @@
+//! Time-based rotation periods of the exported statistics.
+//!
+//! A week-long session would otherwise end in a single CSV. With rotation enabled, the
+//! flush thread (see `flush`) exports everything counted so far to `keylog_<period>.csv`
+//! whenever a day or hour boundary passes and resets the counters.
+
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, Timelike};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Daily,
+    Hourly,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 2] = [Rotation::Daily, Rotation::Hourly];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Rotation::Daily => "daily",
+            Rotation::Hourly => "hourly",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label.trim();
+        Self::ALL
+            .into_iter()
+            .find(|r| r.label().eq_ignore_ascii_case(label))
+    }
+
+    /// strftime format of the period in rotated file names
+    const fn period_format(self) -> &'static str {
+        match self {
+            Rotation::Daily => "%Y-%m-%d",
+            Rotation::Hourly => "%Y-%m-%d_%H",
+        }
+    }
+
+    /// Name of the period containing `time`, e.g. `2025-07-27` or `2025-07-27_14`
+    pub fn period_label(self, time: DateTime<Local>) -> String {
+        time.format(self.period_format()).to_string()
+    }
+
+    /// Start of the period containing `time`
+    pub fn period_start(self, time: DateTime<Local>) -> DateTime<Local> {
+        let start = match self {
+            Rotation::Daily => time
+                .date_naive()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest(),
+            Rotation::Hourly => time
+                .with_nanosecond(0)
+                .and_then(|t| t.with_second(0))
+                .and_then(|t| t.with_minute(0)),
+        };
+        // Midnight can fall into a DST gap; the period then starts at the first valid instant
+        start.unwrap_or(time)
+    }
+
+    /// Start of the period following the one containing `time`
+    pub fn next_boundary(self, time: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            Rotation::Daily => {
+                let tomorrow = time.date_naive() + TimeDelta::days(1);
+                tomorrow
+                    .and_time(NaiveTime::MIN)
+                    .and_local_timezone(Local)
+                    .earliest()
+                    .unwrap_or_else(|| self.period_start(time) + TimeDelta::days(1))
+            }
+            Rotation::Hourly => self.period_start(time) + TimeDelta::hours(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_labels() {
+        for rotation in Rotation::ALL {
+            assert_eq!(Rotation::from_label(rotation.label()), Some(rotation));
+        }
+        assert_eq!(Rotation::from_label(" Hourly "), Some(Rotation::Hourly));
+        assert_eq!(Rotation::from_label("weekly"), None);
+    }
+
+    #[test]
+    fn test_daily_periods() {
+        let time = local(2025, 7, 27, 14, 30, 5);
+        assert_eq!(Rotation::Daily.period_label(time), "2025-07-27");
+        assert_eq!(
+            Rotation::Daily.period_start(time),
+            local(2025, 7, 27, 0, 0, 0)
+        );
+        assert_eq!(
+            Rotation::Daily.next_boundary(time),
+            local(2025, 7, 28, 0, 0, 0)
+        );
+        assert_eq!(
+            Rotation::Daily.next_boundary(local(2025, 12, 31, 23, 59, 59)),
+            local(2026, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_hourly_periods() {
+        let time = local(2025, 7, 27, 14, 30, 5);
+        assert_eq!(Rotation::Hourly.period_label(time), "2025-07-27_14");
+        assert_eq!(
+            Rotation::Hourly.period_start(time),
+            local(2025, 7, 27, 14, 0, 0)
+        );
+        assert_eq!(
+            Rotation::Hourly.next_boundary(time),
+            local(2025, 7, 27, 15, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Opt-in typing session statistics.
+//!
+//! Presses are grouped into sessions separated by idle gaps: a pause of at least the
+//! configured gap ends the current session and the next press starts a new one. Each
+//! session keeps only its span and per-key press counts, never what was typed or in
+//! which order.
+
+use crate::{clock, error::Result};
+use chrono::{DateTime, Local, TimeDelta};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// One burst of activity between idle gaps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub start: DateTime<Local>,
+    /// Time of the last press
+    pub end: DateTime<Local>,
+    pub counts: HashMap<&'static str, u64>,
+}
+
+impl Session {
+    pub fn keystrokes(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+
+    /// `None` for a session of a single instant, e.g. one key press
+    pub fn keys_per_minute(&self) -> Option<f64> {
+        let millis = self.duration().num_milliseconds();
+        (millis > 0).then(|| self.keystrokes() as f64 * 60_000.0 / millis as f64)
+    }
+}
+
+/// Closed sessions and the one still being typed.
+#[derive(Debug)]
+pub struct SessionLog {
+    gap: Duration,
+    /// Source timestamp and wall-clock time of the first press, which anchor all others
+    origin: Option<(Duration, DateTime<Local>)>,
+    closed: Vec<Session>,
+    /// Open session with the source timestamp of its last press
+    current: Option<(Session, Duration)>,
+}
+
+impl SessionLog {
+    fn record(&mut self, key: &'static str, timestamp: Duration) {
+        // Source timestamps only have meaning relative to each other
+        let (origin, origin_time) = *self.origin.get_or_insert_with(|| (timestamp, clock::now()));
+        let time =
+            origin_time + TimeDelta::from_std(timestamp.saturating_sub(origin)).unwrap_or_default();
+
+        match self.current {
+            Some((ref mut session, ref mut last)) if timestamp.saturating_sub(*last) < self.gap => {
+                if timestamp > *last {
+                    *last = timestamp;
+                    session.end = time;
+                }
+                *session.counts.entry(key).or_insert(0) += 1;
+            }
+            _ => {
+                self.close();
+                let session = Session {
+                    start: time,
+                    end: time,
+                    counts: HashMap::from([(key, 1)]),
+                };
+                self.current = Some((session, timestamp));
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some((session, _)) = self.current.take() {
+            self.closed.push(session);
+        }
+    }
+}
+
+pub type SessionStatistics = Arc<Mutex<SessionLog>>;
+
+/// Sessions end after `gap` without a key press.
+pub fn create_session_statistics(gap: Duration) -> SessionStatistics {
+    Arc::new(Mutex::new(SessionLog {
+        gap,
+        origin: None,
+        closed: Vec::new(),
+        current: None,
+    }))
+}
+
+/// Every session so far, oldest first, including the open one.
+pub fn get_session_snapshot(sessions: &SessionStatistics) -> Result<Vec<Session>> {
+    let guard = sessions.lock().unwrap_or_else(|p| p.into_inner());
+    let mut snapshot = guard.closed.clone();
+    snapshot.extend(guard.current.as_ref().map(|(session, _)| session.clone()));
+    Ok(snapshot)
+}
+
+/// Returns every session and forgets them under a single lock.
+///
+/// The open session is closed as well, so a flush or rotation always ends a session.
+pub fn take_session_snapshot(sessions: &SessionStatistics) -> Result<Vec<Session>> {
+    let mut guard = sessions.lock().unwrap_or_else(|p| p.into_inner());
+    guard.close();
+    Ok(std::mem::take(&mut guard.closed))
+}
+
+/// Puts previously taken sessions back in front of the newer ones (e.g. after a failed export).
+pub fn merge_sessions(sessions: &SessionStatistics, taken: &[Session]) -> Result<()> {
+    let mut guard = sessions.lock().unwrap_or_else(|p| p.into_inner());
+    guard.closed.splice(0..0, taken.iter().cloned());
+    Ok(())
+}
+
+pub fn add_many<I>(sessions: &SessionStatistics, presses: I) -> Result<()>
+where
+    I: IntoIterator<Item = (&'static str, Duration)>,
+{
+    let mut guard = sessions.lock().unwrap_or_else(|p| p.into_inner());
+    for (key, timestamp) in presses {
+        guard.record(key, timestamp);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn test_idle_gap_splits_sessions() {
+        let sessions = create_session_statistics(s(60));
+        add_many(&sessions, [("A", s(0)), ("B", s(30)), ("A", s(89))]).unwrap();
+        // Exactly one gap later starts a new session
+        add_many(&sessions, [("W", s(149)), ("W", s(150))]).unwrap();
+        add_many(&sessions, [("Space", s(1000))]).unwrap();
+
+        let snapshot = get_session_snapshot(&sessions).unwrap();
+        assert_eq!(snapshot.len(), 3);
+
+        assert_eq!(snapshot[0].counts, HashMap::from([("A", 2), ("B", 1)]));
+        assert_eq!(snapshot[0].duration(), TimeDelta::seconds(89));
+        assert_eq!(snapshot[0].keystrokes(), 3);
+        let kpm = snapshot[0].keys_per_minute().unwrap();
+        assert!((kpm - 3.0 * 60.0 / 89.0).abs() < 1e-9);
+
+        assert_eq!(snapshot[1].counts, HashMap::from([("W", 2)]));
+        assert_eq!(
+            snapshot[1].start - snapshot[0].start,
+            TimeDelta::seconds(149)
+        );
+        assert_eq!(snapshot[1].duration(), TimeDelta::seconds(1));
+        // A single press has no rate
+        assert_eq!(snapshot[2].keys_per_minute(), None);
+    }
+
+    #[test]
+    fn test_take_closes_open_session() {
+        let sessions = create_session_statistics(s(60));
+        add_many(&sessions, [("A", s(0))]).unwrap();
+
+        let taken = take_session_snapshot(&sessions).unwrap();
+        assert_eq!(taken.len(), 1);
+        assert!(get_session_snapshot(&sessions).unwrap().is_empty());
+
+        // Continuing to type right after the flush starts a fresh session
+        add_many(&sessions, [("B", s(1))]).unwrap();
+        merge_sessions(&sessions, &taken).unwrap();
+        let snapshot = get_session_snapshot(&sessions).unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].counts, HashMap::from([("A", 1)]));
+        assert_eq!(snapshot[1].counts, HashMap::from([("B", 1)]));
+    }
+}
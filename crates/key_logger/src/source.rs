@@ -0,0 +1,121 @@
+use crate::error::Result;
+use key_id::KeyId;
+use std::time::Duration;
+
+/// Direction of a key transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEventKind {
+    Press,
+    Release,
+    /// Auto-repeat or duplicate press of a key that is already held
+    Repeat,
+    /// The source lost track of a held key (its device was unplugged or dropped events),
+    /// so its release will never come
+    Lost,
+}
+
+/// A single key transition reported by a [`KeySource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: KeyId,
+    pub kind: KeyEventKind,
+    /// Time since a source-specific origin; only differences between events are meaningful
+    pub timestamp: Duration,
+    /// Name of the keyboard that sent the event, for sources that can tell them apart
+    pub device: Option<&'static str>,
+}
+
+impl KeyEvent {
+    pub fn press(key: KeyId, timestamp: Duration) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Press,
+            timestamp,
+            device: None,
+        }
+    }
+
+    pub fn release(key: KeyId, timestamp: Duration) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Release,
+            timestamp,
+            device: None,
+        }
+    }
+
+    pub fn repeat(key: KeyId, timestamp: Duration) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Repeat,
+            timestamp,
+            device: None,
+        }
+    }
+
+    pub fn lost(key: KeyId, timestamp: Duration) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Lost,
+            timestamp,
+            device: None,
+        }
+    }
+
+    /// Attributes the event to the named device.
+    pub fn on_device(mut self, device: &'static str) -> Self {
+        self.device = Some(device);
+        self
+    }
+}
+
+/// Anything that can produce key transitions: the polling backend, evdev, or a script.
+pub trait KeySource {
+    /// Appends newly observed events to `events`.
+    ///
+    /// Implementations may block, but should return within roughly one polling interval
+    /// so the capture loop can react to exit requests. Returns `Ok(false)` once the source
+    /// is exhausted and will never produce more events.
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Result<bool>;
+
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+}
+
+/// Yields a fixed list of events in one batch, then reports exhaustion.
+#[cfg(test)]
+pub struct ScriptedSource {
+    events: Option<Vec<KeyEvent>>,
+}
+
+#[cfg(test)]
+impl ScriptedSource {
+    pub fn new(events: impl IntoIterator<Item = KeyEvent>) -> Self {
+        Self {
+            events: Some(events.into_iter().collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl KeySource for ScriptedSource {
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Result<bool> {
+        match self.events.take() {
+            Some(script) => {
+                events.extend(script);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+}
+
+/// The key labelled `label`, for tests that spell out their events.
+#[cfg(test)]
+pub fn key(label: &str) -> KeyId {
+    KeyId::from_label(label).unwrap_or_else(|| panic!("unknown key label {label:?}"))
+}
@@ -1,38 +1,128 @@
-use crate::error::Result;
+use crate::error::{KeyLoggerError, Result};
+use key_id::KeyId;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
-// Initial capacity for the statistics HashMap to avoid frequent reallocations
+// Initial capacity of snapshots to avoid rehashing while they are built
 // This covers most common keys: 26 letters + 10 digits + 20+ special keys + modifiers
-const INITIAL_STATISTICS_CAPACITY: usize = 64;
+const INITIAL_SNAPSHOT_CAPACITY: usize = 64;
+
+/// Label of every counter, in [`KeyId::index`] order
+static KEY_LABELS: LazyLock<Vec<&'static str>> =
+    LazyLock::new(|| KeyId::all().map(KeyId::label).collect());
+
+/// One counter per [`KeyId`], indexed by [`KeyId::index`].
+///
+/// Recording never takes a lock, so the status socket, autosave and the TUI can read
+/// while the capture loop counts. Counters are independent: a snapshot taken while a
+/// batch is being added may include only part of that batch, but a press is never lost
+/// or counted twice.
+#[derive(Debug)]
+pub struct KeyCounters {
+    counts: [AtomicU64; KeyId::COUNT],
+}
+
+impl KeyCounters {
+    pub fn new() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
 
-// Using a more efficient hash map for better performance
-type StatisticsMap = HashMap<&'static str, u64>;
+    /// Adds `count` presses of `key`; recording a press is an index and an add.
+    pub fn add_key(&self, key: KeyId, count: u64) {
+        self.counts[key.index()].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Adds `count` presses of the key labelled `key`, which must be a canonical
+    /// [`KeyId`] label, e.g. when restoring counts saved by an earlier run.
+    pub fn add(&self, key: &str, count: u64) -> Result<()> {
+        let key = KeyId::from_label(key)
+            .filter(|id| id.label() == key)
+            .ok_or_else(|| KeyLoggerError::UnknownKey(key.to_string()))?;
+        self.add_key(key, count);
+        Ok(())
+    }
 
-pub type KeyStatistics = Arc<Mutex<StatisticsMap>>;
+    /// Keys with a non-zero count, reading each counter with `read`
+    fn collect(&self, read: impl Fn(&AtomicU64) -> u64) -> HashMap<&'static str, u64> {
+        let mut counts = HashMap::with_capacity(INITIAL_SNAPSHOT_CAPACITY);
+        for (&label, counter) in KEY_LABELS.iter().zip(&self.counts) {
+            let count = read(counter);
+            if count > 0 {
+                counts.insert(label, count);
+            }
+        }
+        counts
+    }
+}
+
+impl Default for KeyCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type KeyStatistics = Arc<KeyCounters>;
+
+/// Returns the static label equal to `label`, if it is a canonical [`KeyId`] label.
+pub fn intern_key_label(label: &str) -> Option<&'static str> {
+    KeyId::from_label(label)
+        .map(KeyId::label)
+        .filter(|&canonical| canonical == label)
+}
 
 pub fn create_statistics() -> KeyStatistics {
-    // Pre-allocate capacity for common keys to improve performance
-    Arc::new(Mutex::new(HashMap::with_capacity(
-        INITIAL_STATISTICS_CAPACITY,
-    )))
+    Arc::new(KeyCounters::new())
 }
 
 pub fn get_statistics_snapshot(stats: &KeyStatistics) -> Result<HashMap<&'static str, u64>> {
-    Ok(stats.lock().unwrap_or_else(|p| p.into_inner()).clone())
+    Ok(stats.collect(|counter| counter.load(Ordering::Relaxed)))
+}
+
+/// Returns the counts and resets them to zero.
+///
+/// Each counter is swapped out atomically, so a press recorded meanwhile lands either in
+/// the returned counts or in the fresh ones.
+pub fn take_statistics_snapshot(stats: &KeyStatistics) -> Result<HashMap<&'static str, u64>> {
+    Ok(stats.collect(|counter| counter.swap(0, Ordering::Relaxed)))
+}
+
+/// The `n` most pressed keys, most pressed first; ties are ordered by label.
+pub fn top_keys(snapshot: &HashMap<&'static str, u64>, n: usize) -> Vec<(&'static str, u64)> {
+    let mut rows: Vec<(&'static str, u64)> = snapshot.iter().map(|(&k, &v)| (k, v)).collect();
+    rows.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    rows.truncate(n);
+    rows
 }
 
-pub fn add_many<I>(stats: &KeyStatistics, keys: I) -> Result<()>
+/// Counts one press of each key.
+pub fn add_many<I>(stats: &KeyStatistics, keys: I)
 where
-    I: IntoIterator<Item = &'static str>,
+    I: IntoIterator<Item = KeyId>,
 {
-    let mut guard = stats.lock().unwrap_or_else(|p| p.into_inner());
     for key in keys {
-        *guard.entry(key).or_insert(0) += 1;
+        stats.add_key(key, 1);
     }
-    Ok(())
+}
+
+/// Adds previously recorded counts (e.g. from a checkpoint) to the statistics.
+///
+/// A label that is not canonical is skipped, and the first one is returned as an error
+/// once the rest are counted.
+pub fn merge_counts(stats: &KeyStatistics, counts: &HashMap<&'static str, u64>) -> Result<()> {
+    let mut unknown = Ok(());
+    for (&key, &count) in counts {
+        if let Err(e) = stats.add(key, count) {
+            unknown = unknown.and(Err(e));
+        }
+    }
+    unknown
 }
 
 #[cfg(test)]
@@ -41,10 +131,12 @@ mod tests {
     use std::sync::Arc;
     use std::thread;
 
+    fn key(label: &str) -> KeyId {
+        KeyId::from_label(label).unwrap()
+    }
+
     fn add_key_press(stats: &KeyStatistics, key: &'static str) -> Result<()> {
-        let mut guard = stats.lock().unwrap_or_else(|p| p.into_inner());
-        *guard.entry(key).or_insert(0) += 1;
-        Ok(())
+        stats.add(key, 1)
     }
 
     #[test]
@@ -54,6 +146,24 @@ mod tests {
         assert!(snapshot.is_empty());
     }
 
+    #[test]
+    fn test_unknown_labels_are_reported() {
+        let stats = create_statistics();
+
+        assert!(matches!(
+            stats.add("Space ", 1),
+            Err(KeyLoggerError::UnknownKey(ref key)) if key == "Space "
+        ));
+        assert!(matches!(
+            merge_counts(&stats, &HashMap::from([("A", 1), ("key1", 1), ("B", 1)])),
+            Err(KeyLoggerError::UnknownKey(ref key)) if key == "key1"
+        ));
+
+        // The canonical labels of the batch are still counted
+        let snapshot = get_statistics_snapshot(&stats).unwrap();
+        assert_eq!(snapshot, HashMap::from([("A", 1), ("B", 1)]));
+    }
+
     #[test]
     fn test_add_key_press() {
         let stats = create_statistics();
@@ -71,8 +181,8 @@ mod tests {
     fn test_add_many_function() {
         let stats = create_statistics();
 
-        let keys = vec!["A", "B", "A", "C", "A"];
-        add_many(&stats, keys).unwrap();
+        let keys = ["A", "B", "A", "C", "A"];
+        add_many(&stats, keys.map(key));
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
         assert_eq!(snapshot.get("A"), Some(&3));
@@ -84,8 +194,8 @@ mod tests {
     fn test_add_many_empty_iterator() {
         let stats = create_statistics();
 
-        let keys: Vec<&'static str> = vec![];
-        add_many(&stats, keys).unwrap();
+        let keys: Vec<KeyId> = vec![];
+        add_many(&stats, keys);
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
         assert!(snapshot.is_empty());
@@ -96,8 +206,8 @@ mod tests {
         let stats = create_statistics();
 
         // Test with large numbers to ensure u64 handling
-        let large_keys: Vec<&'static str> = (0..10000).map(|_| "Space").collect();
-        add_many(&stats, large_keys).unwrap();
+        let large_keys: Vec<KeyId> = (0..10000).map(|_| KeyId::Space).collect();
+        add_many(&stats, large_keys);
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
         assert_eq!(snapshot.get("Space"), Some(&10000));
@@ -128,6 +238,34 @@ mod tests {
         assert_eq!(snapshot.get("A"), Some(&2000));
     }
 
+    #[test]
+    fn test_take_while_recording_loses_nothing() {
+        let stats = create_statistics();
+        let writer = {
+            let stats = Arc::clone(&stats);
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    add_many(&stats, [key("A"), KeyId::Space]);
+                }
+            })
+        };
+
+        let mut taken: HashMap<&'static str, u64> = HashMap::new();
+        while !writer.is_finished() {
+            for (key, count) in take_statistics_snapshot(&stats).unwrap() {
+                *taken.entry(key).or_insert(0) += count;
+            }
+        }
+        writer.join().unwrap();
+        for (key, count) in take_statistics_snapshot(&stats).unwrap() {
+            *taken.entry(key).or_insert(0) += count;
+        }
+
+        assert_eq!(taken.get("A"), Some(&10_000));
+        assert_eq!(taken.get("Space"), Some(&10_000));
+        assert!(get_statistics_snapshot(&stats).unwrap().is_empty());
+    }
+
     #[test]
     fn test_special_characters_cross_platform() {
         let stats = create_statistics();
@@ -159,7 +297,7 @@ mod tests {
             "RightBracket",
         ];
 
-        add_many(&stats, special_keys.iter().copied()).unwrap();
+        add_many(&stats, special_keys.iter().copied().map(key));
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
 
@@ -208,7 +346,7 @@ mod tests {
             })
             .collect();
 
-        add_many(&stats, alphabet.iter().copied()).unwrap();
+        add_many(&stats, alphabet.iter().copied().map(key));
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
         assert_eq!(snapshot.len(), 26);
@@ -228,13 +366,37 @@ mod tests {
 
         let snapshot = get_statistics_snapshot(&stats).unwrap();
 
-        // Check that the key in the map is the same static string reference
+        // Snapshots hand out the interned canonical label rather than allocating
+        let canonical = KeyId::Space.label();
         for (key, _) in snapshot.iter() {
             if *key == "Space" {
-                // This should be the same pointer for static strings
-                assert_eq!(key.as_ptr(), test_key.as_ptr());
+                assert_eq!(key.as_ptr(), canonical.as_ptr());
                 break;
             }
         }
     }
+
+    #[test]
+    fn test_merge_counts() {
+        let stats = create_statistics();
+        add_many(&stats, ["A", "B"].map(key));
+
+        let mut previous = HashMap::new();
+        previous.insert("A", 10);
+        previous.insert("Space", 3);
+        merge_counts(&stats, &previous).unwrap();
+
+        let snapshot = get_statistics_snapshot(&stats).unwrap();
+        assert_eq!(snapshot.get("A"), Some(&11));
+        assert_eq!(snapshot.get("B"), Some(&1));
+        assert_eq!(snapshot.get("Space"), Some(&3));
+    }
+
+    #[test]
+    fn test_intern_key_label() {
+        let interned = intern_key_label(&String::from("LeftShift")).unwrap();
+        assert_eq!(interned, "LeftShift");
+        assert_eq!(intern_key_label("NotAKey"), None);
+        assert_eq!(intern_key_label("leftshift"), None);
+    }
 }
@@ -0,0 +1,372 @@
+//! Read-only status endpoint on a Unix domain socket.
+//!
+//! Clients send one JSON query per line and get one JSON object back per line, e.g.
+//! `{"query": "top", "n": 5}`. Only aggregates from the key count snapshot are exposed;
+//! counts are those since the last flush or rotation.
+
+use crate::{
+    clock,
+    error::{KeyLoggerError, Result},
+    platform_common, stats,
+    stats::KeyStatistics,
+};
+use chrono::{DateTime, Local};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+const DEFAULT_TOP: usize = 10;
+// How often the listener checks for shutdown while no client connects
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(200);
+// A client that goes quiet this long is disconnected
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "query", rename_all = "snake_case", deny_unknown_fields)]
+enum Query {
+    /// Everything below in one answer
+    Status {
+        #[serde(default = "default_top")]
+        top: usize,
+    },
+    Totals,
+    Top {
+        #[serde(default = "default_top")]
+        n: usize,
+    },
+    Uptime,
+    Paused,
+}
+
+fn default_top() -> usize {
+    DEFAULT_TOP
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_presses: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unique_keys: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top: Option<Vec<KeyCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uptime_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paused: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct KeyCount {
+    key: &'static str,
+    count: u64,
+}
+
+/// What the endpoint reads from; cheap to clone into each client thread.
+#[derive(Debug, Clone)]
+struct Context {
+    keys: Option<KeyStatistics>,
+    started: DateTime<Local>,
+}
+
+impl Context {
+    fn answer(&self, line: &str) -> Response {
+        let query = match serde_json::from_str::<Query>(line) {
+            Ok(query) => query,
+            Err(e) => {
+                return Response {
+                    error: Some(format!("Invalid query: {e}")),
+                    ..Response::default()
+                };
+            }
+        };
+
+        let needs_keys = !matches!(query, Query::Uptime | Query::Paused);
+        let snapshot = match (&self.keys, needs_keys) {
+            (Some(keys), true) => match stats::get_statistics_snapshot(keys) {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    return Response {
+                        error: Some(e.to_string()),
+                        ..Response::default()
+                    };
+                }
+            },
+            (None, true) => {
+                return Response {
+                    error: Some("Key counting is disabled".to_string()),
+                    ..Response::default()
+                };
+            }
+            (_, false) => None,
+        };
+        let totals = |response: &mut Response| {
+            if let Some(ref snapshot) = snapshot {
+                response.total_presses = Some(snapshot.values().sum());
+                response.unique_keys = Some(snapshot.len());
+            }
+        };
+        let top = |n: usize| {
+            snapshot.as_ref().map(|snapshot| {
+                stats::top_keys(snapshot, n)
+                    .into_iter()
+                    .map(|(key, count)| KeyCount { key, count })
+                    .collect()
+            })
+        };
+        let uptime = || (clock::now() - self.started).num_seconds().max(0) as u64;
+
+        let mut response = Response::default();
+        match query {
+            Query::Status { top: n } => {
+                totals(&mut response);
+                response.top = top(n);
+                response.uptime_secs = Some(uptime());
+                response.paused = Some(platform_common::is_paused());
+            }
+            Query::Totals => totals(&mut response),
+            Query::Top { n } => response.top = top(n),
+            Query::Uptime => response.uptime_secs = Some(uptime()),
+            Query::Paused => response.paused = Some(platform_common::is_paused()),
+        }
+        response
+    }
+
+    /// Answers queries from one client until it disconnects or times out.
+    fn serve_client(&self, stream: UnixStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.answer(&line);
+            let mut json = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+            json.push('\n');
+            writer.write_all(json.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Binds `path` (owner-only permissions) and starts answering status queries until exit.
+///
+/// A leftover socket file from a crashed run is replaced, but one that still has a
+/// listener is not, so two loggers never fight over the same endpoint. Anything at `path`
+/// that is not a socket is left alone and reported as an error.
+pub fn spawn_status_server(
+    path: PathBuf,
+    keys: Option<KeyStatistics>,
+    started: DateTime<Local>,
+) -> Result<thread::JoinHandle<()>> {
+    let listener = bind(&path)?;
+    listener.set_nonblocking(true)?;
+    info!("Status socket listening on {}", path.display());
+
+    let context = Context { keys, started };
+    let handle = thread::Builder::new()
+        .name("status".into())
+        .spawn(move || {
+            while !platform_common::should_exit() {
+                match listener.accept() {
+                    Ok((stream, _)) => serve_in_thread(stream, context.clone()),
+                    Err(e) => {
+                        if e.kind() != ErrorKind::WouldBlock {
+                            warn!("Status socket accept failed: {e}");
+                        }
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                }
+            }
+            let _ = std::fs::remove_file(&path);
+        })?;
+    Ok(handle)
+}
+
+fn serve_in_thread(stream: UnixStream, context: Context) {
+    let spawned = thread::Builder::new()
+        .name("status-client".into())
+        .spawn(move || {
+            // Accepted sockets inherit non-blocking mode from the listener
+            let served = stream
+                .set_nonblocking(false)
+                .and_then(|()| context.serve_client(stream));
+            if let Err(e) = served {
+                debug!("Status client disconnected: {e}");
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to serve status client: {e}");
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(KeyLoggerError::InvalidConfiguration(format!(
+                "Status socket path {} exists and is not a socket",
+                path.display()
+            )));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(KeyLoggerError::InvalidConfiguration(format!(
+                "Status socket {} is in use by another process",
+                path.display()
+            )));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    // Bound inside an owner-only directory and moved into place once it is 0600, so no
+    // other user can connect in the window before the permissions are tightened
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let private_dir = path.with_file_name(format!(".{file_name}.{}", std::process::id()));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|e| KeyLoggerError::CreateDir {
+            path: private_dir.clone(),
+            source: e,
+        })?;
+    let private_path = private_dir.join("socket");
+    let bound = UnixListener::bind(&private_path)
+        .and_then(|listener| {
+            std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&private_path, path)?;
+            Ok(listener)
+        })
+        .map_err(|e| KeyLoggerError::CreateFile {
+            path: path.to_path_buf(),
+            source: e,
+        });
+    let _ = std::fs::remove_file(&private_path);
+    let _ = std::fs::remove_dir(&private_dir);
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::key;
+    use tempfile::TempDir;
+
+    fn context() -> Context {
+        let keys = stats::create_statistics();
+        stats::add_many(&keys, ["E", "E", "T", "A", "E", "T"].map(key));
+        Context {
+            keys: Some(keys),
+            started: Local::now() - chrono::TimeDelta::seconds(90),
+        }
+    }
+
+    fn ask(context: &Context, line: &str) -> serde_json::Value {
+        serde_json::to_value(context.answer(line)).unwrap()
+    }
+
+    #[test]
+    fn test_queries() {
+        let context = context();
+
+        let totals = ask(&context, r#"{"query": "totals"}"#);
+        assert_eq!(totals["total_presses"], 6);
+        assert_eq!(totals["unique_keys"], 3);
+        assert!(totals.get("top").is_none());
+
+        let top = ask(&context, r#"{"query": "top", "n": 2}"#);
+        assert_eq!(
+            top["top"],
+            serde_json::json!([{"key": "E", "count": 3}, {"key": "T", "count": 2}])
+        );
+
+        let uptime = ask(&context, r#"{"query": "uptime"}"#);
+        assert!(uptime["uptime_secs"].as_u64().unwrap() >= 90);
+
+        let status = ask(&context, r#"{"query": "status"}"#);
+        assert_eq!(status["top"].as_array().unwrap().len(), 3);
+        assert!(status["paused"].is_boolean());
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        let context = context();
+        for line in [
+            "status",
+            r#"{"query": "keys"}"#,
+            r#"{"query": "top", "x": 1}"#,
+        ] {
+            assert!(ask(&context, line)["error"].is_string(), "{line}");
+        }
+
+        let disabled = Context {
+            keys: None,
+            started: Local::now(),
+        };
+        assert!(ask(&disabled, r#"{"query": "totals"}"#)["error"].is_string());
+        assert!(ask(&disabled, r#"{"query": "uptime"}"#)["error"].is_null());
+    }
+
+    #[test]
+    fn test_serve_client() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let context = context();
+        let handle = thread::spawn(move || context.serve_client(server));
+
+        let mut writer = client.try_clone().unwrap();
+        writer
+            .write_all(b"{\"query\": \"totals\"}\n\n{\"query\": \"paused\"}\n")
+            .unwrap();
+        writer.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let lines: Vec<serde_json::Value> = BufReader::new(client)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["total_presses"], 6);
+        assert!(lines[1]["paused"].is_boolean());
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("status.sock");
+
+        let listener = bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(matches!(
+            bind(&path),
+            Err(KeyLoggerError::InvalidConfiguration(_))
+        ));
+
+        drop(listener);
+        bind(&path).unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_bind_keeps_other_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("status.sock");
+        std::fs::write(&path, "not a socket").unwrap();
+
+        assert!(matches!(
+            bind(&path),
+            Err(KeyLoggerError::InvalidConfiguration(_))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+    }
+}
@@ -0,0 +1,195 @@
+//! Hold-duration and inter-key interval histograms.
+//!
+//! Durations are binned into fixed log-scaled buckets, so memory stays constant no matter
+//! how long the logger runs and no individual timestamps are kept.
+
+use crate::error::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const BUCKETS_PER_OCTAVE: u32 = 4;
+// 2^13 ms ≈ 8.2 s; anything longer lands in the overflow bucket
+const OCTAVES: u32 = 13;
+
+/// One underflow bucket (< 1 ms), the log-scaled buckets, and one overflow bucket.
+pub const NUM_BUCKETS: usize = (OCTAVES * BUCKETS_PER_OCTAVE) as usize + 2;
+
+/// Lower bound of bucket `index` in milliseconds.
+pub fn bucket_lower_ms(index: usize) -> f64 {
+    match index {
+        0 => 0.0,
+        i => 2f64.powf((i - 1) as f64 / BUCKETS_PER_OCTAVE as f64),
+    }
+}
+
+/// Upper bound of bucket `index` in milliseconds, `None` for the overflow bucket.
+pub fn bucket_upper_ms(index: usize) -> Option<f64> {
+    (index + 1 < NUM_BUCKETS).then(|| 2f64.powf(index as f64 / BUCKETS_PER_OCTAVE as f64))
+}
+
+fn bucket_index(duration: Duration) -> usize {
+    let ms = duration.as_secs_f64() * 1000.0;
+    if ms < 1.0 {
+        return 0;
+    }
+    let index = (ms.log2() * BUCKETS_PER_OCTAVE as f64).floor() as usize + 1;
+    index.min(NUM_BUCKETS - 1)
+}
+
+/// Fixed-size histogram over log-scaled duration buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; NUM_BUCKETS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; NUM_BUCKETS],
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        self.counts[bucket_index(duration)] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Non-empty buckets as `(index, count)`.
+    pub fn buckets(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(i, &count)| (i, count))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimingData {
+    /// Press-to-release duration per key
+    pub hold: HashMap<&'static str, Histogram>,
+    /// Time between consecutive presses of any keys
+    pub interval: Histogram,
+}
+
+impl TimingData {
+    pub fn is_empty(&self) -> bool {
+        self.hold.is_empty() && self.interval.total() == 0
+    }
+
+    pub fn merge(&mut self, other: &TimingData) {
+        for (&key, histogram) in &other.hold {
+            self.hold.entry(key).or_default().merge(histogram);
+        }
+        self.interval.merge(&other.interval);
+    }
+}
+
+pub type TimingStatistics = Arc<Mutex<TimingData>>;
+
+pub fn create_timing_statistics() -> TimingStatistics {
+    Arc::new(Mutex::new(TimingData::default()))
+}
+
+pub fn get_timing_snapshot(timing: &TimingStatistics) -> Result<TimingData> {
+    Ok(timing.lock().unwrap_or_else(|p| p.into_inner()).clone())
+}
+
+/// Returns the histograms and resets them under a single lock.
+pub fn take_timing_snapshot(timing: &TimingStatistics) -> Result<TimingData> {
+    Ok(std::mem::take(
+        &mut *timing.lock().unwrap_or_else(|p| p.into_inner()),
+    ))
+}
+
+/// Adds previously taken histograms back (e.g. after a failed export).
+pub fn merge_timing(timing: &TimingStatistics, data: &TimingData) -> Result<()> {
+    timing.lock().unwrap_or_else(|p| p.into_inner()).merge(data);
+    Ok(())
+}
+
+/// Records a batch of hold durations and inter-key intervals under a single lock.
+pub fn add_many<H, I>(timing: &TimingStatistics, holds: H, intervals: I) -> Result<()>
+where
+    H: IntoIterator<Item = (&'static str, Duration)>,
+    I: IntoIterator<Item = Duration>,
+{
+    let mut guard = timing.lock().unwrap_or_else(|p| p.into_inner());
+    for (key, duration) in holds {
+        guard.hold.entry(key).or_default().record(duration);
+    }
+    for duration in intervals {
+        guard.interval.record(duration);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds_are_contiguous() {
+        assert_eq!(bucket_lower_ms(0), 0.0);
+        for i in 0..NUM_BUCKETS - 1 {
+            assert_eq!(bucket_upper_ms(i), Some(bucket_lower_ms(i + 1)));
+        }
+        assert_eq!(bucket_upper_ms(NUM_BUCKETS - 1), None);
+    }
+
+    #[test]
+    fn test_bucket_index_matches_bounds() {
+        for ms in [0u64, 1, 2, 3, 10, 95, 100, 150, 400, 1000, 8000] {
+            let index = bucket_index(Duration::from_millis(ms));
+            let ms = ms as f64;
+            assert!(bucket_lower_ms(index) <= ms, "{ms} below bucket {index}");
+            if let Some(upper) = bucket_upper_ms(index) {
+                assert!(ms < upper, "{ms} above bucket {index}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bucket_index_extremes() {
+        assert_eq!(bucket_index(Duration::ZERO), 0);
+        assert_eq!(bucket_index(Duration::from_micros(999)), 0);
+        assert_eq!(bucket_index(Duration::from_millis(1)), 1);
+        assert_eq!(bucket_index(Duration::from_secs(3600)), NUM_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_add_many() {
+        let timing = create_timing_statistics();
+        add_many(
+            &timing,
+            [
+                ("A", Duration::from_millis(92)),
+                ("A", Duration::from_millis(95)),
+                ("Space", Duration::from_millis(120)),
+            ],
+            [Duration::from_millis(150), Duration::from_millis(200)],
+        )
+        .unwrap();
+
+        let snapshot = get_timing_snapshot(&timing).unwrap();
+        assert_eq!(snapshot.hold["A"].total(), 2);
+        assert_eq!(snapshot.hold["A"].buckets().count(), 1);
+        assert_eq!(snapshot.hold["Space"].total(), 1);
+        assert_eq!(snapshot.interval.total(), 2);
+        assert!(!snapshot.is_empty());
+    }
+}
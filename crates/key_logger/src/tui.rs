@@ -0,0 +1,388 @@
+//! Live terminal dashboard: a keyboard heatmap and the top keys.
+//!
+//! Rendering only reads a [`Dashboard`] built from the key count snapshot, so it can be
+//! drawn into ratatui's `TestBackend` in tests; [`spawn_dashboard`] owns the real terminal.
+
+use crate::{clock, platform_common, stats, stats::KeyStatistics};
+use chrono::{DateTime, Local};
+use log::{LevelFilter, error};
+use ratatui::{
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table},
+};
+use std::{collections::HashMap, thread, time::Duration};
+
+// Redraw period; also how long a key press in the terminal may wait to be handled
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+const TOP_KEYS: usize = 10;
+
+/// Rows of the heatmap as (key label, cell width in columns); each row is 60 columns wide.
+const HEATMAP_ROWS: [&[(&str, u16)]; 5] = [
+    &[
+        ("Grave", 4),
+        ("1", 4),
+        ("2", 4),
+        ("3", 4),
+        ("4", 4),
+        ("5", 4),
+        ("6", 4),
+        ("7", 4),
+        ("8", 4),
+        ("9", 4),
+        ("0", 4),
+        ("Minus", 4),
+        ("Equal", 4),
+        ("Backspace", 8),
+    ],
+    &[
+        ("Tab", 6),
+        ("Q", 4),
+        ("W", 4),
+        ("E", 4),
+        ("R", 4),
+        ("T", 4),
+        ("Y", 4),
+        ("U", 4),
+        ("I", 4),
+        ("O", 4),
+        ("P", 4),
+        ("LeftBracket", 4),
+        ("RightBracket", 4),
+        ("Backslash", 6),
+    ],
+    &[
+        ("CapsLock", 7),
+        ("A", 4),
+        ("S", 4),
+        ("D", 4),
+        ("F", 4),
+        ("G", 4),
+        ("H", 4),
+        ("J", 4),
+        ("K", 4),
+        ("L", 4),
+        ("Semicolon", 4),
+        ("Apostrophe", 4),
+        ("Enter", 9),
+    ],
+    &[
+        ("LeftShift", 9),
+        ("Z", 4),
+        ("X", 4),
+        ("C", 4),
+        ("V", 4),
+        ("B", 4),
+        ("N", 4),
+        ("M", 4),
+        ("Comma", 4),
+        ("Period", 4),
+        ("Slash", 4),
+        ("RightShift", 11),
+    ],
+    &[
+        ("LeftControl", 5),
+        ("LeftMeta", 5),
+        ("LeftAlt", 5),
+        ("Space", 30),
+        ("RightAlt", 5),
+        ("RightMeta", 5),
+        ("RightControl", 5),
+    ],
+];
+
+/// Heat colors from unused to most pressed
+const HEAT_COLORS: [Color; 6] = [
+    Color::DarkGray,
+    Color::Blue,
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Red,
+];
+
+/// Everything one frame shows.
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    pub snapshot: HashMap<&'static str, u64>,
+    pub uptime: Duration,
+    pub paused: bool,
+}
+
+impl Dashboard {
+    pub fn capture(keys: &KeyStatistics, started: DateTime<Local>) -> crate::error::Result<Self> {
+        Ok(Self {
+            snapshot: stats::get_statistics_snapshot(keys)?,
+            uptime: (clock::now() - started).to_std().unwrap_or_default(),
+            paused: platform_common::is_paused(),
+        })
+    }
+}
+
+/// Text shown on a heatmap cell
+fn short_label(label: &str) -> &str {
+    match label {
+        "Grave" => "`",
+        "Minus" => "-",
+        "Equal" => "=",
+        "LeftBracket" => "[",
+        "RightBracket" => "]",
+        "Backslash" => "\\",
+        "Semicolon" => ";",
+        "Apostrophe" => "'",
+        "Comma" => ",",
+        "Period" => ".",
+        "Slash" => "/",
+        "Backspace" => "Bksp",
+        "CapsLock" => "Caps",
+        "LeftShift" | "RightShift" => "Shift",
+        "LeftControl" | "RightControl" => "Ctrl",
+        "LeftMeta" | "RightMeta" => "Meta",
+        "LeftAlt" | "RightAlt" => "Alt",
+        other => other,
+    }
+}
+
+/// Color for `count`, scaled so the most pressed key is the hottest
+fn heat_color(count: u64, max: u64) -> Color {
+    if count == 0 || max == 0 {
+        return HEAT_COLORS[0];
+    }
+    let steps = (HEAT_COLORS.len() - 1) as u64;
+    // 1..=steps, so any pressed key is distinguishable from an unused one
+    let level = (count * steps).div_ceil(max).clamp(1, steps);
+    HEAT_COLORS[level as usize]
+}
+
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Draws the whole dashboard into `frame`.
+pub fn render(frame: &mut Frame, dashboard: &Dashboard) {
+    let [header, heatmap, table] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(HEATMAP_ROWS.len() as u16 + 2),
+        Constraint::Min(3),
+    ])
+    .areas(frame.area());
+
+    render_header(frame, header, dashboard);
+    render_heatmap(frame, heatmap, dashboard);
+    render_top_keys(frame, table, dashboard);
+}
+
+fn render_header(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let total: u64 = dashboard.snapshot.values().sum();
+    let state = if dashboard.paused {
+        Span::styled(
+            "PAUSED",
+            Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::styled("recording", Style::new().fg(Color::Green))
+    };
+    let line = Line::from(vec![
+        Span::styled("key_logger", Style::new().add_modifier(Modifier::BOLD)),
+        Span::raw(format!(
+            "  {total} presses  {} keys  uptime {}  ",
+            dashboard.snapshot.len(),
+            format_uptime(dashboard.uptime)
+        )),
+        state,
+        Span::raw("  (q to quit)"),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn render_heatmap(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let max = dashboard.snapshot.values().copied().max().unwrap_or(0);
+    let lines: Vec<Line> = HEATMAP_ROWS
+        .iter()
+        .map(|row| {
+            let spans: Vec<Span> = row
+                .iter()
+                .map(|&(label, width)| {
+                    let count = dashboard.snapshot.get(label).copied().unwrap_or(0);
+                    let text = format!("{:^width$}", short_label(label), width = width as usize);
+                    let style = Style::new().fg(Color::Black).bg(heat_color(count, max));
+                    Span::styled(text, style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+    let block = Block::bordered().title(" Heatmap ");
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_top_keys(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let total: u64 = dashboard.snapshot.values().sum();
+    let rows: Vec<Row> = stats::top_keys(&dashboard.snapshot, TOP_KEYS)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (key, count))| {
+            let share = count as f64 * 100.0 / total as f64;
+            Row::new(vec![
+                format!("{}", i + 1),
+                key.to_string(),
+                count.to_string(),
+                format!("{share:.1}%"),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Length(7),
+        ],
+    )
+    .header(
+        Row::new(["#", "Key", "Count", "Share"]).style(Style::new().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title(" Top keys "));
+    frame.render_widget(table, area);
+}
+
+/// Takes over the terminal and redraws the dashboard until exit.
+///
+/// The terminal is in raw mode, so `q`, `Esc` and `Ctrl+C` are read here and stop the
+/// logger like an exit signal would.
+/// Logging is muted while the dashboard is shown, since log lines would tear through it.
+pub fn spawn_dashboard(
+    keys: KeyStatistics,
+    started: DateTime<Local>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+    thread::Builder::new().name("tui".into()).spawn(move || {
+        let mut terminal = match ratatui::try_init() {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                error!("Failed to start the dashboard: {e}");
+                return;
+            }
+        };
+        let log_level = log::max_level();
+        log::set_max_level(LevelFilter::Off);
+
+        let result = run(&mut terminal, &keys, started);
+        let restored = ratatui::try_restore();
+        log::set_max_level(log_level);
+        if let Err(e) = result.and(restored) {
+            error!("Dashboard failed: {e}");
+        }
+    })
+}
+
+fn run(
+    terminal: &mut ratatui::DefaultTerminal,
+    keys: &KeyStatistics,
+    started: DateTime<Local>,
+) -> std::io::Result<()> {
+    while !platform_common::should_exit() {
+        let dashboard = Dashboard::capture(keys, started).map_err(std::io::Error::other)?;
+        terminal.draw(|frame| render(frame, &dashboard))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                platform_common::request_exit();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{Terminal, backend::TestBackend, buffer::Buffer};
+
+    fn draw(dashboard: &Dashboard) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(64, 24)).unwrap();
+        terminal.draw(|frame| render(frame, dashboard)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn line(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect()
+    }
+
+    /// Buffer column where `needle` starts in `line` (borders are multi-byte)
+    fn column(line: &str, needle: &str) -> u16 {
+        line[..line.find(needle).unwrap()].chars().count() as u16
+    }
+
+    #[test]
+    fn test_heatmap_rows_are_equal_width() {
+        for row in HEATMAP_ROWS {
+            assert_eq!(row.iter().map(|&(_, w)| w).sum::<u16>(), 60);
+            for &(label, _) in row {
+                assert_eq!(stats::intern_key_label(label), Some(label));
+            }
+        }
+    }
+
+    #[test]
+    fn test_heat_color() {
+        assert_eq!(heat_color(0, 100), Color::DarkGray);
+        assert_eq!(heat_color(1, 100), Color::Blue);
+        assert_eq!(heat_color(100, 100), Color::Red);
+        assert_eq!(heat_color(0, 0), Color::DarkGray);
+    }
+
+    #[test]
+    fn test_render() {
+        let dashboard = Dashboard {
+            snapshot: HashMap::from([("E", 30), ("T", 20), ("Space", 50)]),
+            uptime: Duration::from_secs(3725),
+            paused: true,
+        };
+        let buffer = draw(&dashboard);
+
+        let header = line(&buffer, 0);
+        assert!(header.contains("100 presses"), "{header}");
+        assert!(header.contains("uptime 01:02:05"), "{header}");
+        assert!(header.contains("PAUSED"), "{header}");
+
+        // Heatmap rows start inside the border at y = 2
+        let qwerty = line(&buffer, 3);
+        assert!(qwerty.contains(" Q   W   E   R   T "), "{qwerty}");
+        let e_x = column(&qwerty, " E ") + 1;
+        assert_eq!(buffer[(e_x, 3)].bg, Color::Green);
+        let q_x = column(&qwerty, " Q ") + 1;
+        assert_eq!(buffer[(q_x, 3)].bg, Color::DarkGray);
+        let space_row = line(&buffer, 6);
+        let space_x = column(&space_row, "Space");
+        assert_eq!(buffer[(space_x, 6)].bg, Color::Red);
+
+        // Top keys, most pressed first
+        let table: Vec<String> = (9..buffer.area.height).map(|y| line(&buffer, y)).collect();
+        let space = table.iter().position(|l| l.contains("Space")).unwrap();
+        let e = table.iter().position(|l| l.contains(" E ")).unwrap();
+        let t = table.iter().position(|l| l.contains(" T ")).unwrap();
+        assert!(space < e && e < t);
+        assert!(table[space].contains("50.0%"), "{}", table[space]);
+    }
+
+    #[test]
+    fn test_render_empty() {
+        let buffer = draw(&Dashboard::default());
+        assert!(line(&buffer, 0).contains("0 presses"));
+        assert!(line(&buffer, 0).contains("recording"));
+    }
+}
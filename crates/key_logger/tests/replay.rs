@@ -0,0 +1,225 @@
+//! Runs the real binary in replay mode, so the whole pipeline is covered without a keyboard.
+
+use std::{collections::HashMap, path::Path, process::Command};
+use tempfile::TempDir;
+
+fn key_logger(output_dir: &Path, args: &[&str]) -> std::process::Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_key_logger"));
+    // Keep the caller's environment from changing what gets recorded
+    for (name, _) in std::env::vars() {
+        if name.starts_with("KEY_LOGGER_") {
+            cmd.env_remove(name);
+        }
+    }
+    cmd.arg("--output-dir").arg(output_dir).args(args);
+    cmd.output().expect("run key_logger")
+}
+
+fn read_rows(path: &Path) -> Vec<Vec<String>> {
+    let mut reader = csv::Reader::from_path(path).unwrap();
+    reader
+        .records()
+        .map(|r| r.unwrap().iter().map(str::to_string).collect())
+        .collect()
+}
+
+#[test]
+fn test_text_replay_exports_csv() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.txt");
+    std::fs::write(&script, "Hello, world!\n").unwrap();
+    let out = temp_dir.path().join("out");
+
+    let output = key_logger(
+        &out,
+        &[
+            "--replay",
+            script.to_str().unwrap(),
+            "--filename-pattern",
+            "replay",
+            "--bigrams",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+
+    let counts: HashMap<String, u64> = read_rows(&out.join("replay.csv"))
+        .into_iter()
+        .map(|row| (row[0].clone(), row[1].parse().unwrap()))
+        .collect();
+    assert_eq!(counts["L"], 3);
+    assert_eq!(counts["O"], 2);
+    assert_eq!(counts["LeftShift"], 2);
+    assert_eq!(counts["Comma"], 1);
+    assert_eq!(counts["1"], 1);
+    assert_eq!(counts["Enter"], 1);
+    assert_eq!(counts.values().sum::<u64>(), 16);
+
+    let metadata: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(out.join("replay.meta.json")).unwrap())
+            .unwrap();
+    assert_eq!(metadata["backend"], "replay");
+    assert_eq!(metadata["layout"], "US");
+    assert_eq!(metadata["schema_version"], 1);
+
+    let bigram_files: Vec<_> = std::fs::read_dir(&out)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("keylog_bigrams_")
+        })
+        .collect();
+    assert_eq!(bigram_files.len(), 1);
+    let bigrams = read_rows(&bigram_files[0]);
+    assert!(bigrams.contains(&vec!["L".to_string(), "L".to_string(), "1".to_string()]));
+}
+
+#[test]
+fn test_json_replay_respects_exclusions() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.json");
+    std::fs::write(
+        &script,
+        r#"[
+            {"key": "Space", "kind": "press", "time_ms": 0},
+            {"key": "Space", "kind": "repeat", "time_ms": 500},
+            {"key": "Space", "kind": "repeat", "time_ms": 533},
+            {"key": "Space", "kind": "release", "time_ms": 560},
+            {"key": "Escape", "kind": "press", "time_ms": 200},
+            {"key": "Escape", "kind": "release", "time_ms": 260}
+        ]"#,
+    )
+    .unwrap();
+    let out = temp_dir.path().join("out");
+
+    let output = key_logger(
+        &out,
+        &[
+            "--replay",
+            script.to_str().unwrap(),
+            "--filename-pattern",
+            "replay",
+            "--exclude",
+            "Escape",
+            "--metadata=false",
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+
+    let rows = read_rows(&out.join("replay.csv"));
+    assert_eq!(rows, vec![vec!["Space", "1", "2"]]);
+    assert!(!out.join("replay.meta.json").exists());
+}
+
+#[test]
+fn test_invalid_script_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.json");
+    std::fs::write(
+        &script,
+        r#"[{"key": "Nope", "kind": "press", "time_ms": 0}]"#,
+    )
+    .unwrap();
+    let out = temp_dir.path().join("out");
+
+    let output = key_logger(&out, &["--replay", script.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown key 'Nope'"));
+    assert!(!out.exists());
+}
+
+#[test]
+fn test_replay_leaves_checkpoint_alone() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.txt");
+    std::fs::write(&script, "abc").unwrap();
+    let out = temp_dir.path().join("out");
+    let args = [
+        "--replay",
+        script.to_str().unwrap(),
+        "--filename-pattern",
+        "replay",
+        "--autosave-secs",
+        "1",
+    ];
+
+    let output = key_logger(&out, &args);
+    assert!(output.status.success(), "{output:?}");
+    assert!(!out.join(".keylog.checkpoint").exists());
+
+    // A live run's checkpoint is neither restored into the replay nor replaced or removed
+    let checkpoint = "Key,Count,Repeat\nZ,5,0\n";
+    std::fs::write(out.join(".keylog.checkpoint"), checkpoint).unwrap();
+    std::fs::remove_file(out.join("replay.csv")).unwrap();
+    let output = key_logger(&out, &args);
+    assert!(output.status.success(), "{output:?}");
+    assert!(
+        read_rows(&out.join("replay.csv"))
+            .iter()
+            .all(|row| row[0] != "Z")
+    );
+    assert_eq!(
+        std::fs::read_to_string(out.join(".keylog.checkpoint")).unwrap(),
+        checkpoint
+    );
+}
+
+#[test]
+fn test_replay_runs_next_to_live_logger() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.txt");
+    std::fs::write(&script, "abc").unwrap();
+    let out = temp_dir.path().join("out");
+    std::fs::create_dir_all(&out).unwrap();
+
+    // Stand in for a live logger holding the instance lock
+    let lock = std::fs::File::create(out.join("key_logger.lock")).unwrap();
+    lock.lock().unwrap();
+    let output = key_logger(&out, &["--replay", script.to_str().unwrap()]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_rotation_names_files_by_period() {
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("script.txt");
+    std::fs::write(&script, "abc").unwrap();
+    let out = temp_dir.path().join("out");
+
+    // Pinned, so both runs fall in the same period even when the test runs across midnight
+    let start = "2025-07-01T12:00:00Z";
+    let day = chrono::DateTime::parse_from_rfc3339(start)
+        .unwrap()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d")
+        .to_string();
+    for _ in 0..2 {
+        let output = key_logger(
+            &out,
+            &[
+                "--replay",
+                script.to_str().unwrap(),
+                "--rotate",
+                "daily",
+                "--replay-start",
+                start,
+            ],
+        );
+        assert!(output.status.success(), "{output:?}");
+    }
+
+    // The second run of the same day gets its own file instead of overwriting the first
+    assert_eq!(read_rows(&out.join(format!("keylog_{day}.csv"))).len(), 3);
+    assert_eq!(read_rows(&out.join(format!("keylog_{day}.2.csv"))).len(), 3);
+    let metadata: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(out.join(format!("keylog_{day}.meta.json"))).unwrap(),
+    )
+    .unwrap();
+    let session_start =
+        chrono::DateTime::parse_from_rfc3339(metadata["session_start"].as_str().unwrap()).unwrap();
+    assert!(
+        (session_start - chrono::DateTime::parse_from_rfc3339(start).unwrap()).num_seconds() < 60
+    );
+}
@@ -0,0 +1,393 @@
+//! Authenticated at-rest encryption shared by `key_logger` and `analyzer`.
+//!
+//! `key_logger` encrypts its output with [`encrypt`] and `analyzer` reads it back with
+//! [`decrypt`], so both sides always agree on the format. Files are sealed with
+//! XChaCha20-Poly1305 under either a raw 256-bit key from a key file or a key derived from a
+//! passphrase with Argon2id.
+//!
+//! Layout (integers little-endian):
+//!
+//! ```text
+//! "KLOGENC" | version: u8 | kdf: u8 | [m_cost: u32 | t_cost: u32 | p_cost: u32 | salt: 16]
+//!           | nonce: 24 | ciphertext + tag: 16
+//! ```
+//!
+//! The bracketed part is only present for passphrases. Everything before the ciphertext is
+//! authenticated too, so a modified header fails like a modified body.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload, rand_core::RngCore},
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Appended to the name of an encrypted file, e.g. `keylog_2025-07-27.csv.enc`
+pub const ENCRYPTED_EXTENSION: &str = "enc";
+
+const MAGIC: &[u8; 7] = b"KLOGENC";
+const FORMAT_VERSION: u8 = 1;
+const KDF_NONE: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const ARGON2_PARAMS_LEN: usize = 12;
+
+// Upper bound for the memory cost read from a file header (1 GiB), so a crafted file cannot
+// make the reader allocate without limit
+const MAX_ARGON2_M_COST_KIB: u32 = 1 << 20;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid key file {path}: {reason}")]
+    InvalidKeyFile { path: PathBuf, reason: String },
+
+    #[error("Passphrase file {0} is empty")]
+    EmptyPassphrase(PathBuf),
+
+    #[error("Data was not encrypted by key_logger")]
+    NotEncrypted,
+
+    #[error("Unsupported encryption format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Data was encrypted with a {expected}, but a {given} was given")]
+    WrongSecretKind {
+        expected: &'static str,
+        given: &'static str,
+    },
+
+    #[error("Encrypted data is truncated or has an invalid header")]
+    InvalidHeader,
+
+    #[error("Decryption failed: wrong key or passphrase, or the data was modified")]
+    Decrypt,
+
+    #[error("Encryption failed")]
+    Encrypt,
+
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What the encryption key comes from.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Secret {
+    /// 256-bit key used as is
+    Key([u8; KEY_LEN]),
+    /// Stretched into a key with Argon2id and a per-file salt
+    Passphrase(String),
+}
+
+// Never print the secret itself, e.g. in a `{:#?}` dump of a configuration
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(_) => f.write_str("Secret::Key(..)"),
+            Self::Passphrase(_) => f.write_str("Secret::Passphrase(..)"),
+        }
+    }
+}
+
+impl Secret {
+    /// Reads a key file holding 64 hex digits (e.g. from `openssl rand -hex 32`).
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let content = read_file(path)?;
+        let invalid = |reason: &str| Error::InvalidKeyFile {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        };
+
+        let hex = content.trim();
+        if hex.len() != KEY_LEN * 2 {
+            return Err(invalid("expected 64 hex digits"));
+        }
+        let mut key = [0u8; KEY_LEN];
+        for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid("not hex"))?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid("not hex"))?;
+        }
+        Ok(Self::Key(key))
+    }
+
+    /// Reads a passphrase from the first line of a file; the line ending is not part of it.
+    pub fn from_passphrase_file(path: &Path) -> Result<Self> {
+        let content = read_file(path)?;
+        let passphrase = content.lines().next().unwrap_or_default();
+        if passphrase.is_empty() {
+            return Err(Error::EmptyPassphrase(path.to_path_buf()));
+        }
+        Ok(Self::Passphrase(passphrase.to_string()))
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Key(_) => "key file",
+            Self::Passphrase(_) => "passphrase",
+        }
+    }
+}
+
+/// Whether `data` starts like something [`encrypt`] produced.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Seals `plaintext` with a fresh nonce (and salt, for a passphrase).
+pub fn encrypt(secret: &Secret, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(MAGIC.len() + 2 + ARGON2_PARAMS_LEN + SALT_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(FORMAT_VERSION);
+
+    let key = match secret {
+        Secret::Key(key) => {
+            header.push(KDF_NONE);
+            *key
+        }
+        Secret::Passphrase(passphrase) => {
+            let params = Params::default();
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+
+            header.push(KDF_ARGON2ID);
+            header.extend_from_slice(&params.m_cost().to_le_bytes());
+            header.extend_from_slice(&params.t_cost().to_le_bytes());
+            header.extend_from_slice(&params.p_cost().to_le_bytes());
+            header.extend_from_slice(&salt);
+            derive_key(passphrase, &salt, params)?
+        }
+    };
+
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    header.extend_from_slice(&nonce);
+
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| Error::Encrypt)?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Opens data sealed by [`encrypt`], verifying that neither header nor body was modified.
+pub fn decrypt(secret: &Secret, data: &[u8]) -> Result<Vec<u8>> {
+    if !is_encrypted(data) {
+        return Err(Error::NotEncrypted);
+    }
+    let mut reader = HeaderReader {
+        data,
+        pos: MAGIC.len(),
+    };
+
+    let version = reader.byte()?;
+    if version != FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let key = match (reader.byte()?, secret) {
+        (KDF_NONE, Secret::Key(key)) => *key,
+        (KDF_ARGON2ID, Secret::Passphrase(passphrase)) => {
+            let m_cost = reader.u32()?;
+            let t_cost = reader.u32()?;
+            let p_cost = reader.u32()?;
+            if m_cost > MAX_ARGON2_M_COST_KIB {
+                return Err(Error::InvalidHeader);
+            }
+            let params = Params::new(m_cost, t_cost, p_cost, Some(KEY_LEN))
+                .map_err(|_| Error::InvalidHeader)?;
+            derive_key(passphrase, reader.take(SALT_LEN)?, params)?
+        }
+        (KDF_NONE, _) => {
+            return Err(Error::WrongSecretKind {
+                expected: "key file",
+                given: secret.kind(),
+            });
+        }
+        (KDF_ARGON2ID, _) => {
+            return Err(Error::WrongSecretKind {
+                expected: "passphrase",
+                given: secret.kind(),
+            });
+        }
+        _ => return Err(Error::InvalidHeader),
+    };
+
+    let nonce = XNonce::from_slice(reader.take(NONCE_LEN)?);
+    let (header, ciphertext) = data.split_at(reader.pos);
+    if ciphertext.len() < TAG_LEN {
+        return Err(Error::InvalidHeader);
+    }
+
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| Error::Decrypt)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: Params) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::Kdf(e.to_string()))?;
+    Ok(key)
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| Error::Read {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+struct HeaderReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(Error::InvalidHeader)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CSV: &[u8] = b"Key,Count,Repeat\nE,189,0\n";
+
+    fn key() -> Secret {
+        Secret::Key([7; KEY_LEN])
+    }
+
+    fn passphrase() -> Secret {
+        Secret::Passphrase("correct horse battery staple".to_string())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for secret in [key(), passphrase()] {
+            let sealed = encrypt(&secret, CSV).unwrap();
+            assert!(is_encrypted(&sealed));
+            assert!(!sealed.windows(3).any(|w| w == b"189"));
+            assert_eq!(decrypt(&secret, &sealed).unwrap(), CSV);
+        }
+        // Fresh nonce every time
+        assert_ne!(encrypt(&key(), CSV).unwrap(), encrypt(&key(), CSV).unwrap());
+    }
+
+    #[test]
+    fn test_wrong_secret_rejected() {
+        let sealed = encrypt(&key(), CSV).unwrap();
+        assert!(matches!(
+            decrypt(&Secret::Key([8; KEY_LEN]), &sealed),
+            Err(Error::Decrypt)
+        ));
+        assert!(matches!(
+            decrypt(&passphrase(), &sealed),
+            Err(Error::WrongSecretKind { .. })
+        ));
+
+        let sealed = encrypt(&passphrase(), CSV).unwrap();
+        let other = Secret::Passphrase("hunter2".to_string());
+        assert!(matches!(decrypt(&other, &sealed), Err(Error::Decrypt)));
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let sealed = encrypt(&passphrase(), CSV).unwrap();
+        // Every byte after the magic is authenticated: version, KDF parameters, salt,
+        // nonce and body
+        for index in [MAGIC.len() + 3, MAGIC.len() + 20, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 1;
+            assert!(decrypt(&passphrase(), &tampered).is_err(), "byte {index}");
+        }
+
+        assert!(matches!(decrypt(&key(), CSV), Err(Error::NotEncrypted)));
+        let sealed = encrypt(&key(), CSV).unwrap();
+        assert!(matches!(
+            decrypt(&key(), &sealed[..MAGIC.len() + 10]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_secret_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("secret");
+
+        std::fs::write(&path, format!("{}\n", "0f".repeat(KEY_LEN))).unwrap();
+        assert_eq!(
+            Secret::from_key_file(&path).unwrap(),
+            Secret::Key([0x0f; KEY_LEN])
+        );
+        for invalid in ["0f0f", &"zz".repeat(KEY_LEN), &"é".repeat(KEY_LEN)] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(
+                matches!(
+                    Secret::from_key_file(&path),
+                    Err(Error::InvalidKeyFile { .. })
+                ),
+                "{invalid}"
+            );
+        }
+
+        std::fs::write(&path, "open sesame\r\nignored\n").unwrap();
+        assert_eq!(
+            Secret::from_passphrase_file(&path).unwrap(),
+            Secret::Passphrase("open sesame".to_string())
+        );
+        std::fs::write(&path, "\n").unwrap();
+        assert!(matches!(
+            Secret::from_passphrase_file(&path),
+            Err(Error::EmptyPassphrase(_))
+        ));
+
+        assert_eq!(format!("{:?}", key()), "Secret::Key(..)");
+    }
+}